@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE newsletter_issue_events (
+    event_id uuid NOT NULL,
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL,
+    event_type TEXT NOT NULL CHECK (event_type IN ('open', 'click')),
+    link_url TEXT NULL,
+    occurred_at timestamptz NOT NULL,
+    PRIMARY KEY (event_id)
+);
+
+CREATE INDEX newsletter_issue_events_issue_idx
+    ON newsletter_issue_events (newsletter_issue_id, event_type);
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE newsletter_issues
+    ADD COLUMN delivery_status TEXT NOT NULL DEFAULT 'active'
+    CHECK (delivery_status IN ('active', 'paused', 'cancelled'));
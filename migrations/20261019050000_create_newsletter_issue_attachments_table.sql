@@ -0,0 +1,18 @@
+-- Add migration script here
+-- Attachments are uploaded before the issue exists: they stay pending
+-- (newsletter_issue_id IS NULL) until their uploader publishes an issue.
+CREATE TABLE newsletter_issue_attachments (
+    attachment_id uuid NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    newsletter_issue_id uuid NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    file_name TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    content_id TEXT NULL UNIQUE,
+    content BYTEA NOT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY (attachment_id)
+);
+
+CREATE INDEX newsletter_issue_attachments_issue_idx
+    ON newsletter_issue_attachments (newsletter_issue_id);
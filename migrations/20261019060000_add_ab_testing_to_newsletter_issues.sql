@@ -0,0 +1,21 @@
+-- Add migration script here
+CREATE TABLE newsletter_issue_variants (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    variant TEXT NOT NULL CHECK (variant IN ('a', 'b')),
+    title TEXT NOT NULL,
+    -- NULL means "same body as the issue"
+    text_content TEXT NULL,
+    html_content TEXT NULL,
+    recipients INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (newsletter_issue_id, variant)
+);
+
+ALTER TABLE newsletter_issues ADD COLUMN ab_test_ends_at timestamptz NULL;
+ALTER TABLE newsletter_issues ADD COLUMN ab_test_winner TEXT NULL;
+
+-- Test recipients get a variant, the rest of the list is held
+-- (variant IS NULL) until a winner has been picked.
+ALTER TABLE issue_delivery_queue ADD COLUMN variant TEXT NULL;
+
+ALTER TABLE newsletter_issue_events ADD COLUMN variant TEXT NULL;
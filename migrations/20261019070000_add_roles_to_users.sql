@@ -0,0 +1,17 @@
+-- Add migration script here
+-- Existing accounts keep full access.
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+    CHECK (role IN ('owner', 'editor', 'viewer'));
+ALTER TABLE users ALTER COLUMN role DROP DEFAULT;
+ALTER TABLE users ADD COLUMN email TEXT NULL UNIQUE;
+ALTER TABLE users ADD COLUMN active BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Invited users have no password until they accept their invitation.
+ALTER TABLE users ALTER COLUMN password_hash DROP NOT NULL;
+
+-- Tokens are stored as their SHA-256 digest, like password reset tokens.
+CREATE TABLE user_invitations (
+    invitation_token_hash TEXT NOT NULL PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    expires_at timestamptz NOT NULL
+);
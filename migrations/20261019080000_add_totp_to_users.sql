@@ -0,0 +1,13 @@
+-- Add migration script here
+-- Base32 TOTP secret, set once the user has confirmed their enrolment.
+ALTER TABLE users ADD COLUMN totp_secret TEXT NULL;
+-- Time step of the last accepted code, to prevent replays.
+ALTER TABLE users ADD COLUMN totp_last_used_step BIGINT NULL;
+
+CREATE TABLE user_recovery_codes (
+    recovery_code_id uuid NOT NULL PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    -- Argon2 PHC string
+    code_hash TEXT NOT NULL,
+    used_at timestamptz NULL
+);
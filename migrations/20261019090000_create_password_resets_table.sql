@@ -0,0 +1,7 @@
+-- Add migration script here
+-- Tokens are stored as their SHA-256 digest, like API tokens.
+CREATE TABLE password_resets (
+    reset_token_hash TEXT NOT NULL PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    expires_at timestamptz NOT NULL
+);
\ No newline at end of file
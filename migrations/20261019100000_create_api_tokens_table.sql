@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE api_tokens (
+    api_token_id uuid NOT NULL PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (user_id),
+    name TEXT NOT NULL,
+    -- Hex-encoded SHA-256 of the token, which is only shown once.
+    token_hash TEXT NOT NULL UNIQUE,
+    -- The role the token acts with, at most the role of its user.
+    scope TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    last_used_at timestamptz NULL,
+    revoked_at timestamptz NULL
+);
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
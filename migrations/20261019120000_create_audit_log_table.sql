@@ -0,0 +1,13 @@
+-- Add migration script here
+-- Who did what, and from where. `user_id` is NULL for anonymous actions,
+-- e.g. subscribing.
+CREATE TABLE audit_log (
+    audit_log_id uuid NOT NULL PRIMARY KEY,
+    user_id uuid NULL REFERENCES users (user_id),
+    action TEXT NOT NULL,
+    target TEXT NULL,
+    ip_address TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+CREATE INDEX audit_log_created_at_idx ON audit_log (created_at);
+CREATE INDEX audit_log_user_id_idx ON audit_log (user_id);
@@ -0,0 +1,4 @@
+-- Add migration script here
+-- A hash of the request an idempotency key was used for, to reject the
+-- reuse of the key for another request. NULL for existing keys.
+ALTER TABLE idempotency ADD COLUMN request_fingerprint BYTEA;
@@ -0,0 +1,2 @@
+-- The W3C traceparent of the request that enqueued the task.
+ALTER TABLE issue_delivery_queue ADD COLUMN trace_context TEXT NULL;
@@ -0,0 +1,208 @@
+//! src/ab_testing.rs
+use crate::configuration::{AbTestSettings, Settings, TrackingSettings};
+use crate::startup::get_connection_pool;
+use anyhow::Context;
+use sqlx::{Executor, PgPool};
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub struct VariantResult {
+    pub variant: String,
+    pub recipients: i64,
+    pub unique_opens: i64,
+}
+
+/// Pick the variant with the highest open rate.
+///
+/// Ties go to the variant listed first.
+///
+/// # Examples
+///
+/// ```
+/// use zero2prod::ab_testing::{VariantResult, pick_winner};
+/// use assert2::assert;
+///
+/// let results = [
+///     VariantResult { variant: "a".into(), recipients: 10, unique_opens: 2 },
+///     VariantResult { variant: "b".into(), recipients: 5, unique_opens: 2 },
+/// ];
+/// assert!(pick_winner(&results) == Some("b"));
+/// ```
+pub fn pick_winner(results: &[VariantResult]) -> Option<&str> {
+    let mut winner: Option<&VariantResult> = None;
+    for result in results {
+        let better = match winner {
+            None => true,
+            // a/b > c/d without dividing by zero
+            Some(best) => {
+                i128::from(result.unique_opens) * i128::from(best.recipients.max(1))
+                    > i128::from(best.unique_opens) * i128::from(result.recipients.max(1))
+            }
+        };
+        if better {
+            winner = Some(result);
+        }
+    }
+    winner.map(|w| w.variant.as_str())
+}
+
+/// Pick a winner for every A/B test whose wait window has elapsed, which
+/// releases the deliveries held back for the rest of the list.
+///
+/// Without tracking there are no opens to decide on: the rest of the list
+/// gets the original subject line.
+#[tracing::instrument(skip_all)]
+pub async fn finish_ab_tests(
+    pool: &PgPool,
+    tracking: &TrackingSettings,
+) -> Result<(), anyhow::Error> {
+    loop {
+        let mut transaction = pool.begin().await?;
+        let issue = sqlx::query!(
+            r#"
+            SELECT newsletter_issue_id
+            FROM newsletter_issues
+            WHERE ab_test_ends_at <= now() AND ab_test_winner IS NULL
+            FOR UPDATE
+            SKIP LOCKED
+            LIMIT 1
+            "#,
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .context("Failed to look for finished A/B tests")?;
+        let Some(issue) = issue else {
+            return Ok(());
+        };
+        let issue_id = issue.newsletter_issue_id;
+
+        let winner = if tracking.enabled {
+            let results = get_variant_results(&mut *transaction, issue_id).await?;
+            let winner = pick_winner(&results).unwrap_or("a").to_string();
+            tracing::info!(newsletter_issue_id = %issue_id, ?results, winner, "A/B test finished");
+            winner
+        } else {
+            tracing::info!(newsletter_issue_id = %issue_id, "A/B test finished without tracking");
+            "a".to_string()
+        };
+
+        let query = sqlx::query!(
+            r#"
+            UPDATE newsletter_issues
+            SET ab_test_winner = $2
+            WHERE newsletter_issue_id = $1
+            "#,
+            issue_id,
+            winner
+        );
+        transaction
+            .execute(query)
+            .await
+            .context("Failed to store the A/B test winner")?;
+        transaction.commit().await?;
+    }
+}
+
+async fn ab_test_loop(
+    pool: PgPool,
+    tracking: TrackingSettings,
+    settings: AbTestSettings,
+) -> Result<(), anyhow::Error> {
+    loop {
+        if let Err(e) = finish_ab_tests(&pool, &tracking).await {
+            tracing::error!(
+            error.cause_chain= ?e,
+            error.message= %e,
+                "Failed to pick the winners of finished A/B tests",
+            );
+        }
+        tokio::time::sleep(settings.check_interval()).await;
+    }
+}
+
+pub async fn run_ab_tests_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    ab_test_loop(
+        connection_pool,
+        configuration.tracking,
+        configuration.ab_testing,
+    )
+    .await
+}
+
+/// Opens reported for each variant of an issue, ordered by variant.
+#[tracing::instrument(skip(executor))]
+pub async fn get_variant_results<'e, E>(
+    executor: E,
+    issue_id: Uuid,
+) -> Result<Vec<VariantResult>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'e>,
+{
+    let results = sqlx::query_as!(
+        VariantResult,
+        r#"
+        SELECT
+            v.variant,
+            v.recipients::bigint as "recipients!",
+            (
+                SELECT COUNT(DISTINCT e.subscriber_id)
+                FROM newsletter_issue_events e
+                WHERE
+                    e.newsletter_issue_id = v.newsletter_issue_id AND
+                    e.variant = v.variant AND
+                    e.event_type = 'open'
+            ) as "unique_opens!"
+        FROM newsletter_issue_variants v
+        WHERE v.newsletter_issue_id = $1
+        ORDER BY v.variant
+        "#,
+        issue_id
+    )
+    .fetch_all(executor)
+    .await
+    .context("Failed to retrieve the A/B test results")?;
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VariantResult, pick_winner};
+
+    fn result(variant: &str, recipients: i64, unique_opens: i64) -> VariantResult {
+        VariantResult {
+            variant: variant.into(),
+            recipients,
+            unique_opens,
+        }
+    }
+
+    #[test]
+    fn the_highest_open_rate_wins() {
+        let results = [result("a", 100, 30), result("b", 100, 45)];
+        assert_eq!(pick_winner(&results), Some("b"));
+    }
+
+    #[test]
+    fn open_rates_account_for_group_sizes() {
+        let results = [result("a", 100, 30), result("b", 50, 20)];
+        assert_eq!(pick_winner(&results), Some("b"));
+    }
+
+    #[test]
+    fn ties_go_to_the_first_variant() {
+        let results = [result("a", 10, 0), result("b", 10, 0)];
+        assert_eq!(pick_winner(&results), Some("a"));
+    }
+
+    #[test]
+    fn variants_without_recipients_do_not_panic() {
+        let results = [result("a", 0, 0), result("b", 3, 1)];
+        assert_eq!(pick_winner(&results), Some("b"));
+    }
+
+    #[test]
+    fn no_variants_no_winner() {
+        assert_eq!(pick_winner(&[]), None);
+    }
+}
@@ -0,0 +1,144 @@
+//! src/audit_log.rs
+use crate::utils::client_ip;
+use actix_web::HttpRequest;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+/// What an audit log entry records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    LogIn,
+    LogOut,
+    ChangePassword,
+    ResetPassword,
+    ChangeEmail,
+    EnableTotp,
+    RevokeSession,
+    RevokeOtherSessions,
+    CreateApiToken,
+    RevokeApiToken,
+    InviteUser,
+    AcceptInvitation,
+    DeactivateUser,
+    PublishNewsletter,
+    PauseIssue,
+    ResumeIssue,
+    CancelIssue,
+    Subscribe,
+    ConfirmSubscription,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 19] = [
+        AuditAction::LogIn,
+        AuditAction::LogOut,
+        AuditAction::ChangePassword,
+        AuditAction::ResetPassword,
+        AuditAction::ChangeEmail,
+        AuditAction::EnableTotp,
+        AuditAction::RevokeSession,
+        AuditAction::RevokeOtherSessions,
+        AuditAction::CreateApiToken,
+        AuditAction::RevokeApiToken,
+        AuditAction::InviteUser,
+        AuditAction::AcceptInvitation,
+        AuditAction::DeactivateUser,
+        AuditAction::PublishNewsletter,
+        AuditAction::PauseIssue,
+        AuditAction::ResumeIssue,
+        AuditAction::CancelIssue,
+        AuditAction::Subscribe,
+        AuditAction::ConfirmSubscription,
+    ];
+
+    /// # Examples
+    ///
+    /// ```
+    /// use zero2prod::audit_log::AuditAction;
+    /// use assert2::assert;
+    ///
+    /// assert!(AuditAction::parse("publish_newsletter") == Ok(AuditAction::PublishNewsletter));
+    /// assert!(AuditAction::parse("drop_table").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<AuditAction, String> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.as_str() == s)
+            .ok_or_else(|| format!("{s} is not a valid audit action."))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::LogIn => "log_in",
+            AuditAction::LogOut => "log_out",
+            AuditAction::ChangePassword => "change_password",
+            AuditAction::ResetPassword => "reset_password",
+            AuditAction::ChangeEmail => "change_email",
+            AuditAction::EnableTotp => "enable_totp",
+            AuditAction::RevokeSession => "revoke_session",
+            AuditAction::RevokeOtherSessions => "revoke_other_sessions",
+            AuditAction::CreateApiToken => "create_api_token",
+            AuditAction::RevokeApiToken => "revoke_api_token",
+            AuditAction::InviteUser => "invite_user",
+            AuditAction::AcceptInvitation => "accept_invitation",
+            AuditAction::DeactivateUser => "deactivate_user",
+            AuditAction::PublishNewsletter => "publish_newsletter",
+            AuditAction::PauseIssue => "pause_issue",
+            AuditAction::ResumeIssue => "resume_issue",
+            AuditAction::CancelIssue => "cancel_issue",
+            AuditAction::Subscribe => "subscribe",
+            AuditAction::ConfirmSubscription => "confirm_subscription",
+        }
+    }
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Record that `user_id` - `None` for anonymous requests - performed
+/// `action` on `target`, e.g. the id of an issue.
+///
+/// Pass the transaction of the action when there is one, so that the entry
+/// is only kept if the action is.
+#[tracing::instrument(name = "Record audit event", skip(executor, request))]
+pub async fn record_audit_event<'e>(
+    executor: impl PgExecutor<'e>,
+    request: &HttpRequest,
+    user_id: Option<Uuid>,
+    action: AuditAction,
+    target: Option<String>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log (audit_log_id, user_id, action, target, ip_address, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        action.as_str(),
+        target,
+        client_ip(request),
+        Utc::now()
+    )
+    .execute(executor)
+    .await
+    .context("Failed to record the audit event")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AuditAction;
+
+    #[test]
+    fn actions_round_trip_through_their_name() {
+        for action in AuditAction::ALL {
+            assert_eq!(AuditAction::parse(action.as_str()), Ok(action));
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! src/authentication/api_token.rs
+use super::Role;
+use anyhow::Context;
+use chrono::Utc;
+use rand::distributions::Alphanumeric;
+use rand::{Rng, thread_rng};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// Makes tokens easy to recognise, e.g. by secret scanners.
+const TOKEN_PREFIX: &str = "z2p_";
+
+/// Create a personal API token for the user, acting with the `scope` role.
+///
+/// Returns the token itself, which is only stored hashed.
+#[tracing::instrument(name = "Issue API token", skip(transaction))]
+pub async fn issue_api_token(
+    user_id: Uuid,
+    name: &str,
+    scope: Role,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<SecretString, anyhow::Error> {
+    let token = generate_api_token();
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (api_token_id, user_id, name, token_hash, scope, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        name,
+        hash_api_token(&token),
+        scope.as_str(),
+        Utc::now()
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to store the API token")?;
+    Ok(token)
+}
+
+/// Returns the user the token belongs to and the role to act with, or
+/// `None` if the token is unknown, revoked or belongs to a deactivated user.
+///
+/// Tokens never grant more than the current role of their user.
+#[tracing::instrument(name = "Authenticate API token", skip(token, pool))]
+pub(super) async fn authenticate_api_token(
+    token: &SecretString,
+    pool: &PgPool,
+) -> Result<Option<(Uuid, Role)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE api_tokens t
+        SET last_used_at = now()
+        FROM users u
+        WHERE
+            t.token_hash = $1 AND
+            t.revoked_at IS NULL AND
+            u.user_id = t.user_id AND
+            u.active
+        RETURNING t.user_id, t.scope, u.role
+        "#,
+        hash_api_token(token)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the API token")?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let scope = Role::parse(&row.scope).map_err(anyhow::Error::msg)?;
+    let role = Role::parse(&row.role).map_err(anyhow::Error::msg)?;
+    Ok(Some((row.user_id, scope.min(role))))
+}
+
+fn generate_api_token() -> SecretString {
+    let mut rng = thread_rng();
+    let random: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(40)
+        .collect();
+    SecretString::from(format!("{TOKEN_PREFIX}{random}"))
+}
+
+/// Tokens are long and random, so a fast hash is enough: unlike passwords,
+/// they cannot be guessed from a dictionary.
+fn hash_api_token(token: &SecretString) -> String {
+    Sha256::digest(token.expose_secret().as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TOKEN_PREFIX, generate_api_token, hash_api_token};
+    use secrecy::ExposeSecret;
+
+    #[test]
+    fn tokens_are_prefixed_and_unique() {
+        let first = generate_api_token();
+        let second = generate_api_token();
+        assert!(first.expose_secret().starts_with(TOKEN_PREFIX));
+        assert_ne!(first.expose_secret(), second.expose_secret());
+    }
+
+    #[test]
+    fn hashes_are_stable_and_do_not_contain_the_token() {
+        let token = generate_api_token();
+        let hash = hash_api_token(&token);
+        assert_eq!(hash, hash_api_token(&token));
+        assert_eq!(hash.len(), 64);
+        assert!(!hash.contains(token.expose_secret()));
+    }
+}
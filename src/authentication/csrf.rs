@@ -0,0 +1,144 @@
+//! src/authentication/csrf.rs
+use crate::session_state::TypedSession;
+use crate::utils::e500;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::middleware::Next;
+use actix_web::web::{self, Bytes};
+use actix_web::{FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use std::collections::HashMap;
+use std::future::{Ready, ready};
+use subtle::ConstantTimeEq;
+
+const FIELD_NAME: &str = "csrf_token";
+const HEADER_NAME: &str = "X-CSRF-Token";
+
+/// The synchronizer token of the session, to embed in the forms of a page.
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    /// A hidden input carrying the token, for the forms of the page.
+    pub fn form_field(&self) -> String {
+        format!(
+            r#"<input hidden type="text" name="{FIELD_NAME}" value="{}">"#,
+            self.0
+        )
+    }
+}
+
+/// The token a multipart form must carry, left for its handler to check:
+/// the middleware cannot read the field without buffering the upload.
+struct PendingCsrfCheck(String);
+
+/// Check the `csrf_token` field of a multipart form, for the handlers of
+/// routes under `reject_cross_site_requests`.
+pub fn check_multipart_csrf_token(
+    req: &HttpRequest,
+    submitted: Option<&str>,
+) -> Result<(), actix_web::Error> {
+    let extensions = req.extensions();
+    // Already checked by the middleware, or authenticated with an API token.
+    let Some(PendingCsrfCheck(expected)) = extensions.get::<PendingCsrfCheck>() else {
+        return Ok(());
+    };
+    match submitted {
+        Some(submitted) if bool::from(expected.as_bytes().ct_eq(submitted.as_bytes())) => Ok(()),
+        _ => Err(csrf_rejection()),
+    }
+}
+
+impl FromRequest for CsrfToken {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<CsrfToken, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let token = TypedSession::from_request(req, payload)
+            .into_inner()
+            .and_then(|session| session.get_csrf_token().map_err(e500));
+        // Requests authenticated with an API token have no session: their
+        // pages get an empty token, which is never accepted.
+        ready(token.map(|token| CsrfToken(token.unwrap_or_default())))
+    }
+}
+
+/// Reject state-changing requests that do not carry the CSRF token of the
+/// session, as a header or a URL-encoded form field. The field of multipart
+/// forms is checked by their handler, with `check_multipart_csrf_token`.
+///
+/// Must be registered inside of `reject_anonymous_users`.
+pub async fn reject_cross_site_requests(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    // API tokens are not sent along automatically by browsers, so they
+    // cannot be abused by other sites.
+    if req.method().is_safe() || has_bearer_token(req.headers()) {
+        return next.call(req).await;
+    }
+
+    let expected = {
+        let (http_request, payload) = req.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?
+    .get_csrf_token()
+    .map_err(e500)?;
+
+    let mut submitted = req
+        .headers()
+        .get(HEADER_NAME)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string);
+    if submitted.is_none() && has_content_type(req.headers(), "application/x-www-form-urlencoded") {
+        let body = req.extract::<Bytes>().await?;
+        submitted = field_value(&body);
+        // Put the body back for the handler.
+        req.set_payload(Payload::from(body));
+    }
+    if submitted.is_none()
+        && has_content_type(req.headers(), "multipart/form-data")
+        && let Some(expected) = expected
+    {
+        req.extensions_mut().insert(PendingCsrfCheck(expected));
+        return next.call(req).await;
+    }
+
+    match (expected, submitted) {
+        (Some(expected), Some(submitted))
+            if bool::from(expected.as_bytes().ct_eq(submitted.as_bytes())) =>
+        {
+            next.call(req).await
+        }
+        _ => Err(csrf_rejection()),
+    }
+}
+
+fn csrf_rejection() -> actix_web::Error {
+    let response = HttpResponse::Forbidden()
+        .body("This form has expired or was not submitted from this site.");
+    let e = anyhow::anyhow!("The CSRF token is missing or invalid");
+    InternalError::from_response(e, response).into()
+}
+
+fn has_bearer_token(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.starts_with("Bearer "))
+}
+
+fn has_content_type(headers: &HeaderMap, content_type: &str) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.starts_with(content_type))
+}
+
+fn field_value(urlencoded: &[u8]) -> Option<String> {
+    let urlencoded = std::str::from_utf8(urlencoded).ok()?;
+    web::Query::<HashMap<String, String>>::from_query(urlencoded)
+        .ok()?
+        .into_inner()
+        .remove(FIELD_NAME)
+}
@@ -1,12 +1,22 @@
 //! src/authentication/middleware.rs
+use super::Role;
+use super::api_token::authenticate_api_token;
+use super::user_session::touch_user_session;
+use crate::configuration::SessionSettings;
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 use actix_web::FromRequest;
 use actix_web::HttpMessage;
-use actix_web::body::MessageBody;
+use actix_web::body::{EitherBody, MessageBody};
 use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::InternalError;
+use actix_web::http::header::{self, HeaderMap, HeaderValue};
 use actix_web::middleware::Next;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use chrono::{DateTime, Utc};
+use secrecy::SecretString;
+use sqlx::PgPool;
 use std::ops::Deref;
 use uuid::Uuid;
 
@@ -27,24 +37,245 @@ impl Deref for UserId {
     }
 }
 
+/// Marks requests authenticated with an API token rather than a session.
+#[derive(Debug, Clone, Copy)]
+struct ApiTokenAuthentication;
+
 pub async fn reject_anonymous_users(
     mut req: ServiceRequest,
     next: Next<impl MessageBody>,
-) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    // Machine clients authenticate with an API token instead of a session.
+    if let Some(token) = bearer_token(req.headers()) {
+        let pool = req
+            .app_data::<web::Data<PgPool>>()
+            .expect("The database pool is not registered as application data");
+        return match authenticate_api_token(&token, pool).await.map_err(e500)? {
+            Some((user_id, role)) => {
+                req.extensions_mut().insert(UserId(user_id));
+                req.extensions_mut().insert(role);
+                req.extensions_mut().insert(ApiTokenAuthentication);
+                next.call(req)
+                    .await
+                    .map(ServiceResponse::map_into_left_body)
+            }
+            None => {
+                let response = HttpResponse::Unauthorized()
+                    .insert_header((
+                        header::WWW_AUTHENTICATE,
+                        HeaderValue::from_static(r#"Bearer realm="admin""#),
+                    ))
+                    .body("The API token is invalid or has been revoked.");
+                Ok(req.into_response(response).map_into_right_body())
+            }
+        };
+    }
+
     let session = {
         let (http_request, payload) = req.parts_mut();
         TypedSession::from_request(http_request, payload).await
     }?;
 
-    match session.get_user_id().map_err(e500)? {
-        Some(user_id) => {
-            req.extensions_mut().insert(UserId(user_id));
-            next.call(req).await
+    let Some(user_id) = session.get_user_id().map_err(e500)? else {
+        let response = see_other("/login");
+        let e = anyhow::anyhow!("The user has not logged in");
+        return Err(InternalError::from_response(e, response).into());
+    };
+    let timeouts = req
+        .app_data::<web::Data<SessionSettings>>()
+        .expect("The session settings are not registered as application data");
+    let now = Utc::now();
+    let expiry = session_expiry(
+        session.get_logged_in_at().map_err(e500)?,
+        session.get_last_seen_at().map_err(e500)?,
+        timeouts,
+        now,
+    );
+    if let Some(expiry) = expiry {
+        session.log_out();
+        FlashMessage::info(expiry.message()).send();
+        return Ok(req.into_response(see_other("/login")).map_into_right_body());
+    }
+    session.insert_last_seen_at(now).map_err(e500)?;
+
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .expect("The database pool is not registered as application data");
+    // Looked up on every request, so that revocations, deactivations and
+    // role changes apply to existing sessions straight away. Sessions from
+    // before sessions were recorded are treated as revoked.
+    let user = match session.get_session_id().map_err(e500)? {
+        Some(session_id) => touch_user_session(session_id, user_id, pool)
+            .await
+            .map_err(e500)?,
+        None => None,
+    };
+    let Some(user) = user else {
+        session.log_out();
+        // Returned as a response rather than an error, for the flash
+        // message to be sent along.
+        FlashMessage::info("Your session has ended, please log in again.").send();
+        return Ok(req.into_response(see_other("/login")).map_into_right_body());
+    };
+    if !user.active {
+        session.log_out();
+        let response = see_other("/login");
+        let e = anyhow::anyhow!("The user has been deactivated");
+        return Err(InternalError::from_response(e, response).into());
+    }
+    req.extensions_mut().insert(UserId(user_id));
+    req.extensions_mut().insert(user.role);
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+#[derive(Debug, PartialEq)]
+enum SessionExpiry {
+    Idle,
+    Absolute,
+}
+
+impl SessionExpiry {
+    fn message(&self) -> &'static str {
+        match self {
+            SessionExpiry::Idle => {
+                "Your session has expired after a period of inactivity, please log in again."
+            }
+            SessionExpiry::Absolute => {
+                "Your session has reached its maximum duration, please log in again."
+            }
         }
-        None => {
-            let response = see_other("/login");
-            let e = anyhow::anyhow!("The user has not logged in");
+    }
+}
+
+/// Sessions without timestamps predate them, and have lived long enough.
+fn session_expiry(
+    logged_in_at: Option<DateTime<Utc>>,
+    last_seen_at: Option<DateTime<Utc>>,
+    timeouts: &SessionSettings,
+    now: DateTime<Utc>,
+) -> Option<SessionExpiry> {
+    if logged_in_at.is_none_or(|at| now - at > timeouts.absolute_timeout()) {
+        Some(SessionExpiry::Absolute)
+    } else if last_seen_at.is_none_or(|at| now - at > timeouts.idle_timeout()) {
+        Some(SessionExpiry::Idle)
+    } else {
+        None
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<SecretString> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| SecretString::from(token.trim()))
+}
+
+/// Must be registered inside of `reject_anonymous_users`.
+pub async fn require_editor(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    require_role(Role::Editor, req, next).await
+}
+
+/// Must be registered inside of `reject_anonymous_users`.
+pub async fn require_owner(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    require_role(Role::Owner, req, next).await
+}
+
+/// Keep API tokens away from account management: a leaked token must not be
+/// enough to take over the account it belongs to.
+///
+/// Must be registered inside of `reject_anonymous_users`.
+pub async fn require_session(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if req.extensions().get::<ApiTokenAuthentication>().is_some() {
+        let response = HttpResponse::Forbidden()
+            .body("API tokens cannot be used on this page, log in instead.");
+        let e = anyhow::anyhow!("An API token was used for account management");
+        return Err(InternalError::from_response(e, response).into());
+    }
+    next.call(req).await
+}
+
+async fn require_role<B: MessageBody>(
+    required: Role,
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<B>, actix_web::Error> {
+    let role = req.extensions().get::<Role>().copied();
+    match role {
+        Some(role) if role >= required => next.call(req).await,
+        _ => {
+            let response = HttpResponse::Forbidden()
+                .body(format!("You need the {required} role to access this page."));
+            let e = anyhow::anyhow!("The user does not have the {required} role");
             Err(InternalError::from_response(e, response).into())
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{SessionExpiry, session_expiry};
+    use crate::configuration::SessionSettings;
+    use chrono::{Duration, Utc};
+
+    fn timeouts() -> SessionSettings {
+        SessionSettings {
+            idle_timeout_seconds: 1800,
+            absolute_timeout_seconds: 43200,
+        }
+    }
+
+    #[test]
+    fn recently_used_sessions_are_valid() {
+        let now = Utc::now();
+        let logged_in_at = Some(now - Duration::hours(2));
+        let last_seen_at = Some(now - Duration::minutes(29));
+        assert_eq!(
+            session_expiry(logged_in_at, last_seen_at, &timeouts(), now),
+            None
+        );
+    }
+
+    #[test]
+    fn idle_sessions_expire() {
+        let now = Utc::now();
+        let logged_in_at = Some(now - Duration::hours(2));
+        let last_seen_at = Some(now - Duration::minutes(31));
+        assert_eq!(
+            session_expiry(logged_in_at, last_seen_at, &timeouts(), now),
+            Some(SessionExpiry::Idle)
+        );
+    }
+
+    #[test]
+    fn active_sessions_expire_after_their_maximum_duration() {
+        let now = Utc::now();
+        let logged_in_at = Some(now - Duration::hours(13));
+        let last_seen_at = Some(now - Duration::minutes(1));
+        assert_eq!(
+            session_expiry(logged_in_at, last_seen_at, &timeouts(), now),
+            Some(SessionExpiry::Absolute)
+        );
+    }
+
+    #[test]
+    fn sessions_without_timestamps_are_expired() {
+        let now = Utc::now();
+        assert_eq!(
+            session_expiry(None, None, &timeouts(), now),
+            Some(SessionExpiry::Absolute)
+        );
+    }
+}
@@ -1,7 +1,24 @@
 //! src/authentication/mod.rs
+mod api_token;
+mod csrf;
 mod middleware;
+mod oidc;
 mod password;
+mod role;
+mod throttle;
+mod totp;
+mod user_session;
 
+pub use api_token::issue_api_token;
+pub use csrf::{CsrfToken, check_multipart_csrf_token, reject_cross_site_requests};
 pub use middleware::UserId;
-pub use middleware::reject_anonymous_users;
+pub use middleware::{reject_anonymous_users, require_editor, require_owner, require_session};
+pub use oidc::OidcClient;
 pub use password::{AuthError, Credentials, change_password, validate_credentials};
+pub use role::Role;
+pub use throttle::LoginThrottle;
+pub use totp::{
+    TotpProvisioning, check_totp_code, count_unused_recovery_codes, enable_totp,
+    generate_totp_secret, get_totp_secret, totp_provisioning, verify_second_factor,
+};
+pub use user_session::{revoke_other_user_sessions, revoke_user_session, start_user_session};
@@ -0,0 +1,288 @@
+//! src/authentication/oidc.rs
+use super::AuthError;
+use crate::configuration::OidcSettings;
+use crate::session_state::PendingOidcLogin;
+use anyhow::Context;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use rand::distributions::Alphanumeric;
+use rand::{Rng, thread_rng};
+use reqwest::{Client, Url};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+
+/// Logs users in with an OpenID Connect provider, using the authorization
+/// code flow with PKCE.
+pub struct OidcClient {
+    enabled: bool,
+    http_client: Client,
+    issuer_url: String,
+    client_id: String,
+    client_secret: SecretString,
+    redirect_uri: String,
+}
+
+/// The parts of the provider's discovery document that we use.
+#[derive(serde::Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(serde::Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+}
+
+impl OidcClient {
+    pub fn new(settings: OidcSettings, base_url: &str) -> Self {
+        let http_client = Client::builder()
+            .timeout(settings.timeout())
+            .build()
+            .unwrap();
+        Self {
+            enabled: settings.enabled,
+            http_client,
+            issuer_url: settings.issuer_url,
+            client_id: settings.client_id,
+            client_secret: settings.client_secret,
+            redirect_uri: format!("{}/login/oidc/callback", base_url),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The provider URL to send the user to, and what to remember in their
+    /// session to complete the login.
+    #[tracing::instrument(name = "Start OIDC login", skip(self))]
+    pub async fn authorization_request(&self) -> Result<(Url, PendingOidcLogin), anyhow::Error> {
+        let metadata = self.discover().await?;
+        let pending = PendingOidcLogin {
+            state: generate_random_string(32),
+            nonce: generate_random_string(32),
+            code_verifier: generate_random_string(64),
+        };
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &self.client_id),
+                ("redirect_uri", &self.redirect_uri),
+                ("scope", "openid email"),
+                ("state", &pending.state),
+                ("nonce", &pending.nonce),
+                ("code_challenge", &pkce_challenge(&pending.code_verifier)),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .context("The provider's authorization endpoint is not a valid URL")?;
+        Ok((url, pending))
+    }
+
+    /// Exchange the authorization code returned by the provider for an ID
+    /// token, and return the email it vouches for.
+    #[tracing::instrument(name = "Complete OIDC login", skip_all, fields(sub = tracing::field::Empty))]
+    pub async fn verified_email(
+        &self,
+        code: &str,
+        pending: &PendingOidcLogin,
+    ) -> Result<String, AuthError> {
+        let metadata = self.discover().await?;
+        let response = self
+            .http_client
+            .post(&metadata.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.redirect_uri),
+                ("client_id", &self.client_id),
+                ("client_secret", self.client_secret.expose_secret()),
+                ("code_verifier", &pending.code_verifier),
+            ])
+            .send()
+            .await
+            .context("Failed to reach the provider's token endpoint")?;
+        if response.status().is_client_error() {
+            return Err(AuthError::InvalidCredentials(anyhow::anyhow!(
+                "The provider rejected the authorization code with {}",
+                response.status()
+            )));
+        }
+        let token: TokenResponse = response
+            .error_for_status()
+            .context("The provider's token endpoint failed")?
+            .json()
+            .await
+            .context("Failed to parse the provider's token response")?;
+        // Keys are fetched at each login so that rotations are picked up.
+        let jwks: JwkSet = self
+            .http_client
+            .get(&metadata.jwks_uri)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .context("Failed to fetch the provider's signing keys")?
+            .json()
+            .await
+            .context("Failed to parse the provider's signing keys")?;
+
+        let claims = verify_id_token(
+            &token.id_token,
+            &jwks,
+            &metadata.issuer,
+            &self.client_id,
+            &pending.nonce,
+        )
+        .map_err(AuthError::InvalidCredentials)?;
+        tracing::Span::current().record("sub", tracing::field::display(&claims.sub));
+        match claims.email {
+            Some(email) if claims.email_verified => Ok(email),
+            Some(_) => Err(AuthError::InvalidCredentials(anyhow::anyhow!(
+                "The provider has not verified the email of {}",
+                claims.sub
+            ))),
+            None => Err(AuthError::InvalidCredentials(anyhow::anyhow!(
+                "The provider did not share the email of {}",
+                claims.sub
+            ))),
+        }
+    }
+
+    async fn discover(&self) -> Result<ProviderMetadata, anyhow::Error> {
+        let metadata: ProviderMetadata = self
+            .http_client
+            .get(format!(
+                "{}/.well-known/openid-configuration",
+                self.issuer_url.trim_end_matches('/')
+            ))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .context("Failed to fetch the provider's discovery document")?
+            .json()
+            .await
+            .context("Failed to parse the provider's discovery document")?;
+        if metadata.issuer.trim_end_matches('/') != self.issuer_url.trim_end_matches('/') {
+            anyhow::bail!(
+                "The discovery document is for {}, not {}",
+                metadata.issuer,
+                self.issuer_url
+            );
+        }
+        Ok(metadata)
+    }
+}
+
+/// Check the signature, issuer, audience, expiry and nonce of an ID token.
+fn verify_id_token(
+    id_token: &str,
+    jwks: &JwkSet,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+) -> Result<IdTokenClaims, anyhow::Error> {
+    let header = jsonwebtoken::decode_header(id_token).context("Malformed ID token")?;
+    // Only accept signatures made with the provider's private keys.
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        anyhow::bail!("ID tokens signed with {:?} are not accepted", header.alg);
+    }
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .context("The ID token is not signed with one of the provider's keys")?;
+    let key = DecodingKey::from_jwk(jwk).context("Unsupported signing key")?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+    let claims = jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)
+        .context("Invalid ID token")?
+        .claims;
+    // Ties the token to the login started in this browser, so that it
+    // cannot be replayed.
+    if claims.nonce.as_deref() != Some(nonce) {
+        anyhow::bail!("The ID token was issued for another login");
+    }
+    Ok(claims)
+}
+
+/// The S256 code challenge sent along the authorization request, for the
+/// verifier sent along the token request.
+fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+fn generate_random_string(length: usize) -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(length)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pkce_challenge, verify_id_token};
+    use jsonwebtoken::jwk::JwkSet;
+    use jsonwebtoken::{EncodingKey, Header};
+
+    #[test]
+    fn pkce_challenge_matches_the_rfc_example() {
+        // RFC 7636, appendix B
+        let challenge = pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk");
+
+        assert_eq!(challenge, "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+    }
+
+    #[test]
+    fn id_tokens_signed_with_a_shared_secret_are_rejected() {
+        // Whoever knows the client secret could forge them.
+        let jwks: JwkSet = serde_json::from_value(serde_json::json!({
+            "keys": [{ "kty": "oct", "k": "c2VjcmV0" }]
+        }))
+        .unwrap();
+        let id_token = jsonwebtoken::encode(
+            &Header::default(),
+            &serde_json::json!({
+                "iss": "https://idp.example.com",
+                "aud": "zero2prod",
+                "sub": "1234",
+                "exp": u32::MAX,
+                "nonce": "nonce",
+            }),
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+
+        let result = verify_id_token(
+            &id_token,
+            &jwks,
+            "https://idp.example.com",
+            "zero2prod",
+            "nonce",
+        );
+
+        assert!(result.is_err());
+    }
+}
@@ -1,10 +1,13 @@
 //! src/authentication/password.rs
+use super::user_session::revoke_other_user_sessions;
+use crate::configuration::PasswordHashingSettings;
+use crate::domain::NewPassword;
 use crate::telemetry::spawn_blocking_with_tracing;
 use anyhow::Context;
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
 use secrecy::{ExposeSecret, SecretString};
-use sqlx::PgPool;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
 
 pub struct Credentials {
     pub username: String,
@@ -19,18 +22,21 @@ pub enum AuthError {
     UnexpectedError(#[from] anyhow::Error),
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+#[tracing::instrument(name = "Validate credentials", skip(credentials, hashing, pool))]
 pub async fn validate_credentials(
     credentials: Credentials,
+    hashing: &PasswordHashingSettings,
     pool: &PgPool,
 ) -> Result<uuid::Uuid, AuthError> {
     let mut user_id = None;
-    let mut expected_password_hash = SecretString::from(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
+    // Verified with the current parameters, so that unknown usernames take
+    // as long as known ones.
+    let mut expected_password_hash = SecretString::from(format!(
+        "$argon2id$v=19$m={},t={},p={}$\
         gZiV/M1gPc22ElAH/Jh1Hw$\
-        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
-            .to_string(),
-    );
+        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno",
+        hashing.memory_size_kib, hashing.iterations, hashing.parallelism
+    ));
 
     if let Some((stored_user_id, stored_password_hash)) =
         get_stored_credentials(&credentials.username, pool).await?
@@ -39,22 +45,84 @@ pub async fn validate_credentials(
         expected_password_hash = stored_password_hash;
     }
 
+    let stored_password_hash = expected_password_hash.clone();
+    let password = credentials.password.clone();
     spawn_blocking_with_tracing(move || {
         verify_password_hash(expected_password_hash, credentials.password)
     })
     .await
     .context("Failed to spawn blocking task.")??;
 
-    user_id
+    let user_id = user_id
         .ok_or_else(|| anyhow::anyhow!("Unknown username"))
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+    if needs_rehash(&stored_password_hash, hashing) {
+        // The login succeeds even if the upgrade fails: it is retried the
+        // next time.
+        if let Err(e) =
+            rehash_password(user_id, password, &stored_password_hash, hashing, pool).await
+        {
+            tracing::warn!(error.cause_chain = ?e, "Failed to upgrade the password hash");
+        }
+    }
+    Ok(user_id)
+}
+
+/// Whether a hash was made with another algorithm or other parameters than
+/// the ones currently configured.
+fn needs_rehash(password_hash: &SecretString, hashing: &PasswordHashingSettings) -> bool {
+    let Ok(password_hash) = PasswordHash::new(password_hash.expose_secret()) else {
+        return true;
+    };
+    let Ok(params) = Params::try_from(&password_hash) else {
+        return true;
+    };
+    password_hash.algorithm != Algorithm::Argon2id.ident()
+        || password_hash.version != Some(Version::V0x13.into())
+        || params.m_cost() != hashing.memory_size_kib
+        || params.t_cost() != hashing.iterations
+        || params.p_cost() != hashing.parallelism
+}
+
+#[tracing::instrument(
+    name = "Upgrade password hash",
+    skip(password, old_password_hash, hashing, pool)
+)]
+async fn rehash_password(
+    user_id: uuid::Uuid,
+    password: SecretString,
+    old_password_hash: &SecretString,
+    hashing: &PasswordHashingSettings,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let hashing = hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("Failed to hash password")?;
+    // Does not touch a password changed in the meantime, and is not a
+    // password change: existing sessions stay valid.
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $1
+        WHERE user_id = $2 AND password_hash = $3
+        "#,
+        password_hash.expose_secret(),
+        user_id,
+        old_password_hash.expose_secret()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the upgraded password hash")?;
+    Ok(())
 }
 
 #[tracing::instrument(
     name = "Verify password hash",
     skip(expected_password_hash, password_candidate)
 )]
-fn verify_password_hash(
+pub(super) fn verify_password_hash(
     expected_password_hash: SecretString,
     password_candidate: SecretString,
 ) -> Result<(), AuthError> {
@@ -77,9 +145,13 @@ async fn get_stored_credentials(
 ) -> Result<Option<(uuid::Uuid, SecretString)>, anyhow::Error> {
     let row = sqlx::query!(
         r#"
-        SELECT user_id, password_hash
+        SELECT user_id, password_hash as "password_hash!"
         FROM users
-        WHERE username = $1
+        WHERE
+            username = $1 AND
+            active AND
+            -- Invited users cannot log in before picking a password.
+            password_hash IS NOT NULL
         "#,
         username
     )
@@ -91,16 +163,24 @@ async fn get_stored_credentials(
     Ok(row)
 }
 
-#[tracing::instrument(name = "Change password", skip(password, pool))]
+/// Also revokes all the sessions of the user, except `keep_session_id`.
+///
+/// Nothing changes until `transaction` is committed, so that callers can
+/// make other changes along with the new password.
+#[tracing::instrument(name = "Change password", skip(password, hashing, transaction))]
 pub async fn change_password(
     user_id: uuid::Uuid,
-    password: SecretString,
-    pool: &PgPool,
+    password: NewPassword,
+    keep_session_id: Option<uuid::Uuid>,
+    hashing: &PasswordHashingSettings,
+    transaction: &mut Transaction<'_, Postgres>,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("Failed to hash password")?;
-    sqlx::query!(
+    let hashing = hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password.into(), &hashing))
+            .await?
+            .context("Failed to hash password")?;
+    let query = sqlx::query!(
         r#"
         UPDATE users
         SET password_hash = $1
@@ -108,22 +188,71 @@ pub async fn change_password(
         "#,
         password_hash.expose_secret(),
         user_id
-    )
-    .execute(pool)
-    .await
-    .context("Failed to change user's password in the database")?;
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to change user's password in the database")?;
+    revoke_other_user_sessions(user_id, keep_session_id, &mut **transaction).await?;
 
     Ok(())
 }
 
-fn compute_password_hash(password: SecretString) -> Result<SecretString, anyhow::Error> {
+pub(super) fn compute_password_hash(
+    password: SecretString,
+    hashing: &PasswordHashingSettings,
+) -> Result<SecretString, anyhow::Error> {
     let salt = SaltString::generate(&mut rand::thread_rng());
     let password_hash = Argon2::new(
         Algorithm::Argon2id,
         Version::V0x13,
-        Params::new(15000, 2, 1, None)?,
+        Params::new(
+            hashing.memory_size_kib,
+            hashing.iterations,
+            hashing.parallelism,
+            None,
+        )?,
     )
     .hash_password(password.expose_secret().as_bytes(), &salt)?
     .to_string();
     Ok(SecretString::from(password_hash))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{compute_password_hash, needs_rehash};
+    use crate::configuration::PasswordHashingSettings;
+    use secrecy::SecretString;
+
+    fn hashing() -> PasswordHashingSettings {
+        PasswordHashingSettings {
+            memory_size_kib: 15000,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+
+    #[test]
+    fn hashes_with_the_current_parameters_are_kept() {
+        let hash = compute_password_hash(SecretString::from("password"), &hashing()).unwrap();
+        assert!(!needs_rehash(&hash, &hashing()));
+    }
+
+    #[test]
+    fn hashes_with_other_parameters_are_upgraded() {
+        let hash = compute_password_hash(SecretString::from("password"), &hashing()).unwrap();
+        let stronger = PasswordHashingSettings {
+            memory_size_kib: 19456,
+            ..hashing()
+        };
+        assert!(needs_rehash(&hash, &stronger));
+    }
+
+    #[test]
+    fn argon2d_hashes_are_upgraded() {
+        let hash = SecretString::from(
+            "$argon2d$v=19$m=15000,t=2,p=1$DL95bix2KfP6Yzz1L5NXdQ$AnX5SYkZrRxkX8YhxzyHjjf/r/1CUaFHWiLqugcVED0",
+        );
+        assert!(needs_rehash(&hash, &hashing()));
+    }
+}
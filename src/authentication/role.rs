@@ -0,0 +1,68 @@
+//! src/authentication/role.rs
+
+/// What an admin user is allowed to do.
+///
+/// Roles are ordered: every role can do what the roles below it can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Can browse issues and their stats.
+    Viewer,
+    /// Can also publish and manage the delivery of issues.
+    Editor,
+    /// Can also manage the other users.
+    Owner,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Owner, Role::Editor, Role::Viewer];
+
+    /// # Examples
+    ///
+    /// ```
+    /// use zero2prod::authentication::Role;
+    /// use assert2::assert;
+    ///
+    /// assert!(Role::parse("editor") == Ok(Role::Editor));
+    /// assert!(Role::parse("admin").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<Role, String> {
+        match s {
+            "owner" => Ok(Role::Owner),
+            "editor" => Ok(Role::Editor),
+            "viewer" => Ok(Role::Viewer),
+            other => Err(format!("{other} is not a valid role.")),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Editor => "editor",
+            Role::Viewer => "viewer",
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Role;
+
+    #[test]
+    fn roles_round_trip_through_their_name() {
+        for role in Role::ALL {
+            assert_eq!(Role::parse(role.as_str()), Ok(role));
+        }
+    }
+
+    #[test]
+    fn owners_outrank_editors_who_outrank_viewers() {
+        assert!(Role::Owner > Role::Editor);
+        assert!(Role::Editor > Role::Viewer);
+    }
+}
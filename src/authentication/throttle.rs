@@ -0,0 +1,92 @@
+//! src/authentication/throttle.rs
+use crate::configuration::LoginThrottlingSettings;
+use anyhow::Context;
+use redis::AsyncCommands;
+use redis::aio::ConnectionManager;
+use secrecy::{ExposeSecret, SecretString};
+
+/// Counts failed logins per username and per IP address in Redis, and
+/// locks them out once they go over the configured limits.
+///
+/// Counters expire `lockout_seconds` after the first failure they record.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    connection: ConnectionManager,
+    settings: LoginThrottlingSettings,
+}
+
+impl LoginThrottle {
+    pub async fn new(
+        redis_uri: &SecretString,
+        settings: LoginThrottlingSettings,
+    ) -> Result<Self, anyhow::Error> {
+        let client = redis::Client::open(redis_uri.expose_secret())
+            .context("Failed to parse the Redis URI")?;
+        let connection = ConnectionManager::new(client)
+            .await
+            .context("Failed to connect to Redis")?;
+        Ok(Self {
+            connection,
+            settings,
+        })
+    }
+
+    /// Checked before verifying any password, so that locked out attempts
+    /// do not cost an Argon2 verification.
+    #[tracing::instrument(name = "Check login lockout", skip(self))]
+    pub async fn is_locked_out(&self, username: &str, ip: &str) -> Result<bool, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let (username_failures, ip_failures): (Option<u32>, Option<u32>) = redis::pipe()
+            .get(username_key(username))
+            .get(ip_key(ip))
+            .query_async(&mut connection)
+            .await
+            .context("Failed to read failed login counters")?;
+        Ok(
+            username_failures.unwrap_or(0) >= self.settings.max_failures_per_username
+                || ip_failures.unwrap_or(0) >= self.settings.max_failures_per_ip,
+        )
+    }
+
+    #[tracing::instrument(name = "Record failed login", skip(self))]
+    pub async fn record_failure(&self, username: &str, ip: &str) -> Result<(), anyhow::Error> {
+        for key in [username_key(username), ip_key(ip)] {
+            self.increment(&key).await?;
+        }
+        Ok(())
+    }
+
+    /// Forget the failures of a username after a successful login.
+    #[tracing::instrument(name = "Reset failed logins", skip(self))]
+    pub async fn reset(&self, username: &str) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let _: () = connection
+            .del(username_key(username))
+            .await
+            .context("Failed to reset the failed login counter")?;
+        Ok(())
+    }
+
+    async fn increment(&self, key: &str) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let failures: u32 = connection
+            .incr(key, 1)
+            .await
+            .context("Failed to increment a failed login counter")?;
+        if failures == 1 {
+            let _: () = connection
+                .expire(key, self.settings.lockout_seconds as i64)
+                .await
+                .context("Failed to set the expiry of a failed login counter")?;
+        }
+        Ok(())
+    }
+}
+
+fn username_key(username: &str) -> String {
+    format!("login_failures:username:{}", username.to_lowercase())
+}
+
+fn ip_key(ip: &str) -> String {
+    format!("login_failures:ip:{ip}")
+}
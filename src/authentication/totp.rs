@@ -0,0 +1,347 @@
+//! src/authentication/totp.rs
+use super::password::{compute_password_hash, verify_password_hash};
+use crate::configuration::PasswordHashingSettings;
+use crate::telemetry::spawn_blocking_with_tracing;
+use anyhow::Context;
+use rand::{Rng, thread_rng};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{Executor, PgPool};
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+const ISSUER: &str = "zero2prod";
+const STEP_SECONDS: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// What an authenticator app needs to be set up.
+pub struct TotpProvisioning {
+    /// `otpauth://` URI, for manual entry.
+    pub uri: String,
+    /// The same URI as a base64-encoded PNG QR code.
+    pub qr_code_png: String,
+}
+
+/// Generate a new base32-encoded TOTP secret.
+pub fn generate_totp_secret() -> SecretString {
+    match Secret::generate_secret().to_encoded() {
+        Secret::Encoded(secret) => SecretString::from(secret),
+        Secret::Raw(_) => unreachable!("to_encoded always returns an encoded secret"),
+    }
+}
+
+pub fn totp_provisioning(
+    secret: &SecretString,
+    username: &str,
+) -> Result<TotpProvisioning, anyhow::Error> {
+    let totp = totp(secret, username)?;
+    let qr_code_png = totp
+        .get_qr_base64()
+        .map_err(anyhow::Error::msg)
+        .context("Failed to render the TOTP QR code")?;
+    Ok(TotpProvisioning {
+        uri: totp.get_url(),
+        qr_code_png,
+    })
+}
+
+/// Return the time step `code` is valid for, allowing for one step of
+/// clock drift in either direction.
+pub fn check_totp_code(secret: &SecretString, code: &str) -> Result<Option<u64>, anyhow::Error> {
+    let totp = totp(secret, "")?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("The system clock is set before the UNIX epoch")?
+        .as_secs();
+    let current_step = now / STEP_SECONDS;
+    let code = code.trim();
+    Ok((current_step.saturating_sub(1)..=current_step + 1)
+        .find(|step| totp.check(code, step * STEP_SECONDS)))
+}
+
+fn totp(secret: &SecretString, username: &str) -> Result<TOTP, anyhow::Error> {
+    let secret = Secret::Encoded(secret.expose_secret().to_string())
+        .to_bytes()
+        .map_err(|e| anyhow::anyhow!("Invalid TOTP secret: {e:?}"))?;
+    // `:` separates the issuer from the account name in otpauth URIs.
+    let account_name = username.replace(':', "_");
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        0,
+        STEP_SECONDS,
+        secret,
+        Some(ISSUER.to_string()),
+        account_name,
+    )
+    .context("Failed to build the TOTP generator")
+}
+
+#[tracing::instrument(name = "Get TOTP secret", skip(pool))]
+pub async fn get_totp_secret(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<Option<SecretString>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT totp_secret
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the TOTP secret")?;
+    Ok(row.totp_secret.map(SecretString::from))
+}
+
+/// Turn on two-factor authentication for the user, once they proved that
+/// their authenticator produces a valid code for `secret` at `step`.
+///
+/// Returns freshly generated recovery codes, which are only stored hashed.
+#[tracing::instrument(name = "Enable TOTP", skip(secret, hashing, pool))]
+pub async fn enable_totp(
+    user_id: Uuid,
+    secret: SecretString,
+    step: u64,
+    hashing: &PasswordHashingSettings,
+    pool: &PgPool,
+) -> Result<Vec<String>, anyhow::Error> {
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+    let codes = recovery_codes.clone();
+    let hashing = hashing.clone();
+    let code_hashes = spawn_blocking_with_tracing(move || {
+        codes
+            .into_iter()
+            .map(|code| compute_password_hash(SecretString::from(code), &hashing))
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await?
+    .context("Failed to hash the recovery codes")?;
+
+    let mut transaction = pool.begin().await?;
+    let query = sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = $2, totp_last_used_step = $3
+        WHERE user_id = $1
+        "#,
+        user_id,
+        secret.expose_secret(),
+        step as i64
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to store the TOTP secret")?;
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM user_recovery_codes
+        WHERE user_id = $1
+        "#,
+        user_id
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete old recovery codes")?;
+    for code_hash in code_hashes {
+        let query = sqlx::query!(
+            r#"
+            INSERT INTO user_recovery_codes (recovery_code_id, user_id, code_hash)
+            VALUES ($1, $2, $3)
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            code_hash.expose_secret()
+        );
+        transaction
+            .execute(query)
+            .await
+            .context("Failed to store a recovery code")?;
+    }
+    transaction.commit().await?;
+    Ok(recovery_codes)
+}
+
+/// Check a TOTP code, or else an unused recovery code, for the user.
+///
+/// Each TOTP code and recovery code can only be used once.
+#[tracing::instrument(name = "Verify second factor", skip(code, pool))]
+pub async fn verify_second_factor(
+    user_id: Uuid,
+    code: &str,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let Some(secret) = get_totp_secret(user_id, pool).await? else {
+        return Ok(false);
+    };
+    if let Some(step) = check_totp_code(&secret, code)? {
+        let updated = sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_last_used_step = $2
+            WHERE
+                user_id = $1 AND
+                (totp_last_used_step IS NULL OR totp_last_used_step < $2)
+            "#,
+            user_id,
+            step as i64
+        )
+        .execute(pool)
+        .await
+        .context("Failed to record the TOTP code as used")?
+        .rows_affected();
+        return Ok(updated > 0);
+    }
+    use_recovery_code(user_id, code, pool).await
+}
+
+#[tracing::instrument(name = "Use recovery code", skip(code, pool))]
+async fn use_recovery_code(
+    user_id: Uuid,
+    code: &str,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let code = normalize_recovery_code(code);
+    let candidates = sqlx::query!(
+        r#"
+        SELECT recovery_code_id, code_hash
+        FROM user_recovery_codes
+        WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve recovery codes")?;
+    let candidates: Vec<(Uuid, SecretString)> = candidates
+        .into_iter()
+        .map(|r| (r.recovery_code_id, SecretString::from(r.code_hash)))
+        .collect();
+    let matching = spawn_blocking_with_tracing(move || {
+        candidates.into_iter().find_map(|(id, hash)| {
+            verify_password_hash(hash, SecretString::from(code.clone()))
+                .is_ok()
+                .then_some(id)
+        })
+    })
+    .await
+    .context("Failed to spawn blocking task.")?;
+    let Some(recovery_code_id) = matching else {
+        return Ok(false);
+    };
+    let updated = sqlx::query!(
+        r#"
+        UPDATE user_recovery_codes
+        SET used_at = now()
+        WHERE recovery_code_id = $1 AND used_at IS NULL
+        "#,
+        recovery_code_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to mark the recovery code as used")?
+    .rows_affected();
+    Ok(updated > 0)
+}
+
+#[tracing::instrument(name = "Count unused recovery codes", skip(pool))]
+pub async fn count_unused_recovery_codes(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<i64, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM user_recovery_codes
+        WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count unused recovery codes")?;
+    Ok(row.count)
+}
+
+/// Ten characters in two groups, e.g. `k3x9q-m2p7w`.
+fn generate_recovery_code() -> String {
+    const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+    let mut rng = thread_rng();
+    let mut code: String = (0..10)
+        .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+        .collect();
+    code.insert(5, '-');
+    code
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    match code.len() {
+        10 => format!("{}-{}", &code[..5], &code[5..]),
+        _ => code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        STEP_SECONDS, check_totp_code, generate_recovery_code, generate_totp_secret,
+        normalize_recovery_code, totp,
+    };
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn current_codes_are_accepted() {
+        let secret = generate_totp_secret();
+        let code = totp(&secret, "admin").unwrap().generate(now());
+        assert!(check_totp_code(&secret, &code).unwrap().is_some());
+    }
+
+    #[test]
+    fn codes_from_the_next_step_are_accepted() {
+        let secret = generate_totp_secret();
+        let code = totp(&secret, "admin")
+            .unwrap()
+            .generate(now() + STEP_SECONDS);
+        assert!(check_totp_code(&secret, &code).unwrap().is_some());
+    }
+
+    #[test]
+    fn old_codes_are_rejected() {
+        let secret = generate_totp_secret();
+        let code = totp(&secret, "admin")
+            .unwrap()
+            .generate(now() - 10 * STEP_SECONDS);
+        assert!(check_totp_code(&secret, &code).unwrap().is_none());
+    }
+
+    #[test]
+    fn recovery_codes_are_normalized() {
+        let code = generate_recovery_code();
+        assert_eq!(normalize_recovery_code(&code), code);
+        assert_eq!(normalize_recovery_code(&code.to_uppercase()), code);
+        assert_eq!(normalize_recovery_code(&code.replace('-', " ")), code);
+    }
+
+    #[test]
+    fn usernames_with_a_colon_can_be_provisioned() {
+        let secret = generate_totp_secret();
+        assert!(totp(&secret, "a:b").is_ok());
+    }
+}
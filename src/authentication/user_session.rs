@@ -0,0 +1,128 @@
+//! src/authentication/user_session.rs
+use super::Role;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+/// Record a new session for the user, returning its id.
+#[tracing::instrument(name = "Start user session", skip(pool, user_agent))]
+pub async fn start_user_session(
+    user_id: Uuid,
+    ip_address: &str,
+    user_agent: Option<&str>,
+    pool: &PgPool,
+) -> Result<Uuid, anyhow::Error> {
+    let session_id = Uuid::new_v4();
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        INSERT INTO user_sessions (
+            session_id,
+            user_id,
+            created_at,
+            last_seen_at,
+            ip_address,
+            user_agent
+        )
+        VALUES ($1, $2, $3, $3, $4, $5)
+        "#,
+        session_id,
+        user_id,
+        now,
+        ip_address,
+        user_agent
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record the user session")?;
+    Ok(session_id)
+}
+
+pub(super) struct SessionUser {
+    pub role: Role,
+    pub active: bool,
+}
+
+/// Mark the session as seen, returning its user if it has not been revoked.
+#[tracing::instrument(name = "Touch user session", skip(pool))]
+pub(super) async fn touch_user_session(
+    session_id: Uuid,
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<Option<SessionUser>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE user_sessions s
+        SET last_seen_at = now()
+        FROM users u
+        WHERE
+            s.session_id = $1 AND
+            s.user_id = $2 AND
+            s.revoked_at IS NULL AND
+            u.user_id = s.user_id
+        RETURNING u.role, u.active
+        "#,
+        session_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to update the user session")?;
+    row.map(|r| {
+        Ok(SessionUser {
+            role: Role::parse(&r.role).map_err(anyhow::Error::msg)?,
+            active: r.active,
+        })
+    })
+    .transpose()
+}
+
+/// Revoke a session of the user. Returns `false` if there was no such
+/// active session.
+#[tracing::instrument(name = "Revoke user session", skip(executor))]
+pub async fn revoke_user_session(
+    user_id: Uuid,
+    session_id: Uuid,
+    executor: impl Executor<'_, Database = Postgres>,
+) -> Result<bool, anyhow::Error> {
+    let revoked = sqlx::query!(
+        r#"
+        UPDATE user_sessions
+        SET revoked_at = now()
+        WHERE session_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        session_id,
+        user_id
+    )
+    .execute(executor)
+    .await
+    .context("Failed to revoke the user session")?
+    .rows_affected();
+    Ok(revoked > 0)
+}
+
+/// Revoke all the sessions of the user, except `keep_session_id`.
+#[tracing::instrument(name = "Revoke other user sessions", skip(executor))]
+pub async fn revoke_other_user_sessions(
+    user_id: Uuid,
+    keep_session_id: Option<Uuid>,
+    executor: impl Executor<'_, Database = Postgres>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE user_sessions
+        SET revoked_at = now()
+        WHERE
+            user_id = $1 AND
+            revoked_at IS NULL AND
+            session_id IS DISTINCT FROM $2
+        "#,
+        user_id,
+        keep_session_id
+    )
+    .execute(executor)
+    .await
+    .context("Failed to revoke the user sessions")?;
+    Ok(())
+}
@@ -13,6 +13,18 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_uri: SecretString,
+    pub tracking: TrackingSettings,
+    pub attachments: AttachmentSettings,
+    pub ab_testing: AbTestSettings,
+    pub login_throttling: LoginThrottlingSettings,
+    pub password_policy: PasswordPolicySettings,
+    pub password_hashing: PasswordHashingSettings,
+    pub session: SessionSettings,
+    pub oidc: OidcSettings,
+    pub idempotency: IdempotencySettings,
+    pub metrics: MetricsSettings,
+    pub otlp: OtlpSettings,
+    pub health: HealthSettings,
 }
 
 #[derive(Deserialize, Clone)]
@@ -24,6 +36,8 @@ pub struct DatabaseSettings {
     pub host: String,
     pub database_name: String,
     pub require_ssl: bool,
+    /// Apply pending migrations when the application starts.
+    pub migrate_on_startup: bool,
 }
 
 #[derive(Deserialize, Clone)]
@@ -33,6 +47,10 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: SecretString,
+    /// Reverse proxies allowed to report the client address in
+    /// `X-Forwarded-For`.
+    #[serde(default)]
+    pub trusted_proxies: Vec<std::net::IpAddr>,
 }
 
 #[derive(Deserialize, Clone)]
@@ -41,6 +59,183 @@ pub struct EmailClientSettings {
     pub sender_email: String,
     pub authorization_token: SecretString,
     pub timeout_milliseconds: u64,
+    /// Password expected in the basic auth credentials of provider webhooks.
+    pub webhook_token: SecretString,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TrackingSettings {
+    /// When disabled, outgoing issues are sent untouched and the
+    /// tracking endpoints stop recording events.
+    pub enabled: bool,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AttachmentSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_file_size_bytes: usize,
+    /// Upper bound for the sum of all attachments of a single issue.
+    /// Attachments grow by a third once base64-encoded: keep this under
+    /// three quarters of the provider's message size limit (10 MB for
+    /// Postmark).
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_total_size_bytes: usize,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AbTestSettings {
+    /// Share of the list, split evenly across variants, that takes part
+    /// in a subject line test.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub test_percentage: u8,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub wait_window_minutes: u32,
+    /// How often to look for tests whose wait window is over.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub check_interval_seconds: u64,
+}
+
+impl AbTestSettings {
+    pub fn check_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.check_interval_seconds)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LoginThrottlingSettings {
+    /// Failed logins for a username before it is locked out.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_username: u32,
+    /// Failed logins from an IP address, across all usernames, before it
+    /// is locked out.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_ip: u32,
+    /// How long failures are remembered, i.e. how long a lockout lasts.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lockout_seconds: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct PasswordPolicySettings {
+    /// Directory holding the SHA-1 range files of breached passwords that
+    /// new passwords are checked against.
+    pub breached_passwords_directory: String,
+    /// Refuse to start unless the directory holds every range file, and
+    /// reject passwords whose range cannot be read. Only the tests turn it
+    /// off, to use a small sample of the list.
+    pub require_complete_list: bool,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SessionSettings {
+    /// Sessions unused for this long are logged out.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub idle_timeout_seconds: u64,
+    /// Sessions are logged out this long after logging in, however active.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub absolute_timeout_seconds: u64,
+}
+
+impl SessionSettings {
+    pub fn idle_timeout(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.idle_timeout_seconds as i64)
+    }
+
+    pub fn absolute_timeout(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.absolute_timeout_seconds as i64)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct HealthSettings {
+    /// Also check that the email provider answers, on `/health/ready`.
+    pub check_email_provider: bool,
+    /// How long each dependency has to answer before being reported down.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_milliseconds: u64,
+}
+
+impl HealthSettings {
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout_milliseconds)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct OtlpSettings {
+    /// When enabled, spans are exported to an OpenTelemetry collector.
+    pub enabled: bool,
+    /// The collector's OTLP/HTTP traces endpoint.
+    pub endpoint: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub timeout_milliseconds: u64,
+}
+
+impl OtlpSettings {
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout_milliseconds)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MetricsSettings {
+    /// Expected from scrapers of `/metrics`, as a bearer token.
+    pub bearer_token: SecretString,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct IdempotencySettings {
+    /// Saved responses are replayed for this long; after that, a request
+    /// reusing the key is processed as a new one.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retention_hours: u64,
+    /// How often the background worker deletes expired keys.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub cleanup_interval_seconds: u64,
+    /// How long a request waits for another request with the same key to
+    /// finish, before giving up with a `409 Conflict`.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub wait_timeout_milliseconds: u64,
+}
+
+impl IdempotencySettings {
+    pub fn retention(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.retention_hours as i64)
+    }
+
+    pub fn cleanup_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.cleanup_interval_seconds)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct OidcSettings {
+    /// When disabled, the login page does not offer single sign-on.
+    pub enabled: bool,
+    /// The provider's discovery document is served under
+    /// `{issuer_url}/.well-known/openid-configuration`.
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: SecretString,
+    pub timeout_milliseconds: u64,
+}
+
+impl OidcSettings {
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout_milliseconds)
+    }
+}
+
+/// Argon2id parameters for new password hashes. Hashes made with other
+/// parameters are upgraded the next time their user logs in.
+#[derive(Deserialize, Clone, Debug)]
+pub struct PasswordHashingSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub memory_size_kib: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub iterations: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub parallelism: u32,
 }
 
 pub enum Environment {
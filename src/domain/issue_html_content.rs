@@ -0,0 +1,169 @@
+//! src/domain/issue_html_content.rs
+
+#[derive(Debug)]
+pub struct IssueHtmlContent(String);
+
+// Elements that never have a closing tag.
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+impl IssueHtmlContent {
+    pub const MAX_LENGTH: usize = 100_000;
+
+    /// Parse and validate the HTML body of a newsletter issue.
+    ///
+    /// Every opened element must be closed, in order. Void elements
+    /// (e.g. `<br>`) and self-closing tags are accepted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zero2prod::domain::IssueHtmlContent;
+    /// use assert2::assert;
+    ///
+    /// assert!(IssueHtmlContent::parse("<p>Hello<br>world</p>".to_string()).is_ok());
+    ///
+    /// // Malformed HTML is rejected
+    /// assert!(IssueHtmlContent::parse("<p><b>Hello</p></b>".to_string()).is_err());
+    /// assert!(IssueHtmlContent::parse("<p>Hello".to_string()).is_err());
+    /// ```
+    pub fn parse(s: String) -> Result<IssueHtmlContent, String> {
+        if s.trim().is_empty() {
+            return Err("The HTML content cannot be empty.".into());
+        }
+        if s.chars().count() > Self::MAX_LENGTH {
+            return Err(format!(
+                "The HTML content cannot be longer than {} characters.",
+                Self::MAX_LENGTH
+            ));
+        }
+        check_well_formed(&s).map_err(|e| format!("The HTML content is not well-formed: {e}."))?;
+        Ok(Self(s))
+    }
+}
+
+impl AsRef<str> for IssueHtmlContent {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+fn check_well_formed(html: &str) -> Result<(), String> {
+    let mut open_elements: Vec<String> = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment
+                .find("-->")
+                .ok_or_else(|| "a comment is never closed".to_string())?;
+            rest = &comment[end + "-->".len()..];
+            continue;
+        }
+        let is_tag = rest[1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        if !is_tag {
+            // A stray `<` in text content.
+            rest = &rest[1..];
+            continue;
+        }
+        let end = tag_end(rest).ok_or_else(|| "a tag is never closed".to_string())?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if tag.starts_with('!') {
+            // <!DOCTYPE ...>
+            continue;
+        }
+        if let Some(closing) = tag.strip_prefix('/') {
+            let name = closing.trim().to_ascii_lowercase();
+            match open_elements.pop() {
+                Some(open) if open == name => {}
+                Some(open) => return Err(format!("expected </{open}> but found </{name}>")),
+                None => return Err(format!("</{name}> has no matching opening tag")),
+            }
+            continue;
+        }
+        let name: String = tag
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if !tag.ends_with('/') && !VOID_ELEMENTS.contains(&name.as_str()) {
+            open_elements.push(name);
+        }
+    }
+    match open_elements.pop() {
+        Some(open) => Err(format!("<{open}> is never closed")),
+        None => Ok(()),
+    }
+}
+
+/// Byte offset of the `>` closing the tag that starts at the beginning of
+/// `s`, skipping over quoted attribute values.
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::IssueHtmlContent;
+
+    #[test]
+    fn a_full_document_is_valid() {
+        let html = r#"<!DOCTYPE html>
+            <html><head><meta charset="utf-8"></head>
+            <body><!-- intro --><p class="x">1 < 2 <img src="a.png"/></p></body></html>"#;
+        assert!(IssueHtmlContent::parse(html.to_string()).is_ok());
+    }
+
+    #[test]
+    fn attributes_containing_a_closing_bracket_are_valid() {
+        let html = r#"<a title="a > b" href='x'>link</a>"#;
+        assert!(IssueHtmlContent::parse(html.to_string()).is_ok());
+    }
+
+    #[test]
+    fn tag_names_are_case_insensitive() {
+        assert!(IssueHtmlContent::parse("<P>Hello</p>".to_string()).is_ok());
+    }
+
+    #[test]
+    fn empty_string_is_rejected() {
+        assert!(IssueHtmlContent::parse("".to_string()).is_err());
+    }
+
+    #[test]
+    fn interleaved_tags_are_rejected() {
+        let html = "<p><b>Hello</p></b>".to_string();
+        assert!(IssueHtmlContent::parse(html).is_err());
+    }
+
+    #[test]
+    fn unclosed_elements_are_rejected() {
+        assert!(IssueHtmlContent::parse("<div><p>Hello</p>".to_string()).is_err());
+    }
+
+    #[test]
+    fn unmatched_closing_tags_are_rejected() {
+        assert!(IssueHtmlContent::parse("Hello</p>".to_string()).is_err());
+    }
+
+    #[test]
+    fn unterminated_tags_are_rejected() {
+        assert!(IssueHtmlContent::parse("<p class=\"x\"".to_string()).is_err());
+    }
+}
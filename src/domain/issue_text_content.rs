@@ -0,0 +1,53 @@
+//! src/domain/issue_text_content.rs
+
+#[derive(Debug)]
+pub struct IssueTextContent(String);
+
+impl IssueTextContent {
+    pub const MAX_LENGTH: usize = 100_000;
+
+    /// Parse and validate the plain text body of a newsletter issue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zero2prod::domain::IssueTextContent;
+    /// use assert2::assert;
+    ///
+    /// assert!(IssueTextContent::parse("Hello!".to_string()).is_ok());
+    /// assert!(IssueTextContent::parse("".to_string()).is_err());
+    /// ```
+    pub fn parse(s: String) -> Result<IssueTextContent, String> {
+        if s.trim().is_empty() {
+            return Err("The plain text content cannot be empty.".into());
+        }
+        if s.chars().count() > Self::MAX_LENGTH {
+            return Err(format!(
+                "The plain text content cannot be longer than {} characters.",
+                Self::MAX_LENGTH
+            ));
+        }
+        Ok(Self(s))
+    }
+}
+
+impl AsRef<str> for IssueTextContent {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::IssueTextContent;
+
+    #[test]
+    fn whitespace_only_content_is_rejected() {
+        assert!(IssueTextContent::parse("\n ".to_string()).is_err());
+    }
+
+    #[test]
+    fn content_longer_than_the_limit_is_rejected() {
+        assert!(IssueTextContent::parse("a".repeat(100_001)).is_err());
+    }
+}
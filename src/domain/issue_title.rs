@@ -0,0 +1,70 @@
+//! src/domain/issue_title.rs
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug)]
+pub struct IssueTitle(String);
+
+impl IssueTitle {
+    pub const MAX_LENGTH: usize = 256;
+
+    /// Parse and validate the title of a newsletter issue.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zero2prod::domain::IssueTitle;
+    /// use assert2::assert;
+    ///
+    /// let title = IssueTitle::parse("Release notes".to_string()).unwrap();
+    /// assert!(title.as_ref() == "Release notes");
+    ///
+    /// // Invalid titles are rejected
+    /// assert!(IssueTitle::parse(" ".to_string()).is_err());
+    /// assert!(IssueTitle::parse("a".repeat(257)).is_err());
+    /// ```
+    pub fn parse(s: String) -> Result<IssueTitle, String> {
+        if s.trim().is_empty() {
+            return Err("The issue title cannot be empty.".into());
+        }
+        if s.graphemes(true).count() > Self::MAX_LENGTH {
+            return Err(format!(
+                "The issue title cannot be longer than {} characters.",
+                Self::MAX_LENGTH
+            ));
+        }
+        Ok(Self(s))
+    }
+}
+
+impl AsRef<str> for IssueTitle {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::IssueTitle;
+
+    #[test]
+    fn a_256_grapheme_long_title_is_valid() {
+        let title = "ё".repeat(256);
+        assert!(IssueTitle::parse(title).is_ok());
+    }
+
+    #[test]
+    fn a_title_longer_than_256_graphemes_is_rejected() {
+        let title = "a".repeat(257);
+        assert!(IssueTitle::parse(title).is_err());
+    }
+
+    #[test]
+    fn empty_string_is_rejected() {
+        assert!(IssueTitle::parse("".to_string()).is_err());
+    }
+
+    #[test]
+    fn whitespace_only_titles_are_rejected() {
+        assert!(IssueTitle::parse(" \t".to_string()).is_err());
+    }
+}
@@ -1,9 +1,19 @@
 //! src/domain/mod.rs
 
+mod issue_html_content;
+mod issue_text_content;
+mod issue_title;
+mod new_issue;
+mod new_password;
 mod new_subscriber;
 mod subscriber_email;
 mod subscriber_name;
 
+pub use issue_html_content::IssueHtmlContent;
+pub use issue_text_content::IssueTextContent;
+pub use issue_title::IssueTitle;
+pub use new_issue::{IssueVariant, NewIssue};
+pub use new_password::{BreachedPasswords, NewPassword};
 pub use new_subscriber::NewSubscriber;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
@@ -0,0 +1,19 @@
+//! src/domain/new_issue.rs
+use crate::domain::issue_html_content::IssueHtmlContent;
+use crate::domain::issue_text_content::IssueTextContent;
+use crate::domain::issue_title::IssueTitle;
+
+pub struct NewIssue {
+    pub title: IssueTitle,
+    pub text_content: IssueTextContent,
+    pub html_content: IssueHtmlContent,
+    /// An alternative subject line, and optionally body, to test
+    /// against the issue before sending the winner to everyone.
+    pub variant: Option<IssueVariant>,
+}
+
+pub struct IssueVariant {
+    pub title: IssueTitle,
+    pub text_content: Option<IssueTextContent>,
+    pub html_content: Option<IssueHtmlContent>,
+}
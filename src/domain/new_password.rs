@@ -0,0 +1,293 @@
+//! src/domain/new_password.rs
+use anyhow::Context;
+use secrecy::{ExposeSecret, SecretString};
+use sha1::{Digest, Sha1};
+use std::path::PathBuf;
+
+/// A password that satisfies our password policy, ready to be hashed.
+pub struct NewPassword(SecretString);
+
+impl NewPassword {
+    /// Following the OWASP recommendations.
+    pub const MIN_LENGTH: usize = 12;
+    pub const MAX_LENGTH: usize = 128;
+
+    /// Parse a password chosen by `username`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use zero2prod::domain::{BreachedPasswords, NewPassword};
+    /// use assert2::assert;
+    ///
+    /// let breached = BreachedPasswords::new("tests/breached_passwords", false);
+    /// let parse = |p: &str| NewPassword::parse(p.to_string().into(), "admin", &breached);
+    ///
+    /// assert!(parse("a-long-and-unique-password").is_ok());
+    /// // Too short, the username, or known to have leaked
+    /// assert!(parse("short").is_err());
+    /// assert!(parse("admin").is_err());
+    /// assert!(parse("password1234").is_err());
+    /// ```
+    pub fn parse(
+        password: SecretString,
+        username: &str,
+        breached_passwords: &BreachedPasswords,
+    ) -> Result<NewPassword, String> {
+        let exposed = password.expose_secret();
+        let length = exposed.chars().count();
+        if length < Self::MIN_LENGTH {
+            return Err(format!(
+                "The new password must be at least {} characters long.",
+                Self::MIN_LENGTH
+            ));
+        }
+        if length > Self::MAX_LENGTH {
+            return Err(format!(
+                "The new password must be at most {} characters long.",
+                Self::MAX_LENGTH
+            ));
+        }
+        if exposed.to_lowercase() == username.to_lowercase() {
+            return Err("The new password cannot be your username.".into());
+        }
+        match breached_passwords.contains(exposed) {
+            Ok(false) => {}
+            Ok(true) => {
+                return Err(
+                    "This password has appeared in a data breach, please choose another one."
+                        .into(),
+                );
+            }
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to check a password against the breached password list"
+                );
+                return Err(
+                    "This password could not be checked against known data breaches, please try again later."
+                        .into(),
+                );
+            }
+        }
+        Ok(Self(password))
+    }
+}
+
+impl From<NewPassword> for SecretString {
+    fn from(value: NewPassword) -> Self {
+        value.0
+    }
+}
+
+/// An offline copy of a breached password list, in the k-anonymity range
+/// format of Have I Been Pwned: one `{PREFIX}.txt` file per 5-character
+/// SHA-1 prefix, listing the remaining `SUFFIX:COUNT` of each hash.
+///
+/// Only the range file of the password's prefix is read, so the full list
+/// never has to fit in memory.
+#[derive(Clone)]
+pub struct BreachedPasswords {
+    directory: PathBuf,
+    require_complete_list: bool,
+}
+
+impl BreachedPasswords {
+    /// One range file per 5-character hexadecimal prefix.
+    pub const RANGE_COUNT: usize = 16usize.pow(5);
+
+    /// `require_complete_list` should only be `false` for the small sample
+    /// used in tests: see [`Self::check_directory`] and [`Self::contains`].
+    pub fn new(directory: impl Into<PathBuf>, require_complete_list: bool) -> Self {
+        Self {
+            directory: directory.into(),
+            require_complete_list,
+        }
+    }
+
+    /// Fail unless the directory holds a range file for every prefix, so
+    /// that a wrong path or a partial download is caught at startup. An
+    /// incomplete list only has to hold at least one range file.
+    pub fn check_directory(&self) -> Result<(), anyhow::Error> {
+        let entries = std::fs::read_dir(&self.directory).with_context(|| {
+            format!(
+                "Failed to read the breached password directory {}",
+                self.directory.display()
+            )
+        })?;
+        let mut range_count = 0;
+        for entry in entries {
+            let entry = entry.with_context(|| {
+                format!(
+                    "Failed to read the breached password directory {}",
+                    self.directory.display()
+                )
+            })?;
+            if is_range_file_name(&entry.file_name().to_string_lossy()) {
+                range_count += 1;
+            }
+        }
+        if range_count == 0 {
+            anyhow::bail!(
+                "The breached password directory {} holds no range file",
+                self.directory.display()
+            );
+        }
+        if self.require_complete_list && range_count != Self::RANGE_COUNT {
+            anyhow::bail!(
+                "The breached password directory {} holds {} of the {} range files",
+                self.directory.display(),
+                range_count,
+                Self::RANGE_COUNT
+            );
+        }
+        Ok(())
+    }
+
+    /// Fails if the range file of the password cannot be read, so that a
+    /// password is never accepted without being checked. With an incomplete
+    /// list, a missing range file is logged and the password is assumed not
+    /// to be breached instead.
+    pub fn contains(&self, password: &str) -> Result<bool, anyhow::Error> {
+        let hash: String = Sha1::digest(password.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect();
+        let (prefix, suffix) = hash.split_at(5);
+        let path = self.directory.join(format!("{prefix}.txt"));
+        let range = match std::fs::read_to_string(&path) {
+            Ok(range) => range,
+            Err(e) if !self.require_complete_list && e.kind() == std::io::ErrorKind::NotFound => {
+                tracing::warn!(
+                    path = %path.display(),
+                    "The breached password list has no range for this password, accepting it"
+                );
+                return Ok(false);
+            }
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!(
+                        "Failed to read the breached password range {}",
+                        path.display()
+                    )
+                });
+            }
+        };
+        Ok(range.lines().any(|line| {
+            line.split(':')
+                .next()
+                .is_some_and(|s| s.trim().eq_ignore_ascii_case(suffix))
+        }))
+    }
+}
+
+/// `{PREFIX}.txt`, with a 5-character uppercase hexadecimal prefix.
+fn is_range_file_name(file_name: &str) -> bool {
+    file_name.strip_suffix(".txt").is_some_and(|prefix| {
+        prefix.len() == 5
+            && prefix
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('A'..='F').contains(&c))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BreachedPasswords, NewPassword};
+    use assert2::assert;
+
+    fn breached_passwords() -> BreachedPasswords {
+        BreachedPasswords::new("tests/breached_passwords", false)
+    }
+
+    fn parse(password: &str, username: &str) -> Result<NewPassword, String> {
+        NewPassword::parse(password.to_string().into(), username, &breached_passwords())
+    }
+
+    fn parse_with(
+        password: &str,
+        breached_passwords: &BreachedPasswords,
+    ) -> Result<NewPassword, String> {
+        NewPassword::parse(password.to_string().into(), "admin", breached_passwords)
+    }
+
+    #[test]
+    fn a_12_character_password_is_valid() {
+        assert!(parse(&"ё".repeat(12), "admin").is_ok());
+    }
+
+    #[test]
+    fn an_11_character_password_is_rejected() {
+        assert!(parse(&"a".repeat(11), "admin").is_err());
+    }
+
+    #[test]
+    fn a_128_character_password_is_valid() {
+        assert!(parse(&"a".repeat(128), "admin").is_ok());
+    }
+
+    #[test]
+    fn a_129_character_password_is_rejected() {
+        assert!(parse(&"a".repeat(129), "admin").is_err());
+    }
+
+    #[test]
+    fn the_username_is_rejected_regardless_of_case() {
+        assert!(parse("Administrator", "administrator").is_err());
+    }
+
+    #[test]
+    fn breached_passwords_are_rejected() {
+        assert!(parse("correcthorsebatterystaple", "admin").is_err());
+    }
+
+    #[test]
+    fn passwords_outside_of_the_list_are_accepted() {
+        assert!(
+            !breached_passwords()
+                .contains("a-long-and-unique-password")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn the_sample_list_passes_the_directory_check() {
+        assert!(breached_passwords().check_directory().is_ok());
+    }
+
+    #[test]
+    fn the_sample_list_fails_the_complete_directory_check() {
+        let breached_passwords = BreachedPasswords::new("tests/breached_passwords", true);
+        assert!(breached_passwords.check_directory().is_err());
+    }
+
+    #[test]
+    fn missing_or_empty_directories_fail_the_directory_check() {
+        let empty = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir(&empty).unwrap();
+
+        assert!(
+            BreachedPasswords::new("does/not/exist", false)
+                .check_directory()
+                .is_err()
+        );
+        assert!(
+            BreachedPasswords::new(&empty, false)
+                .check_directory()
+                .is_err()
+        );
+        std::fs::remove_dir(&empty).unwrap();
+    }
+
+    #[test]
+    fn a_missing_range_rejects_the_password_when_the_list_must_be_complete() {
+        let breached_passwords = BreachedPasswords::new("tests/breached_passwords", true);
+        assert!(
+            breached_passwords
+                .contains("a-long-and-unique-password")
+                .is_err()
+        );
+        assert!(parse_with("a-long-and-unique-password", &breached_passwords).is_err());
+    }
+}
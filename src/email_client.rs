@@ -1,7 +1,12 @@
 //! src/email_client.rs
 use crate::domain::SubscriberEmail;
+use crate::metrics::record_email_provider_call;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
 use reqwest::Client;
 use secrecy::{ExposeSecret, SecretString};
+use std::collections::BTreeMap;
+use std::time::Instant;
 
 pub struct EmailClient {
     http_client: Client,
@@ -18,6 +23,57 @@ struct SendEmailRequest<'a> {
     subject: &'a str,
     html_body: &'a str,
     text_body: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<AttachmentRequest<'a>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: &'a BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    track_opens: bool,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct AttachmentRequest<'a> {
+    name: &'a str,
+    content: &'a str,
+    content_type: &'a str,
+    #[serde(rename = "ContentID", skip_serializing_if = "Option::is_none")]
+    content_id: Option<String>,
+}
+
+pub struct EmailAttachment {
+    pub file_name: String,
+    pub content_type: String,
+    /// Set for inline images, which the HTML body references as
+    /// `cid:<content_id>`.
+    pub content_id: Option<String>,
+    /// Base64-encoded, as sent to the provider.
+    content: String,
+}
+
+impl EmailAttachment {
+    pub fn new(
+        file_name: String,
+        content_type: String,
+        content_id: Option<String>,
+        content: &[u8],
+    ) -> Self {
+        Self {
+            file_name,
+            content_type,
+            content_id,
+            content: STANDARD.encode(content),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EmailOptions<'a> {
+    pub attachments: &'a [EmailAttachment],
+    /// Echoed back by the provider in its webhooks.
+    pub metadata: BTreeMap<String, String>,
+    /// Ask the provider to report opens, whatever the server's default.
+    pub track_opens: bool,
 }
 
 impl EmailClient {
@@ -36,23 +92,62 @@ impl EmailClient {
         }
     }
 
+    /// Check that the provider can be reached. Any HTTP response will do.
+    pub async fn ping(&self) -> Result<(), reqwest::Error> {
+        self.http_client.head(&self.base_url).send().await?;
+        Ok(())
+    }
+
     pub async fn send_email(
         &self,
         recipient: &SubscriberEmail,
         subject: &str,
         html_content: &str,
         text_content: &str,
+    ) -> Result<(), reqwest::Error> {
+        self.send_email_with_options(
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            &EmailOptions::default(),
+        )
+        .await
+    }
+
+    pub async fn send_email_with_options(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        options: &EmailOptions<'_>,
     ) -> Result<(), reqwest::Error> {
         let url = format!("{}/email", self.base_url);
+        let attachments = options
+            .attachments
+            .iter()
+            .map(|a| AttachmentRequest {
+                name: &a.file_name,
+                content: &a.content,
+                content_type: &a.content_type,
+                content_id: a.content_id.as_ref().map(|id| format!("cid:{id}")),
+            })
+            .collect();
         let request_body = SendEmailRequest {
             from: self.sender.as_ref(),
             to: recipient.as_ref(),
             subject,
             html_body: html_content,
             text_body: text_content,
+            attachments,
+            metadata: &options.metadata,
+            track_opens: options.track_opens,
         };
 
-        self.http_client
+        let started_at = Instant::now();
+        let outcome = self
+            .http_client
             .post(url)
             .header(
                 "X-Postmark-Server-Token",
@@ -60,8 +155,10 @@ impl EmailClient {
             )
             .json(&request_body)
             .send()
-            .await?
-            .error_for_status()?;
+            .await
+            .and_then(|r| r.error_for_status());
+        record_email_provider_call(&outcome, started_at);
+        outcome?;
         Ok(())
     }
 }
@@ -69,7 +166,7 @@ impl EmailClient {
 #[cfg(test)]
 mod tests {
     use crate::domain::SubscriberEmail;
-    use crate::email_client::EmailClient;
+    use crate::email_client::{EmailAttachment, EmailClient, EmailOptions};
     use fake::faker::internet::en::SafeEmail;
     use fake::faker::lorem::en::{Paragraph, Sentence};
     use fake::{Fake, Faker};
@@ -137,6 +234,76 @@ mod tests {
         // Assert
     }
 
+    #[tokio::test]
+    async fn attachments_are_sent_base64_encoded() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let attachments = [EmailAttachment::new(
+            "logo.png".into(),
+            "image/png".into(),
+            Some("logo.png".into()),
+            b"not really a png",
+        )];
+        let options = EmailOptions {
+            attachments: &attachments,
+            ..Default::default()
+        };
+        email_client
+            .send_email_with_options(&email(), &subject(), &content(), &content(), &options)
+            .await
+            .unwrap();
+
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(
+            body["Attachments"],
+            serde_json::json!([{
+                "Name": "logo.png",
+                "Content": "bm90IHJlYWxseSBhIHBuZw==",
+                "ContentType": "image/png",
+                "ContentID": "cid:logo.png"
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn opens_are_tracked_only_when_asked_for() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let options = EmailOptions {
+            track_opens: true,
+            ..Default::default()
+        };
+        email_client
+            .send_email_with_options(&email(), &subject(), &content(), &content(), &options)
+            .await
+            .unwrap();
+        email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await
+            .unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let tracked: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        let untracked: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!(tracked["TrackOpens"], true);
+        assert!(untracked.get("TrackOpens").is_none());
+    }
+
     #[tokio::test]
     async fn send_email_succeeds_if_the_server_returns_200() {
         let mock_server = MockServer::start().await;
@@ -174,6 +341,22 @@ mod tests {
         assert!(outcome.is_err());
     }
 
+    #[tokio::test]
+    async fn ping_succeeds_whatever_the_status_returned_by_the_server() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.ping().await;
+
+        assert!(outcome.is_ok());
+    }
+
     #[tokio::test]
     async fn send_email_times_out_if_the_server_takes_too_long() {
         let mock_server = MockServer::start().await;
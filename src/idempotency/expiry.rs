@@ -0,0 +1,69 @@
+//! src/idempotency/expiry.rs
+use crate::configuration::{IdempotencySettings, Settings};
+use crate::startup::get_connection_pool;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+
+/// Rows deleted per statement, to keep locks and WAL bursts small.
+const BATCH_SIZE: i64 = 1000;
+
+/// Delete the keys saved more than `retention` ago, returning how many were
+/// deleted.
+#[tracing::instrument(skip(pool))]
+pub async fn delete_expired_idempotency_keys(
+    pool: &PgPool,
+    retention: chrono::Duration,
+) -> Result<u64, anyhow::Error> {
+    let expired_before = Utc::now() - retention;
+    let mut n_deleted = 0;
+    loop {
+        // Keys being taken over by a request are locked: leave them be.
+        let n_deleted_in_batch = sqlx::query!(
+            r#"
+            DELETE FROM idempotency
+            WHERE (user_id, idempotency_key) IN (
+                SELECT user_id, idempotency_key
+                FROM idempotency
+                WHERE created_at < $1
+                FOR UPDATE
+                SKIP LOCKED
+                LIMIT $2
+            )
+            "#,
+            expired_before,
+            BATCH_SIZE
+        )
+        .execute(pool)
+        .await
+        .context("Failed to delete expired idempotency keys")?
+        .rows_affected();
+        n_deleted += n_deleted_in_batch;
+        if n_deleted_in_batch < BATCH_SIZE as u64 {
+            return Ok(n_deleted);
+        }
+    }
+}
+
+async fn cleanup_loop(pool: PgPool, settings: IdempotencySettings) -> Result<(), anyhow::Error> {
+    loop {
+        match delete_expired_idempotency_keys(&pool, settings.retention()).await {
+            Ok(n_deleted) => {
+                tracing::info!(n_deleted, "Deleted expired idempotency keys");
+            }
+            Err(e) => {
+                tracing::error!(
+                error.cause_chain= ?e,
+                error.message= %e,
+                    "Failed to delete expired idempotency keys",
+                );
+            }
+        }
+        tokio::time::sleep(settings.cleanup_interval()).await;
+    }
+}
+
+pub async fn run_cleanup_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    cleanup_loop(connection_pool, configuration.idempotency).await
+}
@@ -0,0 +1,205 @@
+//! src/idempotency/middleware.rs
+use super::{IdempotencyKey, NextAction, save_response, try_processing};
+use crate::authentication::UserId;
+use crate::configuration::IdempotencySettings;
+use crate::utils::{e400, e500};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::middleware::Next;
+use actix_web::web::{self, Bytes};
+use actix_web::{FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use std::future::{Ready, ready};
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+
+const FIELD_NAME: &str = "idempotency_key";
+const HEADER_NAME: &str = "Idempotency-Key";
+const CSRF_FIELD_NAME: &str = "csrf_token";
+
+/// The transaction holding the idempotency key of the request. Handlers
+/// make their changes in it, so that they are committed along with the
+/// saved response - or not at all.
+#[derive(Clone)]
+pub struct IdempotencyTransaction(Arc<Mutex<Transaction<'static, Postgres>>>);
+
+impl IdempotencyTransaction {
+    pub async fn lock(&self) -> MutexGuard<'_, Transaction<'static, Postgres>> {
+        self.0.lock().await
+    }
+}
+
+impl FromRequest for IdempotencyTransaction {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<IdempotencyTransaction, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<IdempotencyTransaction>()
+                .cloned()
+                .ok_or_else(|| e500("The route is not wrapped in `enforce_idempotency`")),
+        )
+    }
+}
+
+/// Marks responses to requests rejected before making any change, e.g.
+/// invalid forms: they are not saved, so that the key can be used again.
+pub struct Unprocessed;
+
+/// The flash message of a route, to show again when one of its redirects is
+/// replayed. Registered as app data of the route.
+pub struct ReplayMessage(pub &'static str);
+
+/// Process a state-changing request at most once per idempotency key: a
+/// request reusing the key of an earlier one gets the response saved for it.
+///
+/// The key is read from the `Idempotency-Key` header or, for URL-encoded
+/// forms, the `idempotency_key` field. Keys are scoped per user - requests
+/// made with an API token share them with the sessions of its user.
+///
+/// Reusing a key for a request with another method, path or body is
+/// rejected with a `422 Unprocessable Entity`. Server errors are not saved,
+/// so that the request can be retried. Handlers write through the
+/// `IdempotencyTransaction` extractor. Must be registered inside of
+/// `reject_anonymous_users`.
+pub async fn enforce_idempotency(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    if req.method().is_safe() {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_boxed_body);
+    }
+
+    let user_id = req
+        .extensions()
+        .get::<UserId>()
+        .map(|user_id| **user_id)
+        .ok_or_else(|| e500("The idempotency middleware requires an authenticated user"))?;
+    let body = req.extract::<Bytes>().await?;
+    let request_fingerprint = fingerprint(&req, &body);
+    let submitted = req
+        .headers()
+        .get(HEADER_NAME)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string)
+        .or_else(|| {
+            is_url_encoded_form(req.headers())
+                .then(|| field_value(&body))
+                .flatten()
+        });
+    // Put the body back for the handler.
+    req.set_payload(Payload::from(body));
+    let idempotency_key: IdempotencyKey = submitted
+        .ok_or_else(|| anyhow::anyhow!("The request does not carry an idempotency key"))
+        .and_then(IdempotencyKey::try_from)
+        .map_err(e400)?;
+
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .expect("The database pool is not registered as application data");
+    let settings = req
+        .app_data::<web::Data<IdempotencySettings>>()
+        .expect("The idempotency settings are not registered as application data");
+    let transaction = match try_processing(
+        pool,
+        &idempotency_key,
+        user_id,
+        &request_fingerprint,
+        settings,
+    )
+    .await
+    .map_err(e500)?
+    {
+        NextAction::StartProcessing(t) => t,
+        NextAction::ReturnSavedResponse(saved_response) => {
+            // Flash messages are not part of the saved response: they are
+            // added on the way out, by an outer middleware.
+            if saved_response.status().is_redirection()
+                && let Some(ReplayMessage(message)) = req.app_data::<ReplayMessage>()
+            {
+                FlashMessage::info(*message).send();
+            }
+            return Ok(req.into_response(saved_response));
+        }
+        NextAction::StillProcessing => {
+            let response = HttpResponse::Conflict()
+                .insert_header((header::RETRY_AFTER, "1"))
+                .body("This request is still being processed. Please try again shortly.");
+            return Ok(req.into_response(response));
+        }
+        NextAction::KeyReused => {
+            let response = HttpResponse::UnprocessableEntity()
+                .body("This idempotency key was already used for a different request.");
+            return Ok(req.into_response(response));
+        }
+    };
+
+    let transaction = Arc::new(Mutex::new(transaction));
+    req.extensions_mut()
+        .insert(IdempotencyTransaction(Arc::clone(&transaction)));
+    // On errors, the transaction is rolled back and the key released.
+    let response = next.call(req).await?.map_into_boxed_body();
+    response
+        .request()
+        .extensions_mut()
+        .remove::<IdempotencyTransaction>();
+    if response.status().is_server_error()
+        || response.response().extensions().contains::<Unprocessed>()
+    {
+        return Ok(response);
+    }
+    let transaction = Arc::try_unwrap(transaction)
+        .map_err(|_| e500("The handler kept hold of the idempotency transaction"))?
+        .into_inner();
+    let (request, response) = response.into_parts();
+    let response = save_response(transaction, &idempotency_key, user_id, response)
+        .await
+        .map_err(e500)?;
+    Ok(ServiceResponse::new(request, response))
+}
+
+/// Identifies the request a key was first used for.
+///
+/// The CSRF token of forms is left out: it changes with the session, and a
+/// retry after logging in again is still the same request.
+fn fingerprint(req: &ServiceRequest, body: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(req.method().as_str());
+    hasher.update(b" ");
+    hasher.update(req.path());
+    hasher.update(b"\n");
+    let form_fields = is_url_encoded_form(req.headers())
+        .then(|| serde_urlencoded::from_bytes::<Vec<(String, String)>>(body).ok())
+        .flatten();
+    match form_fields {
+        Some(mut fields) => {
+            fields.retain(|(name, _)| name != CSRF_FIELD_NAME);
+            hasher.update(serde_urlencoded::to_string(fields).unwrap_or_default());
+        }
+        None => hasher.update(body),
+    }
+    hasher.finalize().to_vec()
+}
+
+fn is_url_encoded_form(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.starts_with("application/x-www-form-urlencoded"))
+}
+
+fn field_value(urlencoded: &[u8]) -> Option<String> {
+    let urlencoded = std::str::from_utf8(urlencoded).ok()?;
+    web::Query::<HashMap<String, String>>::from_query(urlencoded)
+        .ok()?
+        .into_inner()
+        .remove(FIELD_NAME)
+}
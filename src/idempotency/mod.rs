@@ -1,9 +1,13 @@
 //! src/idempotency/mod.rs
 
+mod expiry;
 mod key;
+mod middleware;
 mod persistence;
 
+pub use expiry::{delete_expired_idempotency_keys, run_cleanup_until_stopped};
 pub use key::IdempotencyKey;
+pub use middleware::{IdempotencyTransaction, ReplayMessage, Unprocessed, enforce_idempotency};
 pub use persistence::get_saved_response;
 pub use persistence::save_response;
 pub use persistence::{NextAction, try_processing};
@@ -1,12 +1,17 @@
 //! src/idempotency/persistence.rs
 use super::IdempotencyKey;
+use crate::configuration::IdempotencySettings;
 use actix_web::HttpResponse;
 use actix_web::body::to_bytes;
 use actix_web::http::StatusCode;
+use chrono::Utc;
 use sqlx::{Executor, PgPool};
 use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
 
+/// Raised by Postgres when `lock_timeout` elapses.
+const LOCK_NOT_AVAILABLE: &str = "55P03";
+
 #[derive(Debug, sqlx::Type)]
 #[sqlx(type_name = "header_pair")]
 struct HeaderPairRecord {
@@ -18,6 +23,10 @@ struct HeaderPairRecord {
 pub enum NextAction {
     StartProcessing(Transaction<'static, Postgres>),
     ReturnSavedResponse(HttpResponse),
+    /// Another request with the same key is still being processed.
+    StillProcessing,
+    /// The key was used for a different request.
+    KeyReused,
 }
 
 pub async fn get_saved_response(
@@ -99,34 +108,111 @@ pub async fn save_response(
     Ok(http_response)
 }
 
+/// Keys saved more than the retention period ago have expired: they are
+/// taken over as if they were new, even before the cleanup job deletes them.
+///
+/// While another request holds the key, we wait for it to save its response
+/// - up to the configured wait timeout.
+///
+/// `request_fingerprint` tells retries of a request apart from different
+/// requests reusing its key.
 pub async fn try_processing(
     pool: &PgPool,
     idempotency_key: &IdempotencyKey,
     user_id: Uuid,
+    request_fingerprint: &[u8],
+    settings: &IdempotencySettings,
 ) -> Result<NextAction, anyhow::Error> {
     let mut transaction = pool.begin().await?;
+    // Inserting a key held by another transaction blocks until it ends.
+    sqlx::query!(
+        "SELECT set_config('lock_timeout', $1, true)",
+        settings.wait_timeout_milliseconds.to_string()
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
     let query = sqlx::query!(
         r#"
         INSERT INTO idempotency (
              user_id,
              idempotency_key,
+             request_fingerprint,
              created_at
         )
-        VALUES ($1, $2, now())
-        ON CONFLICT DO NOTHING
+        VALUES ($1, $2, $4, now())
+        ON CONFLICT (user_id, idempotency_key) DO UPDATE
+        SET
+            created_at = now(),
+            request_fingerprint = $4,
+            response_status_code = NULL,
+            response_headers = NULL,
+            response_body = NULL
+        WHERE idempotency.created_at < $3
         "#,
         user_id,
-        idempotency_key.as_ref()
+        idempotency_key.as_ref(),
+        Utc::now() - settings.retention(),
+        request_fingerprint
     );
 
-    let n_inserted_rows = transaction.execute(query).await?.rows_affected();
+    let n_inserted_rows = match transaction.execute(query).await {
+        Ok(outcome) => outcome.rows_affected(),
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some(LOCK_NOT_AVAILABLE) => {
+            return Ok(NextAction::StillProcessing);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    // Only the wait for the key is bounded, not the queries of the handler.
+    transaction
+        .execute("SET LOCAL lock_timeout = DEFAULT")
+        .await?;
 
     if n_inserted_rows > 0 {
         Ok(NextAction::StartProcessing(transaction))
     } else {
+        let Some(saved_fingerprint) =
+            get_request_fingerprint(pool, idempotency_key, user_id).await?
+        else {
+            // Deleted by the cleanup job in the meantime: the key is new again.
+            transaction.rollback().await?;
+            return Box::pin(try_processing(
+                pool,
+                idempotency_key,
+                user_id,
+                request_fingerprint,
+                settings,
+            ))
+            .await;
+        };
+        // Keys saved before fingerprints were recorded have none.
+        if saved_fingerprint.is_some_and(|f| f != request_fingerprint) {
+            return Ok(NextAction::KeyReused);
+        }
         let saved_response = get_saved_response(pool, idempotency_key, user_id)
             .await?
             .ok_or_else(|| anyhow::anyhow!("We expected a saved response we didn't find it"))?;
         Ok(NextAction::ReturnSavedResponse(saved_response))
     }
 }
+
+/// `None` if the key is not saved.
+async fn get_request_fingerprint(
+    pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<Option<Option<Vec<u8>>>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT request_fingerprint
+        FROM idempotency
+        WHERE
+            user_id = $1 AND
+            idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| r.request_fingerprint))
+}
@@ -1,10 +1,15 @@
 //! src/issue_delivery_worker.rs
+use crate::configuration::TrackingSettings;
 use crate::domain::SubscriberEmail;
-use crate::email_client::EmailClient;
+use crate::email_client::{EmailAttachment, EmailClient, EmailOptions};
+use crate::metrics::record_email_delivery;
+use crate::telemetry::set_parent_trace_context;
+use crate::tracking::add_tracking;
 use crate::{configuration::Settings, startup::get_connection_pool};
 use sqlx::{Executor, PgPool, Postgres, Transaction};
+use std::collections::BTreeMap;
 use std::time::Duration;
-use tracing::{Span, field::display};
+use tracing::{Instrument, Span, field::display};
 use uuid::Uuid;
 
 pub enum ExecutionOutcome {
@@ -12,6 +17,28 @@ pub enum ExecutionOutcome {
     EmptyQueue,
 }
 
+/// The attachments of the issue being delivered, loaded and encoded once
+/// rather than for each recipient.
+#[derive(Default)]
+pub struct AttachmentCache {
+    issue_id: Option<Uuid>,
+    attachments: Vec<EmailAttachment>,
+}
+
+impl AttachmentCache {
+    async fn get(
+        &mut self,
+        pool: &PgPool,
+        issue_id: Uuid,
+    ) -> Result<&[EmailAttachment], anyhow::Error> {
+        if self.issue_id != Some(issue_id) {
+            self.attachments = get_issue_attachments(pool, issue_id).await?;
+            self.issue_id = Some(issue_id);
+        }
+        Ok(&self.attachments)
+    }
+}
+
 #[tracing::instrument(
 skip_all, fields(
     newsletter_issue_id=tracing::field::Empty,
@@ -21,39 +48,74 @@ skip_all, fields(
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
+    base_url: &str,
+    tracking: &TrackingSettings,
+    attachments: &mut AttachmentCache,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
-    if let Some((transaction, issue_id, email)) = dequeue_task(pool).await? {
+    if let Some((transaction, issue_id, email, variant, trace_context)) = dequeue_task(pool).await?
+    {
         Span::current()
             .record("newsletter_issue_id", display(issue_id))
             .record("subscriber_email", display(&email));
 
-        match SubscriberEmail::parse(email.clone()) {
-            Ok(email) => {
-                let issue = get_issue(pool, issue_id).await?;
-                if let Err(e) = email_client
-                    .send_email(
-                        &email,
-                        &issue.title,
-                        &issue.html_content,
-                        &issue.text_content,
-                    )
-                    .await
-                {
+        // Continue the trace of the request that published the issue.
+        let delivery_span = tracing::info_span!("Deliver issue email");
+        if let Some(trace_context) = &trace_context {
+            set_parent_trace_context(&delivery_span, trace_context);
+        }
+        async {
+            match SubscriberEmail::parse(email.clone()) {
+                Ok(email) => {
+                    let issue = get_issue(pool, issue_id, variant.as_deref()).await?;
+                    // Link indexes in tracked URLs refer to the issue's own HTML
+                    // body, so a variant with a different body is sent untracked.
+                    let html_content = if tracking.enabled && !issue.html_overridden {
+                        tracked_html_content(pool, &issue, issue_id, &email, base_url).await?
+                    } else {
+                        issue.html_content
+                    };
+                    let attachments = attachments.get(pool, issue_id).await?;
+                    let mut metadata = BTreeMap::new();
+                    metadata.insert("newsletter_issue_id".to_string(), issue_id.to_string());
+                    if let Some(variant) = issue.variant {
+                        metadata.insert("variant".to_string(), variant);
+                    }
+                    let options = EmailOptions {
+                        attachments,
+                        metadata,
+                        // A/B tests are decided on the opens reported by the provider.
+                        track_opens: tracking.enabled,
+                    };
+                    let outcome = email_client
+                        .send_email_with_options(
+                            &email,
+                            &issue.title,
+                            &html_content,
+                            &issue.text_content,
+                            &options,
+                        )
+                        .await;
+                    record_email_delivery(&outcome);
+                    if let Err(e) = outcome {
+                        tracing::error!(
+                        error.cause_chain= ?e,
+                        error.message= %e,
+                            "Failed to deliver issue to a confirmed subscriber. Skipping.",
+                        );
+                    }
+                }
+                Err(e) => {
                     tracing::error!(
                     error.cause_chain= ?e,
                     error.message= %e,
-                        "Failed to deliver issue to a confirmed subscriber. Skipping.",
+                        "Skipping a confirmed subscriber. Their stored contact details are invalid",
                     );
                 }
             }
-            Err(e) => {
-                tracing::error!(
-                error.cause_chain= ?e,
-                error.message= %e,
-                    "Skipping a confirmed subscriber. Their stored contact details are invalid",
-                );
-            }
+            Ok::<(), anyhow::Error>(())
         }
+        .instrument(delivery_span)
+        .await?;
 
         delete_task(transaction, issue_id, &email).await?;
         Ok(ExecutionOutcome::TaskCompleted)
@@ -63,16 +125,27 @@ pub async fn try_execute_task(
 }
 
 type PgTransaction = Transaction<'static, Postgres>;
+/// Transaction holding the lock, issue id, subscriber email, A/B variant and
+/// the trace context of the enqueuing request.
+type Task = (PgTransaction, Uuid, String, Option<String>, Option<String>);
 #[tracing::instrument(skip_all)]
-async fn dequeue_task(
-    pool: &PgPool,
-) -> Result<Option<(PgTransaction, Uuid, String)>, anyhow::Error> {
+async fn dequeue_task(pool: &PgPool) -> Result<Option<Task>, anyhow::Error> {
     let mut transaction = pool.begin().await?;
     let r = sqlx::query!(
         r#"
-            SELECT newsletter_issue_id, subscriber_email
-            FROM issue_delivery_queue
-            FOR UPDATE
+            SELECT q.newsletter_issue_id, q.subscriber_email, q.variant, q.trace_context
+            FROM issue_delivery_queue q
+            JOIN newsletter_issues i
+                ON i.newsletter_issue_id = q.newsletter_issue_id
+            WHERE
+                i.delivery_status = 'active' AND
+                -- Recipients outside of an A/B test wait for its winner.
+                (
+                    q.variant IS NOT NULL OR
+                    i.ab_test_ends_at IS NULL OR
+                    i.ab_test_winner IS NOT NULL
+                )
+            FOR UPDATE OF q
             SKIP LOCKED
             LIMIT 1
         "#,
@@ -84,6 +157,8 @@ async fn dequeue_task(
             transaction,
             r.newsletter_issue_id,
             r.subscriber_email,
+            r.variant,
+            r.trace_context,
         )))
     } else {
         Ok(None)
@@ -115,28 +190,100 @@ struct NewsletterIssue {
     title: String,
     text_content: String,
     html_content: String,
+    variant: Option<String>,
+    html_overridden: bool,
 }
 
+/// Fetch the content to send, taking the A/B test `variant` (or, outside of
+/// the test group, the winning variant) into account.
 #[tracing::instrument(skip_all)]
-async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
+async fn get_issue(
+    pool: &PgPool,
+    issue_id: Uuid,
+    variant: Option<&str>,
+) -> Result<NewsletterIssue, anyhow::Error> {
     let issue = sqlx::query_as!(
         NewsletterIssue,
         r#"
-    SELECT title, text_content, html_content
-    FROM newsletter_issues
+    SELECT
+        COALESCE(v.title, i.title) as "title!",
+        COALESCE(v.text_content, i.text_content) as "text_content!",
+        COALESCE(v.html_content, i.html_content) as "html_content!",
+        v.variant as "variant?",
+        v.html_content IS NOT NULL as "html_overridden!"
+    FROM newsletter_issues i
+    LEFT JOIN newsletter_issue_variants v
+        ON v.newsletter_issue_id = i.newsletter_issue_id AND
+        v.variant = COALESCE($2, i.ab_test_winner)
     WHERE
-    newsletter_issue_id = $1
+    i.newsletter_issue_id = $1
     "#,
-        issue_id
+        issue_id,
+        variant
     )
     .fetch_one(pool)
     .await?;
     Ok(issue)
 }
 
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
+#[tracing::instrument(skip_all)]
+async fn get_issue_attachments(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Vec<EmailAttachment>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+    SELECT file_name, content_type, content_id, content
+    FROM newsletter_issue_attachments
+    WHERE
+    newsletter_issue_id = $1
+    ORDER BY created_at
+    "#,
+        issue_id
+    )
+    .fetch_all(pool)
+    .await?;
+    let attachments = rows
+        .into_iter()
+        .map(|r| EmailAttachment::new(r.file_name, r.content_type, r.content_id, &r.content))
+        .collect();
+    Ok(attachments)
+}
+
+#[tracing::instrument(skip_all)]
+async fn tracked_html_content(
+    pool: &PgPool,
+    issue: &NewsletterIssue,
+    issue_id: Uuid,
+    email: &SubscriberEmail,
+    base_url: &str,
+) -> Result<String, anyhow::Error> {
+    let subscriber = sqlx::query!(
+        r#"
+    SELECT id
+    FROM subscriptions
+    WHERE email = $1
+    "#,
+        email.as_ref()
+    )
+    .fetch_optional(pool)
+    .await?;
+    let html_content = match subscriber {
+        Some(s) => add_tracking(&issue.html_content, base_url, issue_id, s.id),
+        None => issue.html_content.clone(),
+    };
+    Ok(html_content)
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    base_url: String,
+    tracking: TrackingSettings,
+) -> Result<(), anyhow::Error> {
+    let mut attachments = AttachmentCache::default();
     loop {
-        match try_execute_task(&pool, &email_client).await {
+        match try_execute_task(&pool, &email_client, &base_url, &tracking, &mut attachments).await {
             Ok(ExecutionOutcome::EmptyQueue) => {
                 tokio::time::sleep(Duration::from_secs(10)).await;
             }
@@ -151,5 +298,11 @@ async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyh
 pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
     let email_client = configuration.email_client.client();
-    worker_loop(connection_pool, email_client).await
+    worker_loop(
+        connection_pool,
+        email_client,
+        configuration.application.base_url,
+        configuration.tracking,
+    )
+    .await
 }
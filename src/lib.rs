@@ -1,14 +1,19 @@
 #![doc = include_str!("../README.md")]
 //!src/lib.rs
 
+pub mod ab_testing;
+pub mod audit_log;
 pub mod authentication;
 pub mod configuration;
 pub mod domain;
 pub mod email_client;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod metrics;
+pub mod migrations;
 pub mod routes;
 pub mod session_state;
 pub mod startup;
 pub mod telemetry;
+pub mod tracking;
 pub mod utils;
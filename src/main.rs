@@ -1,28 +1,40 @@
 //! src/main.rs
 use std::fmt::{Debug, Display};
 use tokio::task::JoinError;
+use zero2prod::ab_testing::run_ab_tests_until_stopped;
 use zero2prod::configuration::get_configuration;
+use zero2prod::idempotency::run_cleanup_until_stopped;
 use zero2prod::issue_delivery_worker::run_worker_until_stopped;
 use zero2prod::startup::Application;
-use zero2prod::telemetry::{get_subscriber, init_subscriber};
+use zero2prod::telemetry::{get_subscriber, init_subscriber, shutdown_tracer_provider};
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    let subscriber = get_subscriber("zero2prod".into(), "info".into(), std::io::stdout);
-    init_subscriber(subscriber);
-
     let configuration = get_configuration().expect("Failed to read configuration");
 
+    let subscriber = get_subscriber(
+        "zero2prod".into(),
+        "info".into(),
+        std::io::stdout,
+        &configuration.otlp,
+    );
+    init_subscriber(subscriber);
+
     let application = Application::build(configuration.clone()).await?;
 
     let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    let worker_task = tokio::spawn(run_worker_until_stopped(configuration.clone()));
+    let ab_test_task = tokio::spawn(run_ab_tests_until_stopped(configuration.clone()));
+    let cleanup_task = tokio::spawn(run_cleanup_until_stopped(configuration));
 
     tokio::select! {
         o = application_task => report_exit("API", o),
         o = worker_task => report_exit("Background worker", o),
+        o = ab_test_task => report_exit("A/B test decisions", o),
+        o = cleanup_task => report_exit("Idempotency cleanup", o),
     };
 
+    shutdown_tracer_provider();
     Ok(())
 }
 
@@ -0,0 +1,141 @@
+//! src/metrics.rs
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::PgPool;
+use std::sync::LazyLock;
+use std::time::Instant;
+
+/// Metrics recorded as things happen, shared by the API and the worker.
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+static HTTP_REQUESTS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new("http_requests_total", "HTTP requests handled"),
+        &["method", "route", "status"],
+    ))
+});
+
+static HTTP_REQUEST_DURATION_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new(
+            "http_request_duration_seconds",
+            "Time taken to handle HTTP requests",
+        ),
+        &["method", "route"],
+    ))
+});
+
+static EMAILS_SENT_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(
+        Opts::new(
+            "issue_delivery_emails_total",
+            "Issue emails the worker tried to deliver",
+        ),
+        &["outcome"],
+    ))
+});
+
+static EMAIL_PROVIDER_DURATION_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new(
+            "email_provider_request_duration_seconds",
+            "Time taken by the email provider to answer",
+        ),
+        &["outcome"],
+    ))
+});
+
+fn register<M: prometheus::core::Collector + Clone + 'static>(metric: prometheus::Result<M>) -> M {
+    let metric = metric.expect("Invalid metric definition");
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("Metric registered twice");
+    metric
+}
+
+fn outcome<T, E>(result: &Result<T, E>) -> &'static str {
+    if result.is_ok() { "success" } else { "failure" }
+}
+
+/// Record the outcome of delivering an issue email.
+pub fn record_email_delivery<T, E>(result: &Result<T, E>) {
+    EMAILS_SENT_TOTAL
+        .with_label_values(&[outcome(result)])
+        .inc();
+}
+
+/// Record how long a call to the email provider took.
+pub fn record_email_provider_call<T, E>(result: &Result<T, E>, started_at: Instant) {
+    EMAIL_PROVIDER_DURATION_SECONDS
+        .with_label_values(&[outcome(result)])
+        .observe(started_at.elapsed().as_secs_f64());
+}
+
+/// Count and time requests per route. Routes are labelled with their
+/// pattern, e.g. `/admin/issues/{issue_id}/stats`, to bound the number of
+/// series.
+pub async fn track_http_metrics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let method = req.method().to_string();
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let started_at = Instant::now();
+    let response = next.call(req).await;
+    let status = match &response {
+        Ok(response) => response.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    HTTP_REQUESTS_TOTAL
+        .with_label_values(&[method.as_str(), route.as_str(), status.as_str()])
+        .inc();
+    HTTP_REQUEST_DURATION_SECONDS
+        .with_label_values(&[method.as_str(), route.as_str()])
+        .observe(started_at.elapsed().as_secs_f64());
+    response
+}
+
+/// All metrics in the Prometheus text format.
+///
+/// Gauges are measured now, in a registry of their own, so that concurrent
+/// scrapes of different pools do not mix.
+#[tracing::instrument(name = "Gather metrics", skip(pool))]
+pub async fn gather(pool: &PgPool) -> Result<String, anyhow::Error> {
+    let gauges = Registry::new();
+    let connections = IntGaugeVec::new(
+        Opts::new("db_pool_connections", "Connections of the database pool"),
+        &["state"],
+    )?;
+    let idle = pool.num_idle() as i64;
+    connections.with_label_values(&["idle"]).set(idle);
+    connections
+        .with_label_values(&["in_use"])
+        .set(i64::from(pool.size()) - idle);
+    gauges.register(Box::new(connections))?;
+    let queue_depth = IntGauge::new(
+        "issue_delivery_queue_depth",
+        "Emails waiting to be delivered",
+    )?;
+    queue_depth.set(get_issue_delivery_queue_depth(pool).await?);
+    gauges.register(Box::new(queue_depth))?;
+
+    let mut metric_families = REGISTRY.gather();
+    metric_families.extend(gauges.gather());
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&metric_families, &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+async fn get_issue_delivery_queue_depth(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query!(r#"SELECT count(*) as "count!" FROM issue_delivery_queue"#)
+        .fetch_one(pool)
+        .await?;
+    Ok(row.count)
+}
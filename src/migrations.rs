@@ -0,0 +1,62 @@
+//! src/migrations.rs
+use anyhow::Context;
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::{PgConnection, PgPool};
+
+/// The migrations of `migrations/`, embedded in the binary.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Check that the database schema is not ahead of this binary and, if
+/// `apply_pending` is set, apply the migrations it has not seen yet.
+///
+/// Runs under the Postgres advisory lock also taken by `sqlx migrate run`:
+/// replicas starting together wait for the first one to be done rather
+/// than racing to apply the same migrations.
+#[tracing::instrument(name = "Migrate the database", skip(pool))]
+pub async fn migrate_database(pool: &PgPool, apply_pending: bool) -> Result<(), anyhow::Error> {
+    let mut connection = pool
+        .acquire()
+        .await
+        .context("Failed to connect to the database")?;
+    connection
+        .lock()
+        .await
+        .context("Failed to take the migration lock")?;
+    let outcome = check_and_apply(&mut connection, apply_pending).await;
+    let unlocked = connection.unlock().await;
+    if unlocked.is_err() {
+        // The lock is held until the session ends.
+        connection.close_on_drop();
+    }
+    outcome?;
+    unlocked.context("Failed to release the migration lock")?;
+    Ok(())
+}
+
+async fn check_and_apply(
+    connection: &mut PgConnection,
+    apply_pending: bool,
+) -> Result<(), anyhow::Error> {
+    connection.ensure_migrations_table().await?;
+    let latest_unknown = connection
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .filter(|version| !MIGRATOR.version_exists(*version))
+        .max();
+    if let Some(version) = latest_unknown {
+        anyhow::bail!(
+            "The database schema is ahead of this binary: migration {} was applied by a more \
+            recent release. Deploy that release, or a later one.",
+            version
+        );
+    }
+    if apply_pending {
+        MIGRATOR
+            .run_direct(connection)
+            .await
+            .context("Failed to apply the database migrations")?;
+    }
+    Ok(())
+}
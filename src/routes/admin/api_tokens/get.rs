@@ -0,0 +1,125 @@
+//! src/routes/admin/api_tokens/get.rs
+use crate::authentication::{CsrfToken, Role, UserId};
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct ApiTokenSummary {
+    api_token_id: Uuid,
+    name: String,
+    scope: String,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+pub async fn list_api_tokens(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    user_id: ReqData<UserId>,
+    role: ReqData<Role>,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+
+    let tokens = get_api_tokens(&pool, **user_id).await.map_err(e500)?;
+    let csrf_field = csrf_token.form_field();
+
+    let mut rows_html = String::new();
+    for token in tokens {
+        let last_used = token
+            .last_used_at
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "never".into());
+        let (status, actions_html) = match token.revoked_at {
+            Some(_) => ("revoked", String::new()),
+            None => (
+                "active",
+                format!(
+                    r#"<form action="/admin/api-tokens/{}/revoke" method="post">{csrf_field}<button type="submit">revoke</button></form>"#,
+                    token.api_token_id
+                ),
+            ),
+        };
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{last_used}</td><td>{status}</td><td>{actions_html}</td></tr>",
+            escape_html(&token.name),
+            token.scope,
+            token.created_at.format("%Y-%m-%d %H:%M"),
+        )
+        .unwrap();
+    }
+
+    // Tokens cannot be given more access than their user has.
+    let mut scopes_html = String::new();
+    for scope in Role::ALL.into_iter().filter(|scope| scope <= &*role) {
+        writeln!(scopes_html, r#"<option value="{scope}">{scope}</option>"#).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>API tokens</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>API tokens give scripts access to the admin endpoints, with an
+                <code>Authorization: Bearer &lt;token&gt;</code> header.</p>
+                <table>
+                    <tr><th>Name</th><th>Scope</th><th>Created</th><th>Last used</th><th>Status</th><th></th></tr>
+                    {rows_html}
+                </table>
+                <p>Create a token:</p>
+                <form action="/admin/api-tokens" method="post">
+                    {csrf_field}
+                    <label>Name
+                        <input type="text" placeholder="e.g. Release notes CI" name="name">
+                    </label>
+                    <label>Scope
+                        <select name="scope">
+                            {scopes_html}
+                        </select>
+                    </label>
+                    <button type="submit">Create</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
+
+#[tracing::instrument(name = "Get API tokens", skip(pool))]
+async fn get_api_tokens(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<ApiTokenSummary>, anyhow::Error> {
+    let tokens = sqlx::query_as!(
+        ApiTokenSummary,
+        r#"
+        SELECT api_token_id, name, scope, created_at, last_used_at, revoked_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve API tokens")?;
+    Ok(tokens)
+}
@@ -0,0 +1,6 @@
+//! src/routes/admin/api_tokens/mod.rs
+mod get;
+mod post;
+
+pub use get::list_api_tokens;
+pub use post::{create_api_token, revoke_api_token};
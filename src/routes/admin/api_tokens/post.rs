@@ -0,0 +1,127 @@
+//! src/routes/admin/api_tokens/post.rs
+use crate::audit_log::{AuditAction, record_audit_event};
+use crate::authentication::{Role, UserId, issue_api_token};
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::http::header::{CacheControl, CacheDirective, ContentType};
+use actix_web::web::ReqData;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use unicode_segmentation::UnicodeSegmentation;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    name: String,
+    scope: String,
+}
+
+#[tracing::instrument(
+    name = "Create an API token",
+    skip(request, form, pool, user_id, role),
+    fields(user_id = %*user_id, scope = %form.scope)
+)]
+pub async fn create_api_token(
+    request: HttpRequest,
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+    role: ReqData<Role>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = form.name.trim();
+    if name.is_empty() || name.graphemes(true).count() > 100 {
+        FlashMessage::error("The token name must be between 1 and 100 characters long.").send();
+        return Ok(see_other("/admin/api-tokens"));
+    }
+    let scope = match Role::parse(&form.scope) {
+        Ok(scope) if scope <= *role => scope,
+        Ok(scope) => {
+            FlashMessage::error(format!("You cannot create a token with the {scope} scope."))
+                .send();
+            return Ok(see_other("/admin/api-tokens"));
+        }
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/api-tokens"));
+        }
+    };
+
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let token = issue_api_token(**user_id, name, scope, &mut transaction)
+        .await
+        .map_err(e500)?;
+    record_audit_event(
+        &mut *transaction,
+        &request,
+        Some(**user_id),
+        AuditAction::CreateApiToken,
+        Some(name.to_string()),
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    // Tokens are only stored hashed: this is the one chance to see them, so
+    // they are rendered directly instead of redirecting, and never cached.
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>New API token</title>
+            </head>
+            <body>
+                <p>The token {} has been created with the {scope} scope.</p>
+                <p>Copy it now, it will not be shown again:</p>
+                <p><code>{}</code></p>
+                <p><a href="/admin/api-tokens">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+            escape_html(name),
+            token.expose_secret()
+        )))
+}
+
+#[tracing::instrument(name = "Revoke an API token", skip(request, pool, user_id))]
+pub async fn revoke_api_token(
+    request: HttpRequest,
+    api_token_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let api_token_id = api_token_id.into_inner();
+    // Users can only revoke their own tokens.
+    let revoked = sqlx::query!(
+        r#"
+        UPDATE api_tokens
+        SET revoked_at = now()
+        WHERE api_token_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        api_token_id,
+        **user_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to revoke the API token")
+    .map_err(e500)?
+    .rows_affected();
+
+    if revoked > 0 {
+        record_audit_event(
+            pool.get_ref(),
+            &request,
+            Some(**user_id),
+            AuditAction::RevokeApiToken,
+            Some(api_token_id.to_string()),
+        )
+        .await
+        .map_err(e500)?;
+        FlashMessage::info("The token has been revoked.").send();
+    }
+    Ok(see_other("/admin/api-tokens"))
+}
@@ -0,0 +1,163 @@
+//! src/routes/admin/audit_log.rs
+use crate::audit_log::AuditAction;
+use crate::utils::{e400, e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+const PAGE_SIZE: i64 = 50;
+
+#[derive(serde::Deserialize)]
+pub struct QueryParameters {
+    action: Option<String>,
+    username: Option<String>,
+    #[serde(default)]
+    page: u32,
+}
+
+struct AuditLogEntry {
+    created_at: DateTime<Utc>,
+    username: Option<String>,
+    action: String,
+    target: Option<String>,
+    ip_address: String,
+}
+
+/// Most recent entries first, `PAGE_SIZE` at a time.
+pub async fn audit_log(
+    parameters: web::Query<QueryParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let QueryParameters {
+        action,
+        username,
+        page,
+    } = parameters.into_inner();
+    // Empty form fields mean "any".
+    let action = action
+        .filter(|a| !a.is_empty())
+        .map(|a| AuditAction::parse(&a))
+        .transpose()
+        .map_err(e400)?;
+    let username = username.filter(|u| !u.trim().is_empty());
+
+    let mut entries = get_audit_log_entries(&pool, action, username.as_deref(), page)
+        .await
+        .map_err(e500)?;
+    let has_older_entries = entries.len() as i64 > PAGE_SIZE;
+    entries.truncate(PAGE_SIZE as usize);
+
+    let mut rows_html = String::new();
+    for e in entries {
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            e.created_at.format("%Y-%m-%d %H:%M:%S"),
+            e.username
+                .as_deref()
+                .map(escape_html)
+                .unwrap_or_else(|| "anonymous".into()),
+            e.action,
+            escape_html(e.target.as_deref().unwrap_or("")),
+            escape_html(&e.ip_address),
+        )
+        .unwrap();
+    }
+
+    let mut actions_html = String::from(r#"<option value="">any</option>"#);
+    for a in AuditAction::ALL {
+        let selected = if Some(a) == action { " selected" } else { "" };
+        write!(
+            actions_html,
+            r#"<option value="{a}"{selected}>{a}</option>"#
+        )
+        .unwrap();
+    }
+
+    let page_link = |page: u32| {
+        let query = serde_urlencoded::to_string([
+            ("action", action.map(|a| a.as_str()).unwrap_or("")),
+            ("username", username.as_deref().unwrap_or("")),
+            ("page", &page.to_string()),
+        ])
+        .unwrap();
+        format!("/admin/audit-log?{}", escape_html(&query))
+    };
+    let mut pages_html = String::new();
+    if page > 0 {
+        write!(
+            pages_html,
+            r#"<a href="{}">Newer</a> "#,
+            page_link(page - 1)
+        )
+        .unwrap();
+    }
+    if has_older_entries {
+        write!(pages_html, r#"<a href="{}">Older</a>"#, page_link(page + 1)).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Audit log</title>
+            </head>
+            <body>
+                <form action="/admin/audit-log" method="get">
+                    <label>Action
+                        <select name="action">{actions_html}</select>
+                    </label>
+                    <label>Username
+                        <input type="text" name="username" value="{}">
+                    </label>
+                    <button type="submit">Filter</button>
+                </form>
+                <table>
+                    <tr><th>Time</th><th>User</th><th>Action</th><th>Target</th><th>IP address</th></tr>
+                    {rows_html}
+                </table>
+                <p>{pages_html}</p>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+            escape_html(username.as_deref().unwrap_or(""))
+        )))
+}
+
+/// Returns up to one more entry than a page holds, to tell whether there
+/// is a next page.
+#[tracing::instrument(name = "Get audit log entries", skip(pool))]
+async fn get_audit_log_entries(
+    pool: &PgPool,
+    action: Option<AuditAction>,
+    username: Option<&str>,
+    page: u32,
+) -> Result<Vec<AuditLogEntry>, anyhow::Error> {
+    let entries = sqlx::query_as!(
+        AuditLogEntry,
+        r#"
+        SELECT a.created_at, u.username as "username?", a.action, a.target, a.ip_address
+        FROM audit_log a
+        LEFT JOIN users u ON u.user_id = a.user_id
+        WHERE
+            ($1::text IS NULL OR a.action = $1) AND
+            ($2::text IS NULL OR u.username = $2)
+        ORDER BY a.created_at DESC, a.audit_log_id
+        LIMIT $3 OFFSET $4
+        "#,
+        action.map(|a| a.as_str()),
+        username,
+        PAGE_SIZE + 1,
+        i64::from(page) * PAGE_SIZE
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the audit log")?;
+    Ok(entries)
+}
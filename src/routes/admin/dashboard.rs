@@ -1,24 +1,27 @@
 //! src/routes/admin/dashboard.rs
-use crate::session_state::TypedSession;
-use crate::utils::e500;
+use crate::authentication::{CsrfToken, Role, UserId};
+use crate::utils::{e500, escape_html};
 use actix_web::http::header::ContentType;
-use actix_web::http::header::LOCATION;
+use actix_web::web::ReqData;
 use actix_web::{HttpResponse, web};
 use anyhow::Context;
 use sqlx::PgPool;
 use uuid::Uuid;
 
 pub async fn admin_dashboard(
-    session: TypedSession,
     pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+    role: ReqData<Role>,
+    csrf_token: CsrfToken,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let username = if let Some(user_id) = session.get_user_id().map_err(e500)? {
-        get_username(user_id, &pool).await.map_err(e500)?
+    let username = escape_html(&get_username(**user_id, &pool).await.map_err(e500)?);
+    let users_html = if *role == Role::Owner {
+        r#"<li><a href="/admin/users">Users</a></li>
+                    <li><a href="/admin/audit-log">Audit log</a></li>"#
     } else {
-        return Ok(HttpResponse::SeeOther()
-            .insert_header((LOCATION, "/login"))
-            .finish());
+        ""
     };
+    let csrf_field = csrf_token.form_field();
     Ok(HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -32,9 +35,16 @@ pub async fn admin_dashboard(
                 <p>Welcome {username}!</p>
                 <p>Available actions:</p>
                 <ol>
+                    <li><a href="/admin/issues">Newsletter issues</a></li>
+                    {users_html}
                     <li><a href="/admin/password">Change password</a></li>
+                    <li><a href="/admin/email">Change email</a></li>
+                    <li><a href="/admin/totp">Two-factor authentication</a></li>
+                    <li><a href="/admin/sessions">Sessions</a></li>
+                    <li><a href="/admin/api-tokens">API tokens</a></li>
                     <li>
                         <form name="logoutForm" action="/admin/logout" method="post">
+                            {csrf_field}
                             <input type="submit" value="Logout">
                         </form>
                     </li>
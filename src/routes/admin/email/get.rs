@@ -0,0 +1,79 @@
+//! src/routes/admin/email/get.rs
+use crate::authentication::{CsrfToken, UserId};
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+pub async fn change_email_form(
+    flash_message: IncomingFlashMessages,
+    csrf_token: CsrfToken,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = get_email(**user_id, &pool).await.map_err(e500)?;
+    let current_html = match email {
+        Some(email) => format!("<p>Your email is {}.</p>", escape_html(&email)),
+        None => "<p>You have not set an email yet.</p>".to_string(),
+    };
+
+    let mut msg_html = String::new();
+    for m in flash_message.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+    let csrf_field = csrf_token.form_field();
+
+    Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Change Email</title>
+            </head>
+            <body>
+                {msg_html}
+                {current_html}
+                <p>Password reset links are sent to this address.</p>
+                <form action="/admin/email" method="post">
+                    {csrf_field}
+                    <label>Current password
+                        <input type="password" placeholder="Enter current password" name="current_password">
+                    </label>
+                <br>
+                    <label>New email
+                        <input type="email" placeholder="Enter the new email" name="email">
+                    </label>
+                <br>
+                    <button type="submit">Change email</button>
+                </form>
+                    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#,
+    )))
+}
+
+#[tracing::instrument(name = "Get email", skip(pool))]
+async fn get_email(user_id: Uuid, pool: &PgPool) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT email
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to retrieve the email of the user.")?;
+    Ok(row.email)
+}
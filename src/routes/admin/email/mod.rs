@@ -0,0 +1,5 @@
+//! src/routes/admin/email/mod.rs
+mod get;
+mod post;
+pub use get::change_email_form;
+pub use post::change_email;
@@ -0,0 +1,108 @@
+//! src/routes/admin/email/post.rs
+use crate::audit_log::{AuditAction, record_audit_event};
+use crate::authentication::UserId;
+use crate::authentication::{AuthError, Credentials, validate_credentials};
+use crate::configuration::PasswordHashingSettings;
+use crate::domain::SubscriberEmail;
+use crate::routes::admin::dashboard::get_username;
+use crate::utils::{e500, see_other};
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::SecretString;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    current_password: SecretString,
+    email: String,
+}
+
+#[tracing::instrument(name = "Change email", skip_all, fields(user_id = %**user_id))]
+pub async fn change_email(
+    request: HttpRequest,
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    hashing: web::Data<PasswordHashingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let FormData {
+        current_password,
+        email,
+    } = form.0;
+
+    let email = match SubscriberEmail::parse(email.trim().to_string()) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/email"));
+        }
+    };
+
+    let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    let credentials = Credentials {
+        username,
+        password: current_password,
+    };
+    if let Err(e) = validate_credentials(credentials, &hashing, &pool).await {
+        return match e {
+            AuthError::InvalidCredentials(_) => {
+                FlashMessage::error("The current password is incorrect.").send();
+                Ok(see_other("/admin/email"))
+            }
+            AuthError::UnexpectedError(_) => Err(e500(e)),
+        };
+    }
+
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    match update_email(&mut transaction, *user_id, &email).await {
+        Ok(()) => {}
+        Err(e)
+            if e.as_database_error()
+                .is_some_and(|e| e.is_unique_violation()) =>
+        {
+            FlashMessage::error("Another user already has this email.").send();
+            return Ok(see_other("/admin/email"));
+        }
+        Err(e) => {
+            return Err(e500(
+                anyhow::Error::new(e).context("Failed to update the email"),
+            ));
+        }
+    }
+    record_audit_event(
+        &mut *transaction,
+        &request,
+        Some(*user_id),
+        AuditAction::ChangeEmail,
+        None,
+    )
+    .await
+    .context("Failed to record the email change")
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    FlashMessage::info("Your email has been changed.").send();
+    Ok(see_other("/admin/email"))
+}
+
+#[tracing::instrument(skip_all)]
+async fn update_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    email: &SubscriberEmail,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE users
+        SET email = $1
+        WHERE user_id = $2
+        "#,
+        email.as_ref(),
+        user_id
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
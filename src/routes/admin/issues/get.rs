@@ -0,0 +1,107 @@
+//! src/routes/admin/issues/get.rs
+use crate::authentication::CsrfToken;
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct IssueSummary {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: String,
+    delivery_status: String,
+    remaining_deliveries: i64,
+}
+
+pub async fn list_issues(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+
+    let issues = get_issues(&pool).await.map_err(e500)?;
+    let csrf_field = csrf_token.form_field();
+
+    let mut rows_html = String::new();
+    for issue in issues {
+        let id = issue.newsletter_issue_id;
+        let mut actions_html = String::new();
+        let actions: &[&str] = match issue.delivery_status.as_str() {
+            "active" if issue.remaining_deliveries > 0 => &["pause", "cancel"],
+            "paused" => &["resume", "cancel"],
+            _ => &[],
+        };
+        for action in actions {
+            write!(
+                actions_html,
+                r#"<form action="/admin/issues/{id}/{action}" method="post">{csrf_field}<button type="submit">{action}</button></form>"#
+            )
+            .unwrap();
+        }
+        writeln!(
+            rows_html,
+            r#"<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{actions_html}</td><td><a href="/admin/issues/{id}/stats">Stats</a></td></tr>"#,
+            escape_html(&issue.title),
+            issue.published_at, issue.delivery_status, issue.remaining_deliveries
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Newsletter issues</title>
+            </head>
+            <body>
+                {msg_html}
+                <table>
+                    <tr><th>Title</th><th>Published at</th><th>Delivery</th><th>Remaining</th><th></th><th></th></tr>
+                    {rows_html}
+                </table>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
+
+#[tracing::instrument(name = "Get newsletter issues", skip(pool))]
+async fn get_issues(pool: &PgPool) -> Result<Vec<IssueSummary>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        IssueSummary,
+        r#"
+        SELECT
+            i.newsletter_issue_id,
+            i.title,
+            i.published_at,
+            i.delivery_status,
+            (
+                SELECT COUNT(*)
+                FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id
+            ) as "remaining_deliveries!"
+        FROM newsletter_issues i
+        ORDER BY i.published_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve newsletter issues")?;
+    Ok(issues)
+}
@@ -0,0 +1,8 @@
+//! src/routes/admin/issues/mod.rs
+mod get;
+mod post;
+mod stats;
+
+pub use get::list_issues;
+pub use post::{cancel_issue, pause_issue, resume_issue};
+pub use stats::issue_stats;
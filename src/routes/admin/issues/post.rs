@@ -0,0 +1,189 @@
+//! src/routes/admin/issues/post.rs
+use crate::audit_log::{AuditAction, record_audit_event};
+use crate::authentication::UserId;
+use crate::utils::{e500, see_other};
+use actix_web::web::ReqData;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[tracing::instrument(name = "Pause issue delivery", skip(request, pool, user_id))]
+pub async fn pause_issue(
+    request: HttpRequest,
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let updated = set_delivery_status(&mut transaction, *issue_id, &["active"], "paused")
+        .await
+        .map_err(e500)?;
+    if updated {
+        record_audit_event(
+            &mut *transaction,
+            &request,
+            Some(**user_id),
+            AuditAction::PauseIssue,
+            Some(issue_id.to_string()),
+        )
+        .await
+        .map_err(e500)?;
+    } else if !issue_exists(&mut transaction, *issue_id)
+        .await
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    transaction.commit().await.map_err(e500)?;
+    if updated {
+        FlashMessage::info("Delivery of the issue has been paused.").send();
+    } else {
+        FlashMessage::error("Only issues that are being delivered can be paused.").send();
+    }
+    Ok(see_other("/admin/issues"))
+}
+
+#[tracing::instrument(name = "Resume issue delivery", skip(request, pool, user_id))]
+pub async fn resume_issue(
+    request: HttpRequest,
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let updated = set_delivery_status(&mut transaction, *issue_id, &["paused"], "active")
+        .await
+        .map_err(e500)?;
+    if updated {
+        record_audit_event(
+            &mut *transaction,
+            &request,
+            Some(**user_id),
+            AuditAction::ResumeIssue,
+            Some(issue_id.to_string()),
+        )
+        .await
+        .map_err(e500)?;
+    } else if !issue_exists(&mut transaction, *issue_id)
+        .await
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    transaction.commit().await.map_err(e500)?;
+    if updated {
+        FlashMessage::info("Delivery of the issue has been resumed.").send();
+    } else {
+        FlashMessage::error("Only paused issues can be resumed.").send();
+    }
+    Ok(see_other("/admin/issues"))
+}
+
+#[tracing::instrument(name = "Cancel issue delivery", skip(request, pool, user_id))]
+pub async fn cancel_issue(
+    request: HttpRequest,
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let updated = set_delivery_status(
+        &mut transaction,
+        *issue_id,
+        &["active", "paused"],
+        "cancelled",
+    )
+    .await
+    .map_err(e500)?;
+    if updated {
+        delete_pending_deliveries(&mut transaction, *issue_id)
+            .await
+            .map_err(e500)?;
+        record_audit_event(
+            &mut *transaction,
+            &request,
+            Some(**user_id),
+            AuditAction::CancelIssue,
+            Some(issue_id.to_string()),
+        )
+        .await
+        .map_err(e500)?;
+    } else if !issue_exists(&mut transaction, *issue_id)
+        .await
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    transaction.commit().await.map_err(e500)?;
+    if updated {
+        FlashMessage::info("Delivery of the issue has been cancelled.").send();
+    } else {
+        FlashMessage::error("The issue has already been cancelled.").send();
+    }
+    Ok(see_other("/admin/issues"))
+}
+
+/// Returns `false` if the issue does not exist or is not in one of the
+/// `from` statuses.
+async fn set_delivery_status(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+    from: &[&str],
+    to: &str,
+) -> Result<bool, anyhow::Error> {
+    let from: Vec<String> = from.iter().map(|s| s.to_string()).collect();
+    let query = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET delivery_status = $3
+        WHERE newsletter_issue_id = $1 AND delivery_status = ANY($2)
+        "#,
+        issue_id,
+        &from,
+        to
+    );
+    let n_updated_rows = transaction
+        .execute(query)
+        .await
+        .context("Failed to update the issue delivery status")?
+        .rows_affected();
+    Ok(n_updated_rows > 0)
+}
+
+async fn issue_exists(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to look the issue up")?;
+    Ok(row.is_some())
+}
+
+async fn delete_pending_deliveries(
+    transaction: &mut Transaction<'_, Postgres>,
+    issue_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete pending deliveries")?;
+    Ok(())
+}
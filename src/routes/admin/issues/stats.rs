@@ -0,0 +1,135 @@
+//! src/routes/admin/issues/stats.rs
+use crate::ab_testing::get_variant_results;
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct LinkClicks {
+    link_url: String,
+    clicks: i64,
+    unique_clicks: i64,
+}
+
+pub async fn issue_stats(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue_id = issue_id.into_inner();
+    let Some(title) = get_issue_title(&pool, issue_id).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let title = escape_html(&title);
+    let unique_opens = get_unique_opens(&pool, issue_id).await.map_err(e500)?;
+    let link_clicks = get_link_clicks(&pool, issue_id).await.map_err(e500)?;
+    let variant_results = get_variant_results(pool.get_ref(), issue_id)
+        .await
+        .map_err(e500)?;
+
+    let mut variants_html = String::new();
+    if !variant_results.is_empty() {
+        variants_html.push_str(
+            "<table>\n<tr><th>Variant</th><th>Recipients</th><th>Unique opens</th></tr>\n",
+        );
+        for v in variant_results {
+            writeln!(
+                variants_html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                v.variant, v.recipients, v.unique_opens
+            )
+            .unwrap();
+        }
+        variants_html.push_str("</table>");
+    }
+
+    let mut clicks_html = String::new();
+    for l in link_clicks {
+        writeln!(
+            clicks_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_html(&l.link_url),
+            l.clicks,
+            l.unique_clicks
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Issue stats</title>
+            </head>
+            <body>
+                <h1>{title}</h1>
+                <p>Unique opens: {unique_opens}</p>
+                {variants_html}
+                <table>
+                    <tr><th>Link</th><th>Clicks</th><th>Unique clicks</th></tr>
+                    {clicks_html}
+                </table>
+                <p><a href="/admin/issues">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
+
+#[tracing::instrument(name = "Get issue title", skip(pool))]
+async fn get_issue_title(pool: &PgPool, issue_id: Uuid) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT title
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the issue title")?;
+    Ok(row.map(|r| r.title))
+}
+
+#[tracing::instrument(name = "Get unique opens", skip(pool))]
+async fn get_unique_opens(pool: &PgPool, issue_id: Uuid) -> Result<i64, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT COUNT(DISTINCT subscriber_id) as "unique_opens!"
+        FROM newsletter_issue_events
+        WHERE newsletter_issue_id = $1 AND event_type = 'open'
+        "#,
+        issue_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count unique opens")?;
+    Ok(row.unique_opens)
+}
+
+#[tracing::instrument(name = "Get link clicks", skip(pool))]
+async fn get_link_clicks(pool: &PgPool, issue_id: Uuid) -> Result<Vec<LinkClicks>, anyhow::Error> {
+    let clicks = sqlx::query_as!(
+        LinkClicks,
+        r#"
+        SELECT
+            link_url as "link_url!",
+            COUNT(*) as "clicks!",
+            COUNT(DISTINCT subscriber_id) as "unique_clicks!"
+        FROM newsletter_issue_events
+        WHERE newsletter_issue_id = $1 AND event_type = 'click'
+        GROUP BY link_url
+        ORDER BY COUNT(*) DESC
+        "#,
+        issue_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to count link clicks")?;
+    Ok(clicks)
+}
@@ -1,13 +1,36 @@
 //! src/routes.admin/logout.rs
+use crate::audit_log::{AuditAction, record_audit_event};
+use crate::authentication::{UserId, revoke_user_session};
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
-use actix_web::HttpResponse;
+use actix_web::web::ReqData;
+use actix_web::{HttpRequest, HttpResponse, web};
 use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
 
-pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+pub async fn log_out(
+    request: HttpRequest,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
     if session.get_user_id().map_err(e500)?.is_none() {
         Ok(see_other("/login"))
     } else {
+        if let Some(session_id) = session.get_session_id().map_err(e500)? {
+            revoke_user_session(**user_id, session_id, pool.get_ref())
+                .await
+                .map_err(e500)?;
+        }
+        record_audit_event(
+            pool.get_ref(),
+            &request,
+            Some(**user_id),
+            AuditAction::LogOut,
+            None,
+        )
+        .await
+        .map_err(e500)?;
         session.log_out();
         FlashMessage::info("You have successfully logged out.").send();
         Ok(see_other("/login"))
@@ -1,10 +1,24 @@
 //! src/routes/admin/mod.rs
+mod api_tokens;
+mod audit_log;
 mod dashboard;
+mod email;
+mod issues;
 mod logout;
 mod newsletter;
 mod password;
+mod sessions;
+mod totp;
+mod users;
 
-pub use dashboard::admin_dashboard;
+pub use api_tokens::*;
+pub use audit_log::audit_log;
+pub use dashboard::{admin_dashboard, get_username};
+pub use email::*;
+pub use issues::*;
 pub use logout::log_out;
 pub use newsletter::*;
 pub use password::*;
+pub use sessions::*;
+pub use totp::*;
+pub use users::*;
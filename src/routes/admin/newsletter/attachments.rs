@@ -0,0 +1,224 @@
+//! src/routes/admin/newsletter/attachments.rs
+use crate::authentication::{UserId, check_multipart_csrf_token};
+use crate::configuration::AttachmentSettings;
+use crate::utils::{e500, see_other};
+use actix_multipart::form::MultipartForm;
+use actix_multipart::form::bytes::Bytes;
+use actix_multipart::form::text::Text;
+use actix_web::web::ReqData;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+#[derive(MultipartForm)]
+pub struct UploadForm {
+    csrf_token: Option<Text<String>>,
+    file: Bytes,
+    inline: Option<Text<String>>,
+}
+
+pub struct PendingAttachment {
+    pub attachment_id: Uuid,
+    pub file_name: String,
+    pub content_type: String,
+    pub content_id: Option<String>,
+    pub size: i32,
+}
+
+#[tracing::instrument(
+    name = "Upload a newsletter attachment",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn upload_attachment(
+    request: HttpRequest,
+    MultipartForm(form): MultipartForm<UploadForm>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+    settings: web::Data<AttachmentSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let UploadForm {
+        csrf_token,
+        file,
+        inline,
+    } = form;
+    check_multipart_csrf_token(&request, csrf_token.as_ref().map(|t| t.as_str()))?;
+
+    let file_name = match file.file_name.as_deref().map(sanitize_file_name) {
+        Some(file_name) if !file_name.is_empty() => file_name,
+        _ => {
+            FlashMessage::error("Please choose a file to attach.").send();
+            return Ok(see_other("/admin/newsletters"));
+        }
+    };
+    if file.data.is_empty() {
+        FlashMessage::error("The attachment is empty.").send();
+        return Ok(see_other("/admin/newsletters"));
+    }
+    if file.data.len() > settings.max_file_size_bytes {
+        FlashMessage::error(too_large_message(settings.max_file_size_bytes)).send();
+        return Ok(see_other("/admin/newsletters"));
+    }
+    let content_type = file
+        .content_type
+        .map(|m| m.to_string())
+        .unwrap_or_else(|| "application/octet-stream".into());
+    let attachment_id = Uuid::new_v4();
+    // Derived from the attachment id rather than the file name, so that
+    // images with the same name do not replace each other in the email.
+    let content_id = if inline.is_some() {
+        if !content_type.starts_with("image/") {
+            FlashMessage::error("Only images can be embedded inline.").send();
+            return Ok(see_other("/admin/newsletters"));
+        }
+        Some(attachment_id.simple().to_string())
+    } else {
+        None
+    };
+
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    // Concurrent uploads of the same user wait here, so that they cannot
+    // exceed the total size together.
+    let pending_size = get_pending_attachments_size(&mut transaction, *user_id)
+        .await
+        .map_err(e500)?;
+    if pending_size as usize + file.data.len() > settings.max_total_size_bytes {
+        FlashMessage::error(format!(
+            "The attachments of an issue cannot exceed {} bytes in total.",
+            settings.max_total_size_bytes
+        ))
+        .send();
+        return Ok(see_other("/admin/newsletters"));
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issue_attachments (
+            attachment_id,
+            user_id,
+            file_name,
+            content_type,
+            content_id,
+            content,
+            created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, now())
+        "#,
+        attachment_id,
+        *user_id,
+        file_name,
+        content_type,
+        content_id,
+        file.data.as_ref()
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store the attachment")
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    FlashMessage::info(format!("{file_name} has been attached.")).send();
+    Ok(see_other("/admin/newsletters"))
+}
+
+#[tracing::instrument(
+    name = "Remove a newsletter attachment",
+    skip_all,
+    fields(user_id=%*user_id)
+)]
+pub async fn delete_attachment(
+    attachment_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Attachments that already went out with an issue are kept.
+    sqlx::query!(
+        r#"
+        DELETE FROM newsletter_issue_attachments
+        WHERE
+            attachment_id = $1 AND
+            user_id = $2 AND
+            newsletter_issue_id IS NULL
+        "#,
+        *attachment_id,
+        **user_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to delete the attachment")
+    .map_err(e500)?;
+    Ok(see_other("/admin/newsletters"))
+}
+
+pub fn too_large_message(max_file_size_bytes: usize) -> String {
+    format!("The attachment is too large - files cannot exceed {max_file_size_bytes} bytes.")
+}
+
+/// Attachments uploaded by `user_id` that have not been published yet.
+#[tracing::instrument(name = "Get pending attachments", skip(pool))]
+pub async fn get_pending_attachments(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<PendingAttachment>, anyhow::Error> {
+    let attachments = sqlx::query_as!(
+        PendingAttachment,
+        r#"
+        SELECT
+            attachment_id,
+            file_name,
+            content_type,
+            content_id,
+            octet_length(content) as "size!"
+        FROM newsletter_issue_attachments
+        WHERE user_id = $1 AND newsletter_issue_id IS NULL
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve pending attachments")?;
+    Ok(attachments)
+}
+
+/// The size of the pending attachments of `user_id`. The user stays locked
+/// until the end of `transaction`.
+async fn get_pending_attachments_size(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<i64, anyhow::Error> {
+    sqlx::query!(
+        "SELECT user_id FROM users WHERE user_id = $1 FOR UPDATE",
+        user_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to lock the user")?;
+    let row = sqlx::query!(
+        r#"
+        SELECT COALESCE(SUM(octet_length(content)), 0) as "size!"
+        FROM newsletter_issue_attachments
+        WHERE user_id = $1 AND newsletter_issue_id IS NULL
+        "#,
+        user_id
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .context("Failed to compute the size of pending attachments")?;
+    Ok(row.size)
+}
+
+// Browsers may send a full path; keep the last component only.
+fn sanitize_file_name(file_name: &str) -> String {
+    file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
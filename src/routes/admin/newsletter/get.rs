@@ -1,15 +1,62 @@
 //! src/routes/admin/newsletter/get.rs
-use actix_web::HttpResponse;
+use super::attachments::get_pending_attachments;
+use crate::authentication::{CsrfToken, UserId};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, escape_html};
 use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{HttpResponse, web};
 use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
 use std::fmt::Write;
 
 pub async fn publish_newsletter_form(
     flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+    csrf_token: CsrfToken,
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+    let draft = session
+        .take_newsletter_draft()
+        .map_err(e500)?
+        .unwrap_or_default();
+    let title = escape_html(&draft.title);
+    let text_content = escape_html(&draft.text_content);
+    let html_content = escape_html(&draft.html_content);
+    let variant_title = escape_html(&draft.variant_title);
+    let variant_text_content = escape_html(&draft.variant_text_content);
+    let variant_html_content = escape_html(&draft.variant_html_content);
+    let csrf_field = csrf_token.form_field();
+    let mut attachments_html = String::new();
+    for a in get_pending_attachments(&pool, **user_id)
+        .await
+        .map_err(e500)?
+    {
+        let inline_html = match &a.content_id {
+            Some(content_id) => {
+                format!(" - inline as <code>cid:{}</code>", escape_html(content_id))
+            }
+            None => String::new(),
+        };
+        writeln!(
+            attachments_html,
+            r#"<li>{} ({}, {} bytes){inline_html}
+                <form action="/admin/newsletters/attachments/{}/delete" method="post">
+                    {csrf_field}
+                    <button type="submit">Remove</button>
+                </form>
+            </li>"#,
+            escape_html(&a.file_name),
+            escape_html(&a.content_type),
+            a.size,
+            a.attachment_id
+        )
+        .unwrap();
     }
     let idempotency_key = uuid::Uuid::new_v4().to_string();
     Ok(HttpResponse::Ok()
@@ -23,9 +70,20 @@ pub async fn publish_newsletter_form(
             </head>
             <body>
                 {msg_html}
+                <p>Attachments:</p>
+                <ul>
+                    {attachments_html}
+                </ul>
+                <form action="/admin/newsletters/attachments" method="post" enctype="multipart/form-data">
+                    {csrf_field}
+                    <input type="file" name="file">
+                    <label><input type="checkbox" name="inline" value="on"> Inline image</label>
+                    <button type="submit">Attach</button>
+                </form>
                 <form action="/admin/newsletters" method="post">
+                    {csrf_field}
                     <label>Title:<br>
-                        <input type="text" placeholder="Enter the issue title" name="title">
+                        <input type="text" placeholder="Enter the issue title" name="title" value="{title}">
                     </label>
                     <br>
                     <label>Plain text content:<br>
@@ -34,7 +92,7 @@ pub async fn publish_newsletter_form(
                             name="text_content"
                             rows="20"
                             cols="50"
-                        ></textarea>
+                        >{text_content}</textarea>
                     </label>
                     <br>
                     <label>HTML content:<br>
@@ -43,9 +101,34 @@ pub async fn publish_newsletter_form(
                             name="html_content"
                             rows="20"
                             cols="50"
-                        ></textarea>
+                        >{html_content}</textarea>
                     </label>
                     <br>
+                    <fieldset>
+                        <legend>A/B test (optional)</legend>
+                        <label>Variant title:<br>
+                            <input type="text" placeholder="Leave empty to skip the test" name="variant_title" value="{variant_title}">
+                        </label>
+                        <br>
+                        <label>Variant plain text content:<br>
+                            <textarea
+                                placeholder="Leave empty to reuse the content above"
+                                name="variant_text_content"
+                                rows="10"
+                                cols="50"
+                            >{variant_text_content}</textarea>
+                        </label>
+                        <br>
+                        <label>Variant HTML content:<br>
+                            <textarea
+                                placeholder="Leave empty to reuse the content above"
+                                name="variant_html_content"
+                                rows="10"
+                                cols="50"
+                            >{variant_html_content}</textarea>
+                        </label>
+                    </fieldset>
+                    <br>
                     <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
                     <button type="submit">Publish</button>
                 </form>
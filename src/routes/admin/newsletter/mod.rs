@@ -1,6 +1,8 @@
 //! src/routes/admin/newsletter/mod.rs
+mod attachments;
 mod get;
 mod post;
 
+pub use attachments::{delete_attachment, too_large_message, upload_attachment};
 pub use get::publish_newsletter_form;
-pub use post::publish_newsletter;
+pub use post::{ISSUE_ACCEPTED, MAX_FORM_SIZE_BYTES, publish_newsletter};
@@ -1,82 +1,141 @@
 //! src/routes/admin/newsletter/post.rs
+use crate::audit_log::{AuditAction, record_audit_event};
 use crate::authentication::UserId;
-use crate::idempotency::{IdempotencyKey, save_response};
-use crate::idempotency::{NextAction, try_processing};
-use crate::utils::{e400, e500, see_other};
+use crate::configuration::AbTestSettings;
+use crate::domain::{IssueHtmlContent, IssueTextContent, IssueTitle, IssueVariant, NewIssue};
+use crate::idempotency::{IdempotencyTransaction, Unprocessed};
+use crate::session_state::{NewsletterDraft, TypedSession};
+use crate::telemetry::current_trace_context;
+use crate::utils::{e500, see_other};
 use actix_web::web::ReqData;
-use actix_web::{HttpResponse, web};
+use actix_web::{HttpRequest, HttpResponse, web};
 use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
-use sqlx::{Executor, PgPool, Postgres, Transaction};
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres, Transaction};
 use uuid::Uuid;
 
+/// Room for every field of `FormData` at its maximum length, even with
+/// each character taking 4 bytes that are all percent-encoded, so that long
+/// issues reach the validation of their content.
+pub const MAX_FORM_SIZE_BYTES: usize =
+    12 * 2 * (IssueTitle::MAX_LENGTH + IssueTextContent::MAX_LENGTH + IssueHtmlContent::MAX_LENGTH)
+        + 4096;
+
 #[derive(serde::Deserialize)]
 pub struct FormData {
     title: String,
     text_content: String,
     html_content: String,
-    idempotency_key: String,
+    #[serde(default)]
+    variant_title: String,
+    #[serde(default)]
+    variant_text_content: String,
+    #[serde(default)]
+    variant_html_content: String,
 }
 
+/// Also shown when a submission is replayed.
+pub const ISSUE_ACCEPTED: &str =
+    "The newsletter issue has been accepted - emails will go out shortly.";
+
 #[tracing::instrument(
     name = "Publish a newsletter issue",
     skip_all,
     fields(user_id=%*user_id)
 )]
 pub async fn publish_newsletter(
+    request: HttpRequest,
     form: web::Form<FormData>,
-    pool: web::Data<PgPool>,
+    transaction: IdempotencyTransaction,
     user_id: ReqData<UserId>,
+    session: TypedSession,
+    ab_testing: web::Data<AbTestSettings>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
     let FormData {
         title,
         text_content,
         html_content,
-        idempotency_key,
+        variant_title,
+        variant_text_content,
+        variant_html_content,
     } = form.0;
 
-    let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
-
-    let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
-        .await
-        .map_err(e500)?
-    {
-        NextAction::StartProcessing(t) => t,
-        NextAction::ReturnSavedResponse(saved_response) => {
-            success_message().send();
-            return Ok(saved_response);
+    let draft = NewsletterDraft {
+        title,
+        text_content,
+        html_content,
+        variant_title,
+        variant_text_content,
+        variant_html_content,
+    };
+    let new_issue = match NewIssue::try_from(&draft) {
+        Ok(new_issue) => new_issue,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            session.insert_newsletter_draft(&draft).map_err(e500)?;
+            let mut response = see_other("/admin/newsletters");
+            response.extensions_mut().insert(Unprocessed);
+            return Ok(response);
         }
     };
 
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
+    // Committed by `enforce_idempotency`, along with the saved response.
+    let mut transaction = transaction.lock().await;
+
+    let ab_test_ends_at = new_issue
+        .variant
+        .as_ref()
+        .map(|_| Utc::now() + chrono::Duration::minutes(ab_testing.wait_window_minutes.into()));
+    let issue_id = insert_newsletter_issue(&mut transaction, &new_issue, ab_test_ends_at)
         .await
         .context("Failed to store newsletter issue details")
         .map_err(e500)?;
 
-    enqueue_delivery_tasks(&mut transaction, issue_id)
+    attach_pending_attachments(&mut transaction, issue_id, *user_id)
         .await
-        .context("Failed to enqueue delivery tasks")
+        .context("Failed to attach files to the newsletter issue")
         .map_err(e500)?;
 
-    let response = see_other("/admin/newsletters");
-    let response = save_response(transaction, &idempotency_key, *user_id, response)
-        .await
-        .map_err(e500)?;
-    success_message().send();
-    Ok(response)
-}
+    match &new_issue.variant {
+        Some(variant) => {
+            insert_issue_variants(&mut transaction, issue_id, &new_issue, variant)
+                .await
+                .context("Failed to store the issue variants")
+                .map_err(e500)?;
+            enqueue_ab_test_delivery_tasks(&mut transaction, issue_id, ab_testing.test_percentage)
+                .await
+                .context("Failed to enqueue delivery tasks")
+                .map_err(e500)?;
+        }
+        None => {
+            enqueue_delivery_tasks(&mut transaction, issue_id)
+                .await
+                .context("Failed to enqueue delivery tasks")
+                .map_err(e500)?;
+        }
+    }
 
-fn success_message() -> FlashMessage {
-    FlashMessage::info("The newsletter issue has been accepted - emails will go out shortly.")
+    record_audit_event(
+        &mut **transaction,
+        &request,
+        Some(*user_id),
+        AuditAction::PublishNewsletter,
+        Some(issue_id.to_string()),
+    )
+    .await
+    .map_err(e500)?;
+
+    FlashMessage::info(ISSUE_ACCEPTED).send();
+    Ok(see_other("/admin/newsletters"))
 }
 
 #[tracing::instrument(skip_all)]
 async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
-    title: &str,
-    text_content: &str,
-    html_content: &str,
+    new_issue: &NewIssue,
+    ab_test_ends_at: Option<DateTime<Utc>>,
 ) -> Result<Uuid, sqlx::Error> {
     let news_letter_issue_id = Uuid::new_v4();
     let query = sqlx::query!(
@@ -86,19 +145,69 @@ async fn insert_newsletter_issue(
                title,
                text_content,
                html_content,
-               published_at
+               published_at,
+               ab_test_ends_at
             )
-            VALUES ($1, $2, $3, $4, now())
+            VALUES ($1, $2, $3, $4, now(), $5)
         "#,
         news_letter_issue_id,
-        title,
-        text_content,
-        html_content
+        new_issue.title.as_ref(),
+        new_issue.text_content.as_ref(),
+        new_issue.html_content.as_ref(),
+        ab_test_ends_at
     );
     transaction.execute(query).await?;
     Ok(news_letter_issue_id)
 }
 
+#[tracing::instrument(skip_all)]
+async fn insert_issue_variants(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    new_issue: &NewIssue,
+    variant: &IssueVariant,
+) -> Result<(), sqlx::Error> {
+    // Variant "a" is the issue itself.
+    let query = sqlx::query!(
+        r#"
+            INSERT INTO newsletter_issue_variants (
+                newsletter_issue_id,
+                variant,
+                title,
+                text_content,
+                html_content
+            )
+            VALUES ($1, 'a', $2, NULL, NULL), ($1, 'b', $3, $4, $5)
+        "#,
+        newsletter_issue_id,
+        new_issue.title.as_ref(),
+        variant.title.as_ref(),
+        variant.text_content.as_ref().map(|c| c.as_ref()),
+        variant.html_content.as_ref().map(|c| c.as_ref()),
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn attach_pending_attachments(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+            UPDATE newsletter_issue_attachments
+            SET newsletter_issue_id = $1
+            WHERE user_id = $2 AND newsletter_issue_id IS NULL
+        "#,
+        newsletter_issue_id,
+        user_id
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
 #[tracing::instrument(skip_all)]
 async fn enqueue_delivery_tasks(
     transaction: &mut Transaction<'_, Postgres>,
@@ -108,14 +217,110 @@ async fn enqueue_delivery_tasks(
         r#"
             INSERT INTO issue_delivery_queue (
             newsletter_issue_id,
-            subscriber_email
+            subscriber_email,
+            trace_context
             )
-            SELECT $1, email
+            SELECT $1, email, $2
             FROM subscriptions
             WHERE status = 'confirmed'
         "#,
         newsletter_issue_id,
+        current_trace_context(),
     );
     transaction.execute(query).await?;
     Ok(())
 }
+
+/// Send each variant to half of a random `test_percentage` share of the
+/// list, and hold the rest back until a winner has been picked.
+#[tracing::instrument(skip_all)]
+async fn enqueue_ab_test_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    test_percentage: u8,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+            INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email,
+            variant,
+            trace_context
+            )
+            SELECT $1, email,
+                CASE
+                    WHEN n > ceil(total * $2::int / 100.0) THEN NULL
+                    WHEN n % 2 = 1 THEN 'a'
+                    ELSE 'b'
+                END,
+                $3
+            FROM (
+                SELECT
+                    email,
+                    row_number() OVER (ORDER BY random()) as n,
+                    count(*) OVER () as total
+                FROM subscriptions
+                WHERE status = 'confirmed'
+            ) recipients
+        "#,
+        newsletter_issue_id,
+        i32::from(test_percentage),
+        current_trace_context(),
+    );
+    transaction.execute(query).await?;
+
+    let query = sqlx::query!(
+        r#"
+            UPDATE newsletter_issue_variants v
+            SET recipients = (
+                SELECT COUNT(*)
+                FROM issue_delivery_queue q
+                WHERE
+                    q.newsletter_issue_id = v.newsletter_issue_id AND
+                    q.variant = v.variant
+            )
+            WHERE v.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+// type-safe conversion from raw form data to validated domain objects
+impl TryFrom<&NewsletterDraft> for NewIssue {
+    type Error = String;
+    fn try_from(value: &NewsletterDraft) -> Result<Self, Self::Error> {
+        let title = IssueTitle::parse(value.title.clone())?;
+        let text_content = IssueTextContent::parse(value.text_content.clone())?;
+        let html_content = IssueHtmlContent::parse(value.html_content.clone())?;
+        let variant = if value.variant_title.trim().is_empty() {
+            if !value.variant_text_content.trim().is_empty()
+                || !value.variant_html_content.trim().is_empty()
+            {
+                return Err("A variant body needs a variant subject line.".into());
+            }
+            None
+        } else {
+            Some(IssueVariant {
+                title: IssueTitle::parse(value.variant_title.clone())?,
+                text_content: non_empty(&value.variant_text_content)
+                    .map(IssueTextContent::parse)
+                    .transpose()?,
+                html_content: non_empty(&value.variant_html_content)
+                    .map(IssueHtmlContent::parse)
+                    .transpose()?,
+            })
+        };
+        Ok(Self {
+            title,
+            text_content,
+            html_content,
+            variant,
+        })
+    }
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    (!s.trim().is_empty()).then(|| s.to_string())
+}
@@ -1,4 +1,5 @@
 //! src/routes/admin/password/get.rs
+use crate::authentication::CsrfToken;
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 use actix_web::HttpResponse;
@@ -9,6 +10,7 @@ use std::fmt::Write;
 pub async fn change_password_form(
     session: TypedSession,
     flash_message: IncomingFlashMessages,
+    csrf_token: CsrfToken,
 ) -> Result<HttpResponse, actix_web::Error> {
     if session.get_user_id().map_err(e500)?.is_none() {
         return Ok(see_other("/login"));
@@ -16,8 +18,14 @@ pub async fn change_password_form(
 
     let mut msg_html = String::new();
     for m in flash_message.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
     }
+    let csrf_field = csrf_token.form_field();
 
     Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
         format!(
@@ -30,6 +38,7 @@ pub async fn change_password_form(
             <body>
                 {msg_html}
                 <form action="/admin/password" method="post">
+                    {csrf_field}
                     <label>Current password
                         <input type="password" placeholder="Enter current password" name="current_password">
                     </label>
@@ -1,9 +1,13 @@
 //! src/routes/admin/password/post.rs
+use crate::audit_log::{AuditAction, record_audit_event};
 use crate::authentication::UserId;
 use crate::authentication::{AuthError, Credentials, validate_credentials};
+use crate::configuration::PasswordHashingSettings;
+use crate::domain::{BreachedPasswords, NewPassword};
 use crate::routes::admin::dashboard::get_username;
+use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
-use actix_web::{HttpResponse, web};
+use actix_web::{HttpRequest, HttpResponse, web};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::ExposeSecret;
 use secrecy::SecretString;
@@ -17,9 +21,13 @@ pub struct FormData {
 }
 
 pub async fn change_password(
+    request: HttpRequest,
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     user_id: web::ReqData<UserId>,
+    session: TypedSession,
+    breached_passwords: web::Data<BreachedPasswords>,
+    hashing: web::Data<PasswordHashingSettings>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
 
@@ -33,11 +41,11 @@ pub async fn change_password(
 
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
     let credentials = Credentials {
-        username,
+        username: username.clone(),
         password: form.0.current_password,
     };
 
-    if let Err(e) = validate_credentials(credentials, &pool).await {
+    if let Err(e) = validate_credentials(credentials, &hashing, &pool).await {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 FlashMessage::error("The current password is incorrect.").send();
@@ -46,9 +54,36 @@ pub async fn change_password(
             AuthError::UnexpectedError(_) => Err(e500(e)),
         };
     }
-    crate::authentication::change_password(*user_id, form.0.new_password, &pool)
-        .await
-        .map_err(e500)?;
+    let new_password = match NewPassword::parse(form.0.new_password, &username, &breached_passwords)
+    {
+        Ok(new_password) => new_password,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/password"));
+        }
+    };
+    // Ends the other sessions of the user, but not this one.
+    let session_id = session.get_session_id().map_err(e500)?;
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    crate::authentication::change_password(
+        *user_id,
+        new_password,
+        session_id,
+        &hashing,
+        &mut transaction,
+    )
+    .await
+    .map_err(e500)?;
+    record_audit_event(
+        &mut *transaction,
+        &request,
+        Some(*user_id),
+        AuditAction::ChangePassword,
+        None,
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
 
     FlashMessage::error("Your password has been changed.").send();
     Ok(see_other("/admin/password"))
@@ -0,0 +1,118 @@
+//! src/routes/admin/sessions/get.rs
+use crate::authentication::{CsrfToken, UserId};
+use crate::configuration::SessionSettings;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct SessionSummary {
+    session_id: Uuid,
+    created_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    ip_address: String,
+    user_agent: Option<String>,
+}
+
+pub async fn list_sessions(
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    user_id: ReqData<UserId>,
+    timeouts: web::Data<SessionSettings>,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+
+    let current_session_id = session.get_session_id().map_err(e500)?;
+    let csrf_field = csrf_token.form_field();
+    let sessions = get_active_sessions(&pool, **user_id, &timeouts)
+        .await
+        .map_err(e500)?;
+
+    let mut rows_html = String::new();
+    for s in sessions {
+        let actions_html = if Some(s.session_id) == current_session_id {
+            "this session".to_string()
+        } else {
+            format!(
+                r#"<form action="/admin/sessions/{}/revoke" method="post">{csrf_field}<button type="submit">revoke</button></form>"#,
+                s.session_id
+            )
+        };
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{actions_html}</td></tr>",
+            s.created_at.format("%Y-%m-%d %H:%M"),
+            s.last_seen_at.format("%Y-%m-%d %H:%M"),
+            escape_html(&s.ip_address),
+            escape_html(s.user_agent.as_deref().unwrap_or("unknown")),
+        )
+        .unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Sessions</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>You are logged in on:</p>
+                <table>
+                    <tr><th>Logged in</th><th>Last seen</th><th>IP address</th><th>Browser</th><th></th></tr>
+                    {rows_html}
+                </table>
+                <form action="/admin/sessions/revoke-others" method="post">
+                    {csrf_field}
+                    <button type="submit">Log out everywhere else</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
+
+/// Sessions that have neither been revoked nor timed out.
+#[tracing::instrument(name = "Get active sessions", skip(pool, timeouts))]
+async fn get_active_sessions(
+    pool: &PgPool,
+    user_id: Uuid,
+    timeouts: &SessionSettings,
+) -> Result<Vec<SessionSummary>, anyhow::Error> {
+    let now = Utc::now();
+    let sessions = sqlx::query_as!(
+        SessionSummary,
+        r#"
+        SELECT session_id, created_at, last_seen_at, ip_address, user_agent
+        FROM user_sessions
+        WHERE
+            user_id = $1 AND
+            revoked_at IS NULL AND
+            last_seen_at >= $2 AND
+            created_at >= $3
+        ORDER BY last_seen_at DESC
+        "#,
+        user_id,
+        now - timeouts.idle_timeout(),
+        now - timeouts.absolute_timeout()
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the sessions")?;
+    Ok(sessions)
+}
@@ -0,0 +1,6 @@
+//! src/routes/admin/sessions/mod.rs
+mod get;
+mod post;
+
+pub use get::list_sessions;
+pub use post::{revoke_other_sessions, revoke_session};
@@ -0,0 +1,61 @@
+//! src/routes/admin/sessions/post.rs
+use crate::audit_log::{AuditAction, record_audit_event};
+use crate::authentication::{UserId, revoke_other_user_sessions, revoke_user_session};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+use actix_web::web::ReqData;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "Revoke a session", skip(request, pool, user_id))]
+pub async fn revoke_session(
+    request: HttpRequest,
+    session_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let session_id = session_id.into_inner();
+    // Users can only revoke their own sessions.
+    if revoke_user_session(**user_id, session_id, pool.get_ref())
+        .await
+        .map_err(e500)?
+    {
+        record_audit_event(
+            pool.get_ref(),
+            &request,
+            Some(**user_id),
+            AuditAction::RevokeSession,
+            Some(session_id.to_string()),
+        )
+        .await
+        .map_err(e500)?;
+        FlashMessage::info("The session has been revoked.").send();
+    }
+    Ok(see_other("/admin/sessions"))
+}
+
+#[tracing::instrument(name = "Revoke other sessions", skip_all)]
+pub async fn revoke_other_sessions(
+    request: HttpRequest,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let session_id = session.get_session_id().map_err(e500)?;
+    revoke_other_user_sessions(**user_id, session_id, pool.get_ref())
+        .await
+        .map_err(e500)?;
+    record_audit_event(
+        pool.get_ref(),
+        &request,
+        Some(**user_id),
+        AuditAction::RevokeOtherSessions,
+        None,
+    )
+    .await
+    .map_err(e500)?;
+    FlashMessage::info("You have been logged out of all your other sessions.").send();
+    Ok(see_other("/admin/sessions"))
+}
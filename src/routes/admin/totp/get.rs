@@ -0,0 +1,86 @@
+//! src/routes/admin/totp/get.rs
+use crate::authentication::{
+    CsrfToken, UserId, count_unused_recovery_codes, generate_totp_secret, get_totp_secret,
+    totp_provisioning,
+};
+use crate::routes::admin::dashboard::get_username;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn totp_enrolment_form(
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+    flash_messages: IncomingFlashMessages,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = **user_id;
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+
+    let body_html = if get_totp_secret(user_id, &pool)
+        .await
+        .map_err(e500)?
+        .is_some()
+    {
+        let recovery_codes = count_unused_recovery_codes(user_id, &pool)
+            .await
+            .map_err(e500)?;
+        format!(
+            "<p>Two-factor authentication is enabled.</p>\n\
+            <p>Unused recovery codes: {recovery_codes}</p>"
+        )
+    } else {
+        // Keep showing the same secret until the enrolment is confirmed.
+        let secret = match session.get_pending_totp_secret().map_err(e500)? {
+            Some(secret) => secret,
+            None => {
+                let secret = generate_totp_secret();
+                session.insert_pending_totp_secret(&secret).map_err(e500)?;
+                secret
+            }
+        };
+        let username = get_username(user_id, &pool).await.map_err(e500)?;
+        let provisioning = totp_provisioning(&secret, &username).map_err(e500)?;
+        format!(
+            r#"<p>Scan this QR code with your authenticator app:</p>
+                <img src="data:image/png;base64,{}" alt="TOTP QR code">
+                <p>Or enter this URI manually: <code>{}</code></p>
+                <form action="/admin/totp" method="post">
+                    {}
+                    <label>Code from the app
+                        <input type="text" inputmode="numeric" autocomplete="one-time-code" placeholder="123456" name="code">
+                    </label>
+                    <button type="submit">Enable two-factor authentication</button>
+                </form>"#,
+            provisioning.qr_code_png,
+            escape_html(&provisioning.uri),
+            csrf_token.form_field()
+        )
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Two-factor authentication</title>
+            </head>
+            <body>
+                {msg_html}
+                {body_html}
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
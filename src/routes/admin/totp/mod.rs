@@ -0,0 +1,6 @@
+//! src/routes/admin/totp/mod.rs
+mod get;
+mod post;
+
+pub use get::totp_enrolment_form;
+pub use post::confirm_totp_enrolment;
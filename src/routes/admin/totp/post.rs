@@ -0,0 +1,85 @@
+//! src/routes/admin/totp/post.rs
+use crate::audit_log::{AuditAction, record_audit_event};
+use crate::authentication::{UserId, check_totp_code, enable_totp, get_totp_secret};
+use crate::configuration::PasswordHashingSettings;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+#[tracing::instrument(name = "Confirm TOTP enrolment", skip_all, fields(user_id=%*user_id))]
+pub async fn confirm_totp_enrolment(
+    request: HttpRequest,
+    form: web::Form<FormData>,
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    user_id: ReqData<UserId>,
+    hashing: web::Data<PasswordHashingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = **user_id;
+    if get_totp_secret(user_id, &pool)
+        .await
+        .map_err(e500)?
+        .is_some()
+    {
+        FlashMessage::error("Two-factor authentication is already enabled.").send();
+        return Ok(see_other("/admin/totp"));
+    }
+    let Some(secret) = session.get_pending_totp_secret().map_err(e500)? else {
+        return Ok(see_other("/admin/totp"));
+    };
+    let Some(step) = check_totp_code(&secret, &form.code).map_err(e500)? else {
+        FlashMessage::error("The code is invalid, please try again.").send();
+        return Ok(see_other("/admin/totp"));
+    };
+
+    let recovery_codes = enable_totp(user_id, secret, step, &hashing, &pool)
+        .await
+        .map_err(e500)?;
+    session.remove_pending_totp_secret();
+    record_audit_event(
+        pool.get_ref(),
+        &request,
+        Some(user_id),
+        AuditAction::EnableTotp,
+        None,
+    )
+    .await
+    .map_err(e500)?;
+
+    // Recovery codes are only stored hashed: this is the one chance to see
+    // them, so they are rendered directly instead of redirecting.
+    let mut codes_html = String::new();
+    for code in recovery_codes {
+        writeln!(codes_html, "<li><code>{code}</code></li>").unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Recovery codes</title>
+            </head>
+            <body>
+                <p>Two-factor authentication is now enabled.</p>
+                <p>Store these recovery codes somewhere safe. Each of them can be used once
+                instead of a code from your app, and they will not be shown again.</p>
+                <ul>
+                    {codes_html}
+                </ul>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
@@ -0,0 +1,123 @@
+//! src/routes/admin/users/get.rs
+use crate::authentication::{CsrfToken, Role, UserId};
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::web::ReqData;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+struct UserSummary {
+    user_id: Uuid,
+    username: String,
+    email: Option<String>,
+    role: String,
+    active: bool,
+    invited: bool,
+}
+
+pub async fn list_users(
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+    current_user_id: ReqData<UserId>,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+
+    let users = get_users(&pool).await.map_err(e500)?;
+    let csrf_field = csrf_token.form_field();
+
+    let mut rows_html = String::new();
+    for user in users {
+        let status = match (user.active, user.invited) {
+            (false, _) => "deactivated",
+            (true, true) => "invited",
+            (true, false) => "active",
+        };
+        let actions_html = if user.active && user.user_id != **current_user_id {
+            format!(
+                r#"<form action="/admin/users/{}/deactivate" method="post">{csrf_field}<button type="submit">deactivate</button></form>"#,
+                user.user_id
+            )
+        } else {
+            String::new()
+        };
+        writeln!(
+            rows_html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{status}</td><td>{actions_html}</td></tr>",
+            escape_html(&user.username),
+            escape_html(user.email.as_deref().unwrap_or_default()),
+            user.role,
+        )
+        .unwrap();
+    }
+
+    let mut roles_html = String::new();
+    for role in Role::ALL {
+        writeln!(roles_html, r#"<option value="{role}">{role}</option>"#).unwrap();
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Users</title>
+            </head>
+            <body>
+                {msg_html}
+                <table>
+                    <tr><th>Username</th><th>Email</th><th>Role</th><th>Status</th><th></th></tr>
+                    {rows_html}
+                </table>
+                <p>Invite a user:</p>
+                <form action="/admin/users" method="post">
+                    {csrf_field}
+                    <label>Username
+                        <input type="text" placeholder="Enter the username" name="username">
+                    </label>
+                    <label>Email
+                        <input type="email" placeholder="Enter the email" name="email">
+                    </label>
+                    <label>Role
+                        <select name="role">
+                            {roles_html}
+                        </select>
+                    </label>
+                    <button type="submit">Invite</button>
+                </form>
+                <p><a href="/admin/dashboard">&lt;- Back</a></p>
+            </body>
+            </html>"#
+        )))
+}
+
+#[tracing::instrument(name = "Get users", skip(pool))]
+async fn get_users(pool: &PgPool) -> Result<Vec<UserSummary>, anyhow::Error> {
+    let users = sqlx::query_as!(
+        UserSummary,
+        r#"
+        SELECT
+            user_id,
+            username,
+            email,
+            role,
+            active,
+            password_hash IS NULL as "invited!"
+        FROM users
+        ORDER BY username
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve users")?;
+    Ok(users)
+}
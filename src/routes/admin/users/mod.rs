@@ -0,0 +1,6 @@
+//! src/routes/admin/users/mod.rs
+mod get;
+mod post;
+
+pub use get::list_users;
+pub use post::{deactivate_user, invite_user};
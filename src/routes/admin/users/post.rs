@@ -0,0 +1,244 @@
+//! src/routes/admin/users/post.rs
+use crate::audit_log::{AuditAction, record_audit_event};
+use crate::authentication::{Role, UserId};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::routes::invitations::hash_invitation_token;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, escape_html, see_other};
+use actix_web::web::ReqData;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{Rng, thread_rng};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+const INVITATION_VALIDITY: Duration = Duration::days(7);
+
+#[derive(serde::Deserialize)]
+pub struct InviteFormData {
+    username: String,
+    email: String,
+    role: String,
+}
+
+struct Invitee {
+    username: String,
+    email: SubscriberEmail,
+    role: Role,
+}
+
+impl TryFrom<InviteFormData> for Invitee {
+    type Error = String;
+
+    fn try_from(value: InviteFormData) -> Result<Self, Self::Error> {
+        let username = value.username.trim().to_string();
+        if username.is_empty() {
+            return Err("The username cannot be empty.".into());
+        }
+        let email = SubscriberEmail::parse(value.email.trim().to_string())?;
+        let role = Role::parse(&value.role)?;
+        Ok(Self {
+            username,
+            email,
+            role,
+        })
+    }
+}
+
+#[tracing::instrument(
+    name = "Invite a user",
+    skip(request, form, pool, email_client, base_url, current_user_id),
+    fields(username = %form.username, role = %form.role)
+)]
+pub async fn invite_user(
+    request: HttpRequest,
+    form: web::Form<InviteFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    current_user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let invitee: Invitee = match form.0.try_into() {
+        Ok(invitee) => invitee,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/users"));
+        }
+    };
+
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let user_id = match insert_invited_user(&mut transaction, &invitee).await {
+        Ok(user_id) => user_id,
+        Err(e)
+            if e.as_database_error()
+                .is_some_and(|e| e.is_unique_violation()) =>
+        {
+            FlashMessage::error("A user with this username or email already exists.").send();
+            return Ok(see_other("/admin/users"));
+        }
+        Err(e) => {
+            return Err(e500(
+                anyhow::Error::new(e).context("Failed to store the invited user"),
+            ));
+        }
+    };
+    let invitation_token = generate_invitation_token();
+    store_invitation(&mut transaction, user_id, &invitation_token)
+        .await
+        .context("Failed to store the invitation")
+        .map_err(e500)?;
+    record_audit_event(
+        &mut *transaction,
+        &request,
+        Some(**current_user_id),
+        AuditAction::InviteUser,
+        Some(user_id.to_string()),
+    )
+    .await
+    .map_err(e500)?;
+    // Only commit once the invitation is on its way.
+    send_invitation_email(&email_client, &invitee, &base_url.0, &invitation_token)
+        .await
+        .context("Failed to send the invitation email")
+        .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    FlashMessage::info(format!("{} has been invited.", invitee.username)).send();
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(name = "Deactivate a user", skip(request, pool, current_user_id))]
+pub async fn deactivate_user(
+    request: HttpRequest,
+    user_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    current_user_id: ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    if user_id == **current_user_id {
+        FlashMessage::error("You cannot deactivate your own account.").send();
+        return Ok(see_other("/admin/users"));
+    }
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let query = sqlx::query!(
+        r#"
+        UPDATE users
+        SET active = false
+        WHERE user_id = $1
+        "#,
+        user_id
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to deactivate the user")
+        .map_err(e500)?;
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM user_invitations
+        WHERE user_id = $1
+        "#,
+        user_id
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete pending invitations")
+        .map_err(e500)?;
+    record_audit_event(
+        &mut *transaction,
+        &request,
+        Some(**current_user_id),
+        AuditAction::DeactivateUser,
+        Some(user_id.to_string()),
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    FlashMessage::info("The user has been deactivated.").send();
+    Ok(see_other("/admin/users"))
+}
+
+#[tracing::instrument(skip_all)]
+async fn insert_invited_user(
+    transaction: &mut Transaction<'_, Postgres>,
+    invitee: &Invitee,
+) -> Result<Uuid, sqlx::Error> {
+    let user_id = Uuid::new_v4();
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, email, role, password_hash)
+        VALUES ($1, $2, $3, $4, NULL)
+        "#,
+        user_id,
+        invitee.username,
+        invitee.email.as_ref(),
+        invitee.role.as_str()
+    );
+    transaction.execute(query).await?;
+    Ok(user_id)
+}
+
+#[tracing::instrument(skip_all)]
+async fn store_invitation(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    invitation_token: &str,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO user_invitations (invitation_token_hash, user_id, expires_at)
+        VALUES ($1, $2, $3)
+        "#,
+        hash_invitation_token(invitation_token),
+        user_id,
+        Utc::now() + INVITATION_VALIDITY
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_invitation_email(
+    email_client: &EmailClient,
+    invitee: &Invitee,
+    base_url: &str,
+    invitation_token: &str,
+) -> Result<(), reqwest::Error> {
+    let invitation_link = format!(
+        "{}/invitations/accept?invitation_token={}",
+        base_url, invitation_token
+    );
+    let plain_body = format!(
+        "You have been invited to manage our newsletter as {}.\n\
+        Visit {} to choose your password",
+        invitee.username, invitation_link
+    );
+    let html_body = format!(
+        "You have been invited to manage our newsletter as {}.<br/>\
+        Click <a href=\"{}\">here</a> to choose your password",
+        escape_html(&invitee.username),
+        invitation_link
+    );
+    email_client
+        .send_email(
+            &invitee.email,
+            "You have been invited",
+            &html_body,
+            &plain_body,
+        )
+        .await
+}
+
+fn generate_invitation_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(25)
+        .collect()
+}
@@ -1,6 +1,135 @@
 //! src/routes/health_check.rs
-use actix_web::HttpResponse;
+use crate::configuration::HealthSettings;
+use crate::email_client::EmailClient;
+use crate::migrations::MIGRATOR;
+use actix_web::{HttpResponse, web};
+use sqlx::PgPool;
+use sqlx::migrate::Migrate;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+use std::time::{Duration, Instant};
 
+/// Liveness: the process is up and serving requests.
 pub async fn health_check() -> HttpResponse {
     HttpResponse::Ok().finish()
 }
+
+/// Client for the Redis instance backing the session store.
+pub struct SessionStoreClient(pub redis::Client);
+
+#[derive(serde::Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Up,
+    Down,
+}
+
+#[derive(serde::Serialize)]
+struct DependencyReport {
+    status: Status,
+    latency_ms: u64,
+}
+
+#[derive(serde::Serialize)]
+struct ReadinessReport {
+    ready: bool,
+    checks: BTreeMap<&'static str, DependencyReport>,
+}
+
+/// Readiness: the dependencies needed to serve requests are reachable and
+/// the database schema is up to date.
+///
+/// Responds with `503 Service Unavailable` if any of them is not. Failures
+/// are logged rather than reported, as the endpoint is public.
+#[tracing::instrument(name = "Check readiness", skip_all)]
+pub async fn health_ready(
+    pool: web::Data<PgPool>,
+    session_store: web::Data<SessionStoreClient>,
+    email_client: web::Data<EmailClient>,
+    settings: web::Data<HealthSettings>,
+) -> HttpResponse {
+    let timeout = settings.timeout();
+    let email_provider = async {
+        if settings.check_email_provider {
+            Some(check("email_provider", timeout, email_client.ping()).await)
+        } else {
+            None
+        }
+    };
+    let (postgres, redis, migrations, email_provider) = tokio::join!(
+        check("postgres", timeout, check_postgres(&pool)),
+        check("redis", timeout, check_redis(&session_store.0)),
+        check("migrations", timeout, check_migrations(&pool)),
+        email_provider,
+    );
+
+    let mut checks = BTreeMap::from([
+        ("postgres", postgres),
+        ("redis", redis),
+        ("migrations", migrations),
+    ]);
+    if let Some(email_provider) = email_provider {
+        checks.insert("email_provider", email_provider);
+    }
+    let ready = checks.values().all(|c| c.status == Status::Up);
+    let report = ReadinessReport { ready, checks };
+    if ready {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+async fn check<E: Display>(
+    dependency: &str,
+    timeout: Duration,
+    outcome: impl Future<Output = Result<(), E>>,
+) -> DependencyReport {
+    let started_at = Instant::now();
+    let status = match tokio::time::timeout(timeout, outcome).await {
+        Ok(Ok(())) => Status::Up,
+        Ok(Err(e)) => {
+            tracing::warn!(error.message = %e, "{} is not ready", dependency);
+            Status::Down
+        }
+        Err(_) => {
+            tracing::warn!("{} did not answer in time", dependency);
+            Status::Down
+        }
+    };
+    DependencyReport {
+        status,
+        latency_ms: started_at.elapsed().as_millis() as u64,
+    }
+}
+
+async fn check_postgres(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!("SELECT 1 as one").fetch_one(pool).await?;
+    Ok(())
+}
+
+async fn check_redis(client: &redis::Client) -> Result<(), redis::RedisError> {
+    let mut connection = client.get_multiplexed_async_connection().await?;
+    redis::cmd("PING")
+        .query_async::<String>(&mut connection)
+        .await?;
+    Ok(())
+}
+
+async fn check_migrations(pool: &PgPool) -> Result<(), anyhow::Error> {
+    let mut connection = pool.acquire().await?;
+    let applied: HashSet<i64> = connection
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect();
+    let n_pending = MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration() && !applied.contains(&m.version))
+        .count();
+    if n_pending > 0 {
+        anyhow::bail!("{} migrations have not been applied", n_pending);
+    }
+    Ok(())
+}
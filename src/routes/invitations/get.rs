@@ -0,0 +1,61 @@
+//! src/routes/invitations/get.rs
+use super::{get_invitation, invalid_invitation};
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    invitation_token: String,
+}
+
+pub async fn accept_invitation_form(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(invitation) = get_invitation(&pool, &parameters.invitation_token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(invalid_invitation());
+    };
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+    let username = escape_html(&invitation.username);
+    let invitation_token = escape_html(&parameters.invitation_token);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Accept invitation</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>Welcome {username}! Choose a password to activate your account.</p>
+                <form action="/invitations/accept" method="post">
+                    <input hidden type="text" name="invitation_token" value="{invitation_token}">
+                    <label>Password
+                        <input type="password" placeholder="Enter a password" name="new_password">
+                    </label>
+                <br>
+                    <label>Confirm password
+                        <input type="password" placeholder="Type the password again" name="new_password_check">
+                    </label>
+                <br>
+                    <button type="submit">Activate account</button>
+                </form>
+            </body>
+            </html>"#
+        )))
+}
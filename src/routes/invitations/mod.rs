@@ -0,0 +1,54 @@
+//! src/routes/invitations/mod.rs
+mod get;
+mod post;
+
+pub use get::accept_invitation_form;
+pub use post::accept_invitation;
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub struct Invitation {
+    pub user_id: Uuid,
+    pub username: String,
+}
+
+/// Returns `None` if the token is unknown, has expired or belongs to a
+/// deactivated user.
+#[tracing::instrument(name = "Get invitation", skip(pool, invitation_token))]
+async fn get_invitation(
+    pool: &PgPool,
+    invitation_token: &str,
+) -> Result<Option<Invitation>, anyhow::Error> {
+    let invitation = sqlx::query_as!(
+        Invitation,
+        r#"
+        SELECT u.user_id, u.username
+        FROM user_invitations i
+        JOIN users u ON u.user_id = i.user_id
+        WHERE
+            i.invitation_token_hash = $1 AND
+            i.expires_at > now() AND
+            u.active
+        "#,
+        hash_invitation_token(invitation_token)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the invitation")?;
+    Ok(invitation)
+}
+
+/// Tokens are long and random, so a fast hash is enough.
+pub(crate) fn hash_invitation_token(invitation_token: &str) -> String {
+    Sha256::digest(invitation_token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn invalid_invitation() -> actix_web::HttpResponse {
+    actix_web::HttpResponse::Unauthorized().body("This invitation is invalid or has expired.")
+}
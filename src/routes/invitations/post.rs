@@ -0,0 +1,93 @@
+//! src/routes/invitations/post.rs
+use super::{get_invitation, invalid_invitation};
+use crate::audit_log::{AuditAction, record_audit_event};
+use crate::authentication::change_password;
+use crate::configuration::PasswordHashingSettings;
+use crate::domain::{BreachedPasswords, NewPassword};
+use crate::utils::{e500, see_other};
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    invitation_token: String,
+    new_password: SecretString,
+    new_password_check: SecretString,
+}
+
+#[tracing::instrument(name = "Accept an invitation", skip_all)]
+pub async fn accept_invitation(
+    request: HttpRequest,
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    breached_passwords: web::Data<BreachedPasswords>,
+    hashing: web::Data<PasswordHashingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        invitation_token,
+        new_password,
+        new_password_check,
+    } = form.0;
+    let Some(invitation) = get_invitation(&pool, &invitation_token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(invalid_invitation());
+    };
+
+    if new_password.expose_secret() != new_password_check.expose_secret() {
+        FlashMessage::error("You entered two different passwords - the field values must match.")
+            .send();
+        return Ok(see_other(&format!(
+            "/invitations/accept?invitation_token={invitation_token}"
+        )));
+    }
+
+    let new_password =
+        match NewPassword::parse(new_password, &invitation.username, &breached_passwords) {
+            Ok(new_password) => new_password,
+            Err(e) => {
+                FlashMessage::error(e).send();
+                return Ok(see_other(&format!(
+                    "/invitations/accept?invitation_token={invitation_token}"
+                )));
+            }
+        };
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    change_password(
+        invitation.user_id,
+        new_password,
+        None,
+        &hashing,
+        &mut transaction,
+    )
+    .await
+    .map_err(e500)?;
+    sqlx::query!(
+        r#"
+        DELETE FROM user_invitations
+        WHERE user_id = $1
+        "#,
+        invitation.user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete the accepted invitation")
+    .map_err(e500)?;
+    record_audit_event(
+        &mut *transaction,
+        &request,
+        Some(invitation.user_id),
+        AuditAction::AcceptInvitation,
+        None,
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    FlashMessage::info("Your account is ready, you can now log in.").send();
+    Ok(see_other("/login"))
+}
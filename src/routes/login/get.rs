@@ -1,13 +1,27 @@
 //! src/routes/login/get.rs
-use actix_web::{HttpResponse, http::header::ContentType};
+use crate::authentication::OidcClient;
+use actix_web::{HttpResponse, http::header::ContentType, web};
 use actix_web_flash_messages::IncomingFlashMessages;
 use std::fmt::Write;
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+pub async fn login_form(
+    flash_messages: IncomingFlashMessages,
+    oidc: web::Data<OidcClient>,
+) -> HttpResponse {
     let mut error_html = String::new();
     for m in flash_messages.iter() {
-        writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
+        writeln!(
+            error_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
     }
+    let sso_html = if oidc.is_enabled() {
+        r#"<p><a href="/login/oidc">Log in with single sign-on</a></p>"#
+    } else {
+        ""
+    };
     HttpResponse::Ok()
         .content_type(ContentType::html())
         .body(format!(
@@ -28,6 +42,8 @@ pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
                     </label>
                     <button type="submit">Login</button>
                     </form>
+                    {sso_html}
+                    <p><a href="/password-reset">Forgot your password?</a></p>
                 </body>
             </html>"#,
         ))
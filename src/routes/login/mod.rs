@@ -1,6 +1,36 @@
 //! src/routes/login/mod.rs
 mod get;
+mod oidc;
 mod post;
+mod totp;
 
 pub use get::login_form;
+pub use oidc::{login_oidc, login_oidc_callback};
 pub use post::login;
+pub use totp::{login_totp, login_totp_form};
+
+use crate::audit_log::{AuditAction, record_audit_event};
+use crate::authentication::start_user_session;
+use crate::session_state::TypedSession;
+use crate::utils::client_ip;
+use actix_web::HttpRequest;
+use actix_web::http::header::USER_AGENT;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Record a new session for the user and attach it to the cookie session.
+async fn log_in(
+    session: &TypedSession,
+    user_id: Uuid,
+    request: &HttpRequest,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let user_agent = request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|h| h.to_str().ok());
+    let session_id = start_user_session(user_id, &client_ip(request), user_agent, pool).await?;
+    session.insert_user_id(user_id, session_id)?;
+    record_audit_event(pool, request, Some(user_id), AuditAction::LogIn, None).await?;
+    Ok(())
+}
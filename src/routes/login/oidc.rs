@@ -0,0 +1,118 @@
+//! src/routes/login/oidc.rs
+use super::log_in;
+use super::post::{LoginError, login_redirect};
+use crate::authentication::{AuthError, OidcClient};
+use crate::session_state::TypedSession;
+use actix_web::error::InternalError;
+use actix_web::http::header::LOCATION;
+use actix_web::{HttpRequest, HttpResponse, web};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct CallbackParameters {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+/// Send the user to the identity provider.
+#[tracing::instrument(skip(session, oidc))]
+pub async fn login_oidc(
+    session: TypedSession,
+    oidc: web::Data<OidcClient>,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    if !oidc.is_enabled() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let (url, pending) = oidc
+        .authorization_request()
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+    session
+        .insert_pending_oidc_login(&pending)
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+    Ok(HttpResponse::SeeOther()
+        .insert_header((LOCATION, url.as_str()))
+        .finish())
+}
+
+/// Where the identity provider sends the user back to, with an
+/// authorization code for the ID token.
+///
+/// Accounts are matched on the email verified by the provider. The provider
+/// is in charge of second factors: TOTP is not asked for.
+#[tracing::instrument(skip_all, fields(user_id=tracing::field::Empty))]
+pub async fn login_oidc_callback(
+    request: HttpRequest,
+    parameters: web::Query<CallbackParameters>,
+    session: TypedSession,
+    oidc: web::Data<OidcClient>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    if !oidc.is_enabled() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let CallbackParameters { code, state, error } = parameters.0;
+    let pending = session
+        .take_pending_oidc_login()
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+    if let Some(error) = error {
+        return Err(login_redirect(LoginError::AuthError(anyhow::anyhow!(
+            "The provider returned an error: {error}"
+        ))));
+    }
+    // The state ties the callback to the login started in this browser.
+    let pending = pending.filter(|p| Some(&p.state) == state.as_ref());
+    let (Some(pending), Some(code)) = (pending, code) else {
+        return Err(login_redirect(LoginError::AuthError(anyhow::anyhow!(
+            "The callback does not match a login started in this session"
+        ))));
+    };
+
+    let email = oidc.verified_email(&code, &pending).await.map_err(|e| {
+        login_redirect(match e {
+            AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+            AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
+        })
+    })?;
+    let Some(user_id) = get_active_user_id_by_email(&pool, &email)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    else {
+        return Err(login_redirect(LoginError::AuthError(anyhow::anyhow!(
+            "No active user has the email {email}"
+        ))));
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    session.renew();
+    log_in(&session, user_id, &request, &pool)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+    Ok(HttpResponse::SeeOther()
+        .insert_header((LOCATION, "/admin/dashboard"))
+        .finish())
+}
+
+/// Invited users can log in before they accept their invitation: they do
+/// not need a password.
+#[tracing::instrument(name = "Get user by email", skip(pool))]
+async fn get_active_user_id_by_email(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id
+        FROM users
+        WHERE email = $1 AND active
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the user")?;
+    Ok(row.map(|r| r.user_id))
+}
@@ -1,12 +1,15 @@
 //! src/routes/login/post.rs
-use crate::authentication::AuthError;
+use super::log_in;
+use crate::authentication::{AuthError, LoginThrottle, get_totp_secret};
 use crate::authentication::{Credentials, validate_credentials};
+use crate::configuration::PasswordHashingSettings;
 use crate::routes::error_chain_fmt;
 use crate::session_state::TypedSession;
-use actix_web::HttpResponse;
+use crate::utils::client_ip;
 use actix_web::error::InternalError;
 use actix_web::http::header::LOCATION;
 use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::SecretString;
 use sqlx::PgPool;
@@ -21,6 +24,8 @@ pub struct FormData {
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed login attempts, please try again later.")]
+    TooManyAttempts,
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -31,32 +36,66 @@ impl std::fmt::Debug for LoginError {
     }
 }
 
-#[tracing::instrument(skip(form, pool, session),
+#[tracing::instrument(skip(form, pool, session, request, throttle, hashing),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty))]
 pub async fn login(
+    request: HttpRequest,
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     session: TypedSession,
+    throttle: web::Data<LoginThrottle>,
+    hashing: web::Data<PasswordHashingSettings>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
+    let ip = client_ip(&request);
+    let username = form.0.username;
+    // The same answer is given whether or not the username exists.
+    if throttle
+        .is_locked_out(&username, &ip)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        return Err(login_redirect(LoginError::TooManyAttempts));
+    }
     let credentials = Credentials {
-        username: form.0.username,
+        username: username.clone(),
         password: form.0.password,
     };
 
-    match validate_credentials(credentials, &pool).await {
+    match validate_credentials(credentials, &hashing, &pool).await {
         Ok(user_id) => {
             tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            let totp_secret = get_totp_secret(user_id, &pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             session.renew();
-            session
-                .insert_user_id(user_id)
-                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            if totp_secret.is_some() {
+                session
+                    .insert_pending_user_id(user_id)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                return Ok(HttpResponse::SeeOther()
+                    .insert_header((LOCATION, "/login/totp"))
+                    .finish());
+            }
+            throttle
+                .reset(&username)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            log_in(&session, user_id, &request, &pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             Ok(HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/admin/dashboard"))
                 .finish())
         }
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    throttle
+                        .record_failure(&username, &ip)
+                        .await
+                        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                    LoginError::AuthError(e.into())
+                }
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
             Err(login_redirect(e))
@@ -0,0 +1,109 @@
+//! src/routes/login/totp.rs
+use super::log_in;
+use crate::authentication::{LoginThrottle, verify_second_factor};
+use crate::routes::admin::get_username;
+use crate::session_state::TypedSession;
+use crate::utils::client_ip;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+/// After this many invalid codes the password has to be entered again.
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    code: String,
+}
+
+pub async fn login_totp_form(
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_pending_user_id().map_err(e500)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+    let mut error_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            error_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+                <head>
+                    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                    <title>Two-factor authentication</title>
+                </head>
+                <body>
+                    {error_html}
+                    <form action="/login/totp" method="post">
+                    <label>Authentication code
+                        <input type="text" inputmode="numeric" autocomplete="one-time-code" placeholder="Code from your app or a recovery code" name="code">
+                    </label>
+                    <button type="submit">Verify</button>
+                    </form>
+                </body>
+            </html>"#,
+        )))
+}
+
+#[tracing::instrument(
+    skip(form, pool, session, request, throttle),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn login_totp(
+    request: HttpRequest,
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    throttle: web::Data<LoginThrottle>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(user_id) = session.get_pending_user_id().map_err(e500)? else {
+        return Ok(see_other("/login"));
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+    // Invalid codes count as failed logins as well.
+    let ip = client_ip(&request);
+    let username = get_username(user_id, &pool).await.map_err(e500)?;
+    if throttle.is_locked_out(&username, &ip).await.map_err(e500)? {
+        session.log_out();
+        FlashMessage::error("Too many failed login attempts, please try again later.").send();
+        return Ok(see_other("/login"));
+    }
+
+    if verify_second_factor(user_id, &form.code, &pool)
+        .await
+        .map_err(e500)?
+    {
+        throttle.reset(&username).await.map_err(e500)?;
+        session.renew();
+        session.remove_pending_user_id();
+        log_in(&session, user_id, &request, &pool)
+            .await
+            .map_err(e500)?;
+        return Ok(see_other("/admin/dashboard"));
+    }
+
+    throttle
+        .record_failure(&username, &ip)
+        .await
+        .map_err(e500)?;
+    let attempts = session.record_failed_totp_attempt().map_err(e500)?;
+    if attempts >= MAX_FAILED_ATTEMPTS {
+        session.log_out();
+        FlashMessage::error("Too many invalid codes, please log in again.").send();
+        return Ok(see_other("/login"));
+    }
+    FlashMessage::error("Invalid authentication code.").send();
+    Ok(see_other("/login/totp"))
+}
@@ -0,0 +1,42 @@
+//! src/routes/metrics.rs
+use crate::metrics::gather;
+use crate::utils::e500;
+use actix_web::http::header::{self, HeaderMap, HeaderValue};
+use actix_web::{HttpRequest, HttpResponse, web};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+
+#[derive(Clone)]
+pub struct MetricsToken(pub SecretString);
+
+/// Metrics in the Prometheus text format, for scrapers holding the metrics
+/// token.
+pub async fn metrics(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    token: web::Data<MetricsToken>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !is_authorized(request.headers(), &token.0) {
+        return Ok(HttpResponse::Unauthorized()
+            .insert_header((
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(r#"Bearer realm="metrics""#),
+            ))
+            .finish());
+    }
+    let body = gather(&pool).await.map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(body))
+}
+
+fn is_authorized(headers: &HeaderMap, token: &SecretString) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .is_some_and(|submitted| {
+            bool::from(submitted.as_bytes().ct_eq(token.expose_secret().as_bytes()))
+        })
+}
@@ -3,13 +3,23 @@
 mod admin;
 mod health_check;
 mod home;
+mod invitations;
 mod login;
+mod metrics;
+mod password_reset;
 mod subscriptions;
 mod subscriptions_confirm;
+mod tracking;
+mod webhooks;
 
 pub use admin::*;
 pub use health_check::*;
 pub use home::*;
+pub use invitations::*;
 pub use login::*;
+pub use metrics::*;
+pub use password_reset::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use tracking::*;
+pub use webhooks::*;
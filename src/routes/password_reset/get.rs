@@ -0,0 +1,90 @@
+//! src/routes/password_reset/get.rs
+use super::{get_password_reset_user_id, invalid_password_reset};
+use crate::utils::{e500, escape_html};
+use actix_web::http::header::ContentType;
+use actix_web::{HttpResponse, web};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+pub async fn request_password_reset_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Forgot your password?</title>
+            </head>
+            <body>
+                {msg_html}
+                <p>Enter the email address of your account, we will send you a link to choose a new password.</p>
+                <form action="/password-reset" method="post">
+                    <label>Email
+                        <input type="email" placeholder="Enter your email" name="email">
+                    </label>
+                <br>
+                    <button type="submit">Send reset link</button>
+                </form>
+                <p><a href="/login">&lt;- Back to login</a></p>
+            </body>
+            </html>"#
+        ))
+}
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    reset_token: String,
+}
+
+pub async fn password_reset_form(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    if get_password_reset_user_id(&pool, &parameters.reset_token)
+        .await
+        .map_err(e500)?
+        .is_none()
+    {
+        return Ok(invalid_password_reset());
+    }
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", escape_html(m.content())).unwrap();
+    }
+    let reset_token = escape_html(&parameters.reset_token);
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta http-equiv="content-type" content="text/html; charset=utf-8">
+                <title>Reset password</title>
+            </head>
+            <body>
+                {msg_html}
+                <form action="/password-reset/confirm" method="post">
+                    <input hidden type="text" name="reset_token" value="{reset_token}">
+                    <label>New password
+                        <input type="password" placeholder="Enter a new password" name="new_password">
+                    </label>
+                <br>
+                    <label>Confirm new password
+                        <input type="password" placeholder="Type the new password again" name="new_password_check">
+                    </label>
+                <br>
+                    <button type="submit">Reset password</button>
+                </form>
+            </body>
+            </html>"#
+        )))
+}
@@ -0,0 +1,56 @@
+//! src/routes/password_reset/mod.rs
+mod get;
+mod post;
+
+pub use get::{password_reset_form, request_password_reset_form};
+pub use post::{request_password_reset, reset_password};
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Returns the user the token was issued for, or `None` if the token is
+/// unknown, has expired or belongs to a deactivated user.
+#[tracing::instrument(name = "Get password reset", skip(pool, reset_token))]
+async fn get_password_reset_user_id(
+    pool: &PgPool,
+    reset_token: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT u.user_id
+        FROM password_resets r
+        JOIN users u ON u.user_id = r.user_id
+        WHERE
+            r.reset_token_hash = $1 AND
+            r.expires_at > now() AND
+            u.active
+        "#,
+        hash_reset_token(reset_token)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the password reset")?;
+    Ok(row.map(|r| r.user_id))
+}
+
+/// Tokens are long and random, so a fast hash is enough.
+fn hash_reset_token(reset_token: &str) -> String {
+    Sha256::digest(reset_token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// The form to choose a new password with `reset_token`.
+fn password_reset_form_url(reset_token: &str) -> String {
+    let query = serde_urlencoded::to_string([("reset_token", reset_token)])
+        .expect("Failed to encode the reset token");
+    format!("/password-reset/confirm?{query}")
+}
+
+fn invalid_password_reset() -> actix_web::HttpResponse {
+    actix_web::HttpResponse::Unauthorized()
+        .body("This password reset link is invalid or has expired.")
+}
@@ -0,0 +1,252 @@
+//! src/routes/password_reset/post.rs
+use super::{
+    get_password_reset_user_id, hash_reset_token, invalid_password_reset, password_reset_form_url,
+};
+use crate::audit_log::{AuditAction, record_audit_event};
+use crate::authentication::change_password;
+use crate::configuration::PasswordHashingSettings;
+use crate::domain::{BreachedPasswords, NewPassword, SubscriberEmail};
+use crate::email_client::EmailClient;
+use crate::routes::admin::get_username;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, see_other};
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{Rng, thread_rng};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{Executor, PgPool};
+use uuid::Uuid;
+
+const PASSWORD_RESET_VALIDITY: Duration = Duration::hours(1);
+/// Reset links that can be outstanding for an account at any time, so that
+/// the form cannot be used to flood someone's inbox.
+const MAX_PENDING_PASSWORD_RESETS: i64 = 3;
+
+#[derive(serde::Deserialize)]
+pub struct RequestFormData {
+    email: String,
+}
+
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(form, pool, email_client, base_url)
+)]
+pub async fn request_password_reset(
+    form: web::Form<RequestFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // The same answer is given whether or not an account uses the email, so
+    // that the form cannot be used to find out who the admins are.
+    let done = || {
+        FlashMessage::info(
+            "If an account uses this email, we have sent it a link to reset its password.",
+        )
+        .send();
+        Ok(see_other("/login"))
+    };
+    let Ok(email) = SubscriberEmail::parse(form.0.email.trim().to_string()) else {
+        return done();
+    };
+    let Some(user_id) = get_active_user_id_by_email(&pool, &email)
+        .await
+        .map_err(e500)?
+    else {
+        return done();
+    };
+
+    if count_pending_password_resets(&pool, user_id)
+        .await
+        .map_err(e500)?
+        >= MAX_PENDING_PASSWORD_RESETS
+    {
+        tracing::warn!("Too many pending password resets, not sending another one");
+        return done();
+    }
+
+    let reset_token = generate_reset_token();
+    store_password_reset(&pool, user_id, &reset_token)
+        .await
+        .map_err(e500)?;
+    // Failing loudly would tell that an account uses the email.
+    if let Err(e) = send_password_reset_email(&email_client, &email, &base_url.0, &reset_token)
+        .await
+        .context("Failed to send the password reset email")
+    {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send the password reset email"
+        );
+    }
+    done()
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetFormData {
+    reset_token: String,
+    new_password: SecretString,
+    new_password_check: SecretString,
+}
+
+#[tracing::instrument(name = "Reset a password", skip_all)]
+pub async fn reset_password(
+    request: HttpRequest,
+    form: web::Form<ResetFormData>,
+    pool: web::Data<PgPool>,
+    breached_passwords: web::Data<BreachedPasswords>,
+    hashing: web::Data<PasswordHashingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ResetFormData {
+        reset_token,
+        new_password,
+        new_password_check,
+    } = form.0;
+    let Some(user_id) = get_password_reset_user_id(&pool, &reset_token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(invalid_password_reset());
+    };
+
+    if new_password.expose_secret() != new_password_check.expose_secret() {
+        FlashMessage::error("You entered two different passwords - the field values must match.")
+            .send();
+        return Ok(see_other(&password_reset_form_url(&reset_token)));
+    }
+
+    let username = get_username(user_id, &pool).await.map_err(e500)?;
+    let new_password = match NewPassword::parse(new_password, &username, &breached_passwords) {
+        Ok(new_password) => new_password,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&password_reset_form_url(&reset_token)));
+        }
+    };
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    // Also ends the existing sessions of the user.
+    change_password(user_id, new_password, None, &hashing, &mut transaction)
+        .await
+        .map_err(e500)?;
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM password_resets
+        WHERE user_id = $1
+        "#,
+        user_id
+    );
+    transaction
+        .execute(query)
+        .await
+        .context("Failed to delete the used password reset")
+        .map_err(e500)?;
+    record_audit_event(
+        &mut *transaction,
+        &request,
+        Some(user_id),
+        AuditAction::ResetPassword,
+        None,
+    )
+    .await
+    .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+
+    FlashMessage::info("Your password has been reset, you can now log in.").send();
+    Ok(see_other("/login"))
+}
+
+#[tracing::instrument(name = "Get active user by email", skip(pool, email))]
+async fn get_active_user_id_by_email(
+    pool: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id
+        FROM users
+        WHERE email = $1 AND active
+        "#,
+        email.as_ref()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the user")?;
+    Ok(row.map(|r| r.user_id))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn count_pending_password_resets(pool: &PgPool, user_id: Uuid) -> Result<i64, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT count(*) as "count!"
+        FROM password_resets
+        WHERE user_id = $1 AND expires_at > now()
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count pending password resets")?;
+    Ok(row.count)
+}
+
+#[tracing::instrument(skip_all)]
+async fn store_password_reset(
+    pool: &PgPool,
+    user_id: Uuid,
+    reset_token: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO password_resets (reset_token_hash, user_id, expires_at)
+        VALUES ($1, $2, $3)
+        "#,
+        hash_reset_token(reset_token),
+        user_id,
+        Utc::now() + PASSWORD_RESET_VALIDITY
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the password reset")?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_password_reset_email(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    base_url: &str,
+    reset_token: &str,
+) -> Result<(), reqwest::Error> {
+    let reset_link = format!(
+        "{}/password-reset/confirm?reset_token={}",
+        base_url, reset_token
+    );
+    let plain_body = format!(
+        "Someone asked to reset the password of your newsletter account.\n\
+        Visit {} to choose a new password. The link is valid for one hour.\n\
+        If it was not you, you can ignore this email.",
+        reset_link
+    );
+    let html_body = format!(
+        "Someone asked to reset the password of your newsletter account.<br/>\
+        Click <a href=\"{}\">here</a> to choose a new password. The link is valid for one hour.<br/>\
+        If it was not you, you can ignore this email.",
+        reset_link
+    );
+    email_client
+        .send_email(email, "Reset your password", &html_body, &plain_body)
+        .await
+}
+
+fn generate_reset_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(25)
+        .collect()
+}
@@ -1,10 +1,11 @@
 //! src/routes/subscriptions.rs
+use crate::audit_log::{AuditAction, record_audit_event};
 use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
 use crate::email_client::EmailClient;
 use crate::startup::ApplicationBaseUrl;
 use actix_web::ResponseError;
 use actix_web::http::StatusCode;
-use actix_web::{HttpResponse, web};
+use actix_web::{HttpRequest, HttpResponse, web};
 use anyhow::Context;
 use chrono::Utc;
 use rand::distributions::Alphanumeric;
@@ -88,12 +89,13 @@ pub struct FormData {
 
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, pool, email_client, base_url),
+    skip(request, form, pool, email_client, base_url),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name)
 )]
 pub async fn subscribe(
+    request: HttpRequest,
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
@@ -113,6 +115,14 @@ pub async fn subscribe(
     store_token(&mut transaction, subscriber_id, &subscription_token)
         .await
         .context("Failed to store the confirmation token for a new subscriber.")?;
+    record_audit_event(
+        &mut *transaction,
+        &request,
+        None,
+        AuditAction::Subscribe,
+        Some(subscriber_id.to_string()),
+    )
+    .await?;
 
     transaction
         .commit()
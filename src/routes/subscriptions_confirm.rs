@@ -1,5 +1,6 @@
 //! src/routes/subscriptions_confirm.rs
-use actix_web::{HttpResponse, web};
+use crate::audit_log::{AuditAction, record_audit_event};
+use actix_web::{HttpRequest, HttpResponse, web};
 use sqlx::PgPool;
 use uuid::Uuid;
 
@@ -8,8 +9,12 @@ pub struct Parameters {
     subscription_token: String,
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
-pub async fn confirm(parameters: web::Query<Parameters>, pool: web::Data<PgPool>) -> HttpResponse {
+#[tracing::instrument(name = "Confirm a pending subscriber", skip(request, parameters, pool))]
+pub async fn confirm(
+    request: HttpRequest,
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
     let id = match get_subscriber_id_from_token(&pool, &parameters.subscription_token).await {
         Ok(id) => id,
         Err(_) => return HttpResponse::InternalServerError().finish(),
@@ -20,6 +25,18 @@ pub async fn confirm(parameters: web::Query<Parameters>, pool: web::Data<PgPool>
             if confirm_subscriber(&pool, subscriber_id).await.is_err() {
                 return HttpResponse::InternalServerError().finish();
             }
+            if record_audit_event(
+                pool.get_ref(),
+                &request,
+                None,
+                AuditAction::ConfirmSubscription,
+                Some(subscriber_id.to_string()),
+            )
+            .await
+            .is_err()
+            {
+                return HttpResponse::InternalServerError().finish();
+            }
             HttpResponse::Ok().finish()
         }
     }
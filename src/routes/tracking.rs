@@ -0,0 +1,112 @@
+//! src/routes/tracking.rs
+use crate::configuration::TrackingSettings;
+use crate::tracking::extract_links;
+use crate::utils::{e500, see_other};
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{HttpResponse, web};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// A 1x1 transparent GIF.
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+#[tracing::instrument(name = "Track an issue open", skip(pool, tracking))]
+pub async fn track_open(
+    path: web::Path<(Uuid, Uuid)>,
+    pool: web::Data<PgPool>,
+    tracking: web::Data<TrackingSettings>,
+) -> HttpResponse {
+    let (issue_id, subscriber_id) = path.into_inner();
+    if tracking.enabled {
+        // A broken pixel is worse than a missing data point.
+        let _ = record_event(&pool, issue_id, subscriber_id, "open", None).await;
+    }
+    HttpResponse::Ok()
+        .content_type("image/gif")
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(PIXEL)
+}
+
+#[tracing::instrument(name = "Track a link click", skip(pool, tracking))]
+pub async fn track_click(
+    path: web::Path<(Uuid, Uuid, usize)>,
+    pool: web::Data<PgPool>,
+    tracking: web::Data<TrackingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (issue_id, subscriber_id, link_index) = path.into_inner();
+    let html_content = match get_issue_html_content(&pool, issue_id)
+        .await
+        .map_err(e500)?
+    {
+        Some(html_content) => html_content,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    let Some(link) = extract_links(&html_content).into_iter().nth(link_index) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    if tracking.enabled {
+        let _ = record_event(&pool, issue_id, subscriber_id, "click", Some(&link)).await;
+    }
+    Ok(see_other(&link))
+}
+
+#[tracing::instrument(name = "Get issue HTML content", skip(pool))]
+async fn get_issue_html_content(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT html_content
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| r.html_content))
+}
+
+#[tracing::instrument(name = "Record a tracking event", skip(pool))]
+async fn record_event(
+    pool: &PgPool,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+    event_type: &str,
+    link_url: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    // Events for unknown issues or subscribers are silently dropped.
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issue_events (
+            event_id,
+            newsletter_issue_id,
+            subscriber_id,
+            event_type,
+            link_url,
+            occurred_at
+        )
+        SELECT $1, $2, $3, $4, $5, now()
+        WHERE
+            EXISTS (SELECT 1 FROM newsletter_issues WHERE newsletter_issue_id = $2) AND
+            EXISTS (SELECT 1 FROM subscriptions WHERE id = $3)
+        "#,
+        Uuid::new_v4(),
+        issue_id,
+        subscriber_id,
+        event_type,
+        link_url
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+    Ok(())
+}
@@ -0,0 +1,118 @@
+//! src/routes/webhooks.rs
+use crate::configuration::TrackingSettings;
+use crate::utils::e500;
+use actix_web::http::header::{self, HeaderMap, HeaderValue};
+use actix_web::{HttpRequest, HttpResponse, web};
+use anyhow::Context;
+use base64::Engine;
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct WebhookToken(pub SecretString);
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OpenEvent {
+    recipient: String,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+}
+
+#[tracing::instrument(name = "Record an open reported by the provider", skip_all)]
+pub async fn postmark_open(
+    request: HttpRequest,
+    event: web::Json<OpenEvent>,
+    pool: web::Data<PgPool>,
+    token: web::Data<WebhookToken>,
+    tracking: web::Data<TrackingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if !is_authorized(request.headers(), &token.0) {
+        return Ok(HttpResponse::Unauthorized()
+            .insert_header((
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(r#"Basic realm="webhooks""#),
+            ))
+            .finish());
+    }
+    // Acknowledged, so that the provider does not retry.
+    if !tracking.enabled {
+        return Ok(HttpResponse::Ok().finish());
+    }
+    // Opens of emails that were not sent for an issue (e.g. confirmation
+    // emails) are acknowledged and ignored, so that the provider does not
+    // retry them.
+    let Some(issue_id) = event
+        .metadata
+        .get("newsletter_issue_id")
+        .and_then(|id| Uuid::parse_str(id).ok())
+    else {
+        return Ok(HttpResponse::Ok().finish());
+    };
+    let variant = event.metadata.get("variant").map(String::as_str);
+    record_open(&pool, issue_id, &event.recipient, variant)
+        .await
+        .map_err(e500)?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// The provider authenticates with HTTP basic auth, using the webhook token
+/// as password.
+fn is_authorized(headers: &HeaderMap, token: &SecretString) -> bool {
+    let Some(encoded) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Basic "))
+    else {
+        return false;
+    };
+    let Some(decoded) = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+    else {
+        return false;
+    };
+    match decoded.split_once(':') {
+        Some((_, password)) => {
+            bool::from(password.as_bytes().ct_eq(token.expose_secret().as_bytes()))
+        }
+        None => false,
+    }
+}
+
+#[tracing::instrument(name = "Store a reported open", skip(pool, recipient))]
+async fn record_open(
+    pool: &PgPool,
+    issue_id: Uuid,
+    recipient: &str,
+    variant: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    // Events for unknown issues or subscribers are silently dropped.
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issue_events (
+            event_id,
+            newsletter_issue_id,
+            subscriber_id,
+            event_type,
+            variant,
+            occurred_at
+        )
+        SELECT $1, i.newsletter_issue_id, s.id, 'open', $4, now()
+        FROM newsletter_issues i, subscriptions s
+        WHERE i.newsletter_issue_id = $2 AND s.email = $3
+        "#,
+        Uuid::new_v4(),
+        issue_id,
+        recipient,
+        variant
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the open event")?;
+    Ok(())
+}
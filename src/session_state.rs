@@ -3,6 +3,10 @@ use actix_session::SessionExt;
 use actix_session::{Session, SessionGetError, SessionInsertError};
 use actix_web::dev::Payload;
 use actix_web::{FromRequest, HttpRequest};
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{Rng, thread_rng};
+use secrecy::{ExposeSecret, SecretString};
 use std::future::{Ready, ready};
 use uuid::Uuid;
 
@@ -12,26 +16,174 @@ Therefore we must use the extension trait pattern
 */
 pub struct TypedSession(Session);
 
+/// The raw values of a newsletter form that failed validation, kept around
+/// to re-populate the form.
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+pub struct NewsletterDraft {
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    #[serde(default)]
+    pub variant_title: String,
+    #[serde(default)]
+    pub variant_text_content: String,
+    #[serde(default)]
+    pub variant_html_content: String,
+}
+
+/// What is needed to complete a single sign-on login once the provider
+/// redirects back.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct PendingOidcLogin {
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+}
+
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const SESSION_ID_KEY: &'static str = "session_id";
+    const LOGGED_IN_AT_KEY: &'static str = "logged_in_at";
+    const LAST_SEEN_AT_KEY: &'static str = "last_seen_at";
+    const CSRF_TOKEN_KEY: &'static str = "csrf_token";
+    const NEWSLETTER_DRAFT_KEY: &'static str = "newsletter_draft";
+    const PENDING_USER_ID_KEY: &'static str = "pending_user_id";
+    const FAILED_TOTP_ATTEMPTS_KEY: &'static str = "failed_totp_attempts";
+    const PENDING_OIDC_LOGIN_KEY: &'static str = "pending_oidc_login";
+    const PENDING_TOTP_SECRET_KEY: &'static str = "pending_totp_secret";
 
     pub fn renew(&self) {
         self.0.renew()
     }
 
-    pub fn insert_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
-        self.0.insert(Self::USER_ID_KEY, user_id)
+    /// Log the user in, under the `session_id` recorded for this session.
+    pub fn insert_user_id(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<(), SessionInsertError> {
+        let now = Utc::now();
+        self.0.insert(Self::USER_ID_KEY, user_id)?;
+        self.0.insert(Self::SESSION_ID_KEY, session_id)?;
+        self.0.insert(Self::LOGGED_IN_AT_KEY, now)?;
+        self.0.insert(Self::LAST_SEEN_AT_KEY, now)?;
+        self.0.insert(Self::CSRF_TOKEN_KEY, generate_csrf_token())
+    }
+
+    /// The synchronizer token that forms submitted in this session must
+    /// carry, generated at login.
+    pub fn get_csrf_token(&self) -> Result<Option<String>, SessionGetError> {
+        self.0.get(Self::CSRF_TOKEN_KEY)
+    }
+
+    /// `None` for sessions started before login times were recorded.
+    pub fn get_logged_in_at(&self) -> Result<Option<DateTime<Utc>>, SessionGetError> {
+        self.0.get(Self::LOGGED_IN_AT_KEY)
+    }
+
+    pub fn get_last_seen_at(&self) -> Result<Option<DateTime<Utc>>, SessionGetError> {
+        self.0.get(Self::LAST_SEEN_AT_KEY)
+    }
+
+    pub fn insert_last_seen_at(&self, at: DateTime<Utc>) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::LAST_SEEN_AT_KEY, at)
+    }
+
+    /// `None` for sessions started before sessions were recorded.
+    pub fn get_session_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::SESSION_ID_KEY)
     }
 
     pub fn get_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
         self.0.get(Self::USER_ID_KEY)
     }
 
+    /// A user who passed the password check but still has to provide a
+    /// second factor.
+    pub fn insert_pending_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_USER_ID_KEY, user_id)
+    }
+
+    pub fn get_pending_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::PENDING_USER_ID_KEY)
+    }
+
+    pub fn remove_pending_user_id(&self) {
+        self.0.remove(Self::PENDING_USER_ID_KEY);
+        self.0.remove(Self::FAILED_TOTP_ATTEMPTS_KEY);
+    }
+
+    /// Returns the number of failed attempts so far, including this one.
+    pub fn record_failed_totp_attempt(&self) -> Result<u32, anyhow::Error> {
+        let attempts = self
+            .0
+            .get::<u32>(Self::FAILED_TOTP_ATTEMPTS_KEY)?
+            .unwrap_or(0)
+            + 1;
+        self.0.insert(Self::FAILED_TOTP_ATTEMPTS_KEY, attempts)?;
+        Ok(attempts)
+    }
+
+    /// A TOTP secret shown to the user but not confirmed yet.
+    pub fn insert_pending_totp_secret(
+        &self,
+        secret: &SecretString,
+    ) -> Result<(), SessionInsertError> {
+        self.0
+            .insert(Self::PENDING_TOTP_SECRET_KEY, secret.expose_secret())
+    }
+
+    pub fn get_pending_totp_secret(&self) -> Result<Option<SecretString>, SessionGetError> {
+        Ok(self
+            .0
+            .get::<String>(Self::PENDING_TOTP_SECRET_KEY)?
+            .map(SecretString::from))
+    }
+
+    pub fn remove_pending_totp_secret(&self) {
+        self.0.remove(Self::PENDING_TOTP_SECRET_KEY);
+    }
+
+    pub fn insert_pending_oidc_login(
+        &self,
+        pending: &PendingOidcLogin,
+    ) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_OIDC_LOGIN_KEY, pending)
+    }
+
+    /// A provider callback can only complete the login once.
+    pub fn take_pending_oidc_login(&self) -> Result<Option<PendingOidcLogin>, SessionGetError> {
+        let pending = self.0.get(Self::PENDING_OIDC_LOGIN_KEY)?;
+        self.0.remove(Self::PENDING_OIDC_LOGIN_KEY);
+        Ok(pending)
+    }
+
+    pub fn insert_newsletter_draft(
+        &self,
+        draft: &NewsletterDraft,
+    ) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::NEWSLETTER_DRAFT_KEY, draft)
+    }
+
+    pub fn take_newsletter_draft(&self) -> Result<Option<NewsletterDraft>, SessionGetError> {
+        let draft = self.0.get(Self::NEWSLETTER_DRAFT_KEY)?;
+        self.0.remove(Self::NEWSLETTER_DRAFT_KEY);
+        Ok(draft)
+    }
+
     pub fn log_out(self) {
         self.0.purge()
     }
 }
 
+fn generate_csrf_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
 impl FromRequest for TypedSession {
     /*
     This is a complicated way of saying
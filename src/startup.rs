@@ -1,21 +1,53 @@
 //! src/startup.rs
-use crate::authentication::reject_anonymous_users;
-use crate::configuration::{DatabaseSettings, Settings};
+use crate::authentication::{
+    LoginThrottle, OidcClient, reject_anonymous_users, reject_cross_site_requests, require_editor,
+    require_owner, require_session,
+};
+use crate::configuration::{AttachmentSettings, DatabaseSettings, Settings};
+use crate::domain::BreachedPasswords;
 use crate::email_client::EmailClient;
-use crate::routes::{admin_dashboard, change_password, change_password_form, log_out};
+use crate::idempotency::{ReplayMessage, enforce_idempotency};
+use crate::metrics::track_http_metrics;
+use crate::migrations::migrate_database;
+use crate::routes::{
+    ISSUE_ACCEPTED, MAX_FORM_SIZE_BYTES, confirm, health_check, health_ready, publish_newsletter,
+    publish_newsletter_form, subscribe,
+};
+use crate::routes::{
+    MetricsToken, SessionStoreClient, WebhookToken, audit_log, metrics, postmark_open,
+};
+use crate::routes::{accept_invitation, accept_invitation_form};
+use crate::routes::{
+    admin_dashboard, change_email, change_email_form, change_password, change_password_form,
+    log_out,
+};
+use crate::routes::{cancel_issue, issue_stats, list_issues, pause_issue, resume_issue};
+use crate::routes::{confirm_totp_enrolment, totp_enrolment_form};
+use crate::routes::{create_api_token, list_api_tokens, revoke_api_token};
+use crate::routes::{deactivate_user, invite_user, list_users};
+use crate::routes::{delete_attachment, too_large_message, upload_attachment};
+use crate::routes::{home, login, login_form, login_totp, login_totp_form};
+use crate::routes::{list_sessions, revoke_other_sessions, revoke_session};
+use crate::routes::{login_oidc, login_oidc_callback};
 use crate::routes::{
-    confirm, health_check, publish_newsletter, publish_newsletter_form, subscribe,
+    password_reset_form, request_password_reset, request_password_reset_form, reset_password,
 };
-use crate::routes::{home, login, login_form};
+use crate::routes::{track_click, track_open};
+use crate::utils::{TrustedProxies, see_other};
+use actix_multipart::MultipartError;
+use actix_multipart::form::MultipartFormConfig;
 use actix_session::SessionMiddleware;
+use actix_session::config::BrowserSession;
 use actix_session::storage::RedisSessionStore;
 use actix_web::cookie::Key;
+use actix_web::cookie::time::Duration;
 use actix_web::dev::Server;
+use actix_web::error::{InternalError, PayloadError};
 use actix_web::middleware::from_fn;
 use actix_web::web::Data;
 use actix_web::{App, HttpServer, web};
-use actix_web_flash_messages::FlashMessagesFramework;
 use actix_web_flash_messages::storage::CookieMessageStore;
+use actix_web_flash_messages::{FlashMessage, FlashMessagesFramework};
 use secrecy::ExposeSecret;
 use secrecy::SecretString;
 use sqlx::PgPool;
@@ -36,7 +68,8 @@ pub struct HmacSecret(pub SecretString);
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
         let connection_pool = get_connection_pool(&configuration.database);
-        let email_client = configuration.email_client.client();
+        migrate_database(&connection_pool, configuration.database.migrate_on_startup).await?;
+        let email_client = configuration.email_client.clone().client();
 
         let address = format!(
             "{}:{}",
@@ -45,15 +78,7 @@ impl Application {
 
         let listener = TcpListener::bind(address)?;
         let port = listener.local_addr()?.port();
-        let server = run(
-            listener,
-            connection_pool,
-            email_client,
-            configuration.application.base_url,
-            configuration.application.hmac_secret,
-            configuration.redis_uri,
-        )
-        .await?;
+        let server = run(listener, connection_pool, email_client, configuration).await?;
 
         Ok(Self { port, server })
     }
@@ -71,45 +96,219 @@ async fn run(
     listener: TcpListener,
     db_pool: PgPool,
     email_client: EmailClient,
-    base_url: String,
-    hmac_secret: SecretString,
-    redis_url: SecretString,
+    configuration: Settings,
 ) -> Result<Server, anyhow::Error> {
+    let Settings {
+        application,
+        redis_uri: redis_url,
+        tracking,
+        attachments,
+        email_client: email_client_settings,
+        ab_testing,
+        login_throttling,
+        password_policy,
+        password_hashing,
+        session: session_settings,
+        oidc,
+        idempotency,
+        metrics: metrics_settings,
+        health,
+        ..
+    } = configuration;
+    let hmac_secret = application.hmac_secret;
     let db_pool = Data::new(db_pool);
     let email_client = Data::new(email_client);
-    let base_url = Data::new(ApplicationBaseUrl(base_url));
+    let oidc_client = Data::new(OidcClient::new(oidc, &application.base_url));
+    let base_url = Data::new(ApplicationBaseUrl(application.base_url));
+    let trusted_proxies = Data::new(TrustedProxies(application.trusted_proxies));
+    let tracking = Data::new(tracking);
+    let multipart_config = multipart_form_config(&attachments);
+    let attachments = Data::new(attachments);
+    let ab_testing = Data::new(ab_testing);
+    let webhook_token = Data::new(WebhookToken(email_client_settings.webhook_token));
+    let metrics_token = Data::new(MetricsToken(metrics_settings.bearer_token));
+    let password_hashing = Data::new(password_hashing);
+    let idempotency = Data::new(idempotency);
+    let health = Data::new(health);
+    // The timeouts are enforced by `reject_anonymous_users`: Redis only
+    // has to forget sessions after the longest of them, once they cannot be
+    // used anymore.
+    let session_lifecycle = BrowserSession::default().state_ttl(Duration::seconds(
+        session_settings.absolute_timeout_seconds as i64,
+    ));
+    let session_settings = Data::new(session_settings);
+    let breached_passwords = BreachedPasswords::new(
+        password_policy.breached_passwords_directory,
+        password_policy.require_complete_list,
+    );
+    breached_passwords.check_directory()?;
+    let breached_passwords = Data::new(breached_passwords);
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
     let message_store =
         CookieMessageStore::builder(Key::from(hmac_secret.expose_secret().as_bytes())).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
     let redis_store = RedisSessionStore::new(redis_url.expose_secret()).await?;
+    let login_throttle = Data::new(LoginThrottle::new(&redis_url, login_throttling).await?);
+    let session_store_client = Data::new(SessionStoreClient(redis::Client::open(
+        redis_url.expose_secret(),
+    )?));
     let server = HttpServer::new(move || {
         App::new()
-            .wrap(SessionMiddleware::new(
-                redis_store.clone(),
-                secret_key.clone(),
-            ))
+            .wrap(
+                SessionMiddleware::builder(redis_store.clone(), secret_key.clone())
+                    .session_lifecycle(session_lifecycle.clone())
+                    .build(),
+            )
             .wrap(message_framework.clone())
             .wrap(TracingLogger::default())
+            .wrap(from_fn(track_http_metrics))
             .route("/health_check", web::get().to(health_check))
+            .route("/health/ready", web::get().to(health_ready))
             .route("/subscriptions", web::post().to(subscribe))
             .route("/subscriptions/confirm", web::get().to(confirm))
             .route("/login", web::post().to(login))
             .route("/login", web::get().to(login_form))
+            .route("/login/oidc", web::get().to(login_oidc))
+            .route("/login/oidc/callback", web::get().to(login_oidc_callback))
+            .route("/login/totp", web::get().to(login_totp_form))
+            .route("/login/totp", web::post().to(login_totp))
             .route("/", web::get().to(home))
+            .route(
+                "/tracking/open/{issue_id}/{subscriber_id}",
+                web::get().to(track_open),
+            )
+            .route(
+                "/tracking/click/{issue_id}/{subscriber_id}/{link_index}",
+                web::get().to(track_click),
+            )
+            .route("/webhooks/postmark/open", web::post().to(postmark_open))
+            .route("/metrics", web::get().to(metrics))
+            .route("/invitations/accept", web::get().to(accept_invitation_form))
+            .route("/invitations/accept", web::post().to(accept_invitation))
+            .route(
+                "/password-reset",
+                web::get().to(request_password_reset_form),
+            )
+            .route("/password-reset", web::post().to(request_password_reset))
+            .route(
+                "/password-reset/confirm",
+                web::get().to(password_reset_form),
+            )
+            .route("/password-reset/confirm", web::post().to(reset_password))
             .service(
                 web::scope("/admin")
+                    // The CSRF middleware reads URL-encoded bodies before they
+                    // reach their route: allow the largest of the admin forms.
+                    .app_data(web::PayloadConfig::new(MAX_FORM_SIZE_BYTES))
+                    .wrap(from_fn(reject_cross_site_requests))
                     .wrap(from_fn(reject_anonymous_users))
                     .route("/dashboard", web::get().to(admin_dashboard))
-                    .route("/newsletters", web::get().to(publish_newsletter_form))
-                    .route("/newsletters", web::post().to(publish_newsletter))
-                    .route("/password", web::get().to(change_password_form))
-                    .route("/password", web::post().to(change_password))
-                    .route("/logout", web::post().to(log_out)),
+                    .service(
+                        web::resource("/newsletters")
+                            .app_data(ReplayMessage(ISSUE_ACCEPTED))
+                            .app_data(web::FormConfig::default().limit(MAX_FORM_SIZE_BYTES))
+                            .wrap(from_fn(enforce_idempotency))
+                            .wrap(from_fn(require_editor))
+                            .route(web::get().to(publish_newsletter_form))
+                            .route(web::post().to(publish_newsletter)),
+                    )
+                    .service(
+                        web::resource("/newsletters/attachments")
+                            .wrap(from_fn(require_editor))
+                            .route(web::post().to(upload_attachment)),
+                    )
+                    .service(
+                        web::resource("/newsletters/attachments/{attachment_id}/delete")
+                            .wrap(from_fn(require_editor))
+                            .route(web::post().to(delete_attachment)),
+                    )
+                    .route("/issues", web::get().to(list_issues))
+                    .route("/issues/{issue_id}/stats", web::get().to(issue_stats))
+                    .service(
+                        web::resource("/issues/{issue_id}/pause")
+                            .wrap(from_fn(require_editor))
+                            .route(web::post().to(pause_issue)),
+                    )
+                    .service(
+                        web::resource("/issues/{issue_id}/resume")
+                            .wrap(from_fn(require_editor))
+                            .route(web::post().to(resume_issue)),
+                    )
+                    .service(
+                        web::resource("/issues/{issue_id}/cancel")
+                            .wrap(from_fn(require_editor))
+                            .route(web::post().to(cancel_issue)),
+                    )
+                    .service(
+                        web::scope("/users")
+                            .wrap(from_fn(require_owner))
+                            .wrap(from_fn(require_session))
+                            .route("", web::get().to(list_users))
+                            .route("", web::post().to(invite_user))
+                            .route("/{user_id}/deactivate", web::post().to(deactivate_user)),
+                    )
+                    .service(
+                        web::scope("/audit-log")
+                            .wrap(from_fn(require_owner))
+                            .route("", web::get().to(audit_log)),
+                    )
+                    // Account management needs a session, not an API token.
+                    .service(
+                        web::resource("/totp")
+                            .wrap(from_fn(require_session))
+                            .route(web::get().to(totp_enrolment_form))
+                            .route(web::post().to(confirm_totp_enrolment)),
+                    )
+                    .service(
+                        web::resource("/password")
+                            .wrap(from_fn(require_session))
+                            .route(web::get().to(change_password_form))
+                            .route(web::post().to(change_password)),
+                    )
+                    .service(
+                        web::resource("/email")
+                            .wrap(from_fn(require_session))
+                            .route(web::get().to(change_email_form))
+                            .route(web::post().to(change_email)),
+                    )
+                    .service(
+                        web::scope("/sessions")
+                            .wrap(from_fn(require_session))
+                            .route("", web::get().to(list_sessions))
+                            .route("/revoke-others", web::post().to(revoke_other_sessions))
+                            .route("/{session_id}/revoke", web::post().to(revoke_session)),
+                    )
+                    .service(
+                        web::scope("/api-tokens")
+                            .wrap(from_fn(require_session))
+                            .route("", web::get().to(list_api_tokens))
+                            .route("", web::post().to(create_api_token))
+                            .route("/{api_token_id}/revoke", web::post().to(revoke_api_token)),
+                    )
+                    .service(
+                        web::resource("/logout")
+                            .wrap(from_fn(require_session))
+                            .route(web::post().to(log_out)),
+                    ),
             )
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(trusted_proxies.clone())
+            .app_data(tracking.clone())
+            .app_data(attachments.clone())
+            .app_data(multipart_config.clone())
+            .app_data(ab_testing.clone())
+            .app_data(webhook_token.clone())
+            .app_data(metrics_token.clone())
+            .app_data(login_throttle.clone())
+            .app_data(breached_passwords.clone())
+            .app_data(password_hashing.clone())
+            .app_data(session_settings.clone())
+            .app_data(oidc_client.clone())
+            .app_data(idempotency.clone())
+            .app_data(health.clone())
+            .app_data(session_store_client.clone())
             .app_data(Data::new(HmacSecret(hmac_secret.clone())))
     })
     .listen(listener)?
@@ -118,6 +317,23 @@ async fn run(
     Ok(server)
 }
 
+fn multipart_form_config(settings: &AttachmentSettings) -> MultipartFormConfig {
+    // Leave some room for the multipart framing and the other form fields,
+    // the exact file size limit is enforced by the upload handler.
+    let limit = settings.max_file_size_bytes + 64 * 1024;
+    let max_file_size_bytes = settings.max_file_size_bytes;
+    MultipartFormConfig::default()
+        .total_limit(limit)
+        .memory_limit(limit)
+        .error_handler(move |e, _| match e {
+            MultipartError::Payload(PayloadError::Overflow) => {
+                FlashMessage::error(too_large_message(max_file_size_bytes)).send();
+                InternalError::from_response(e, see_other("/admin/newsletters")).into()
+            }
+            e => e.into(),
+        })
+}
+
 pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
     PgPoolOptions::new().connect_lazy_with(configuration.connection_options())
 }
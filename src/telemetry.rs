@@ -1,29 +1,56 @@
 //! src/telemetry.rs
+use crate::configuration::OtlpSettings;
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use std::collections::HashMap;
+use std::sync::OnceLock;
 use tokio::task::JoinHandle;
-use tracing::Subscriber;
 use tracing::subscriber::set_global_default;
+use tracing::{Span, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::{EnvFilter, Registry, layer::SubscriberExt};
 
+/// The W3C header carrying the trace and parent span ids.
+const TRACEPARENT: &str = "traceparent";
+
+/// Kept around to flush pending spans on shutdown.
+static TRACER_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
 /// Create a tracing subscriber with JSON formatting.
 ///
+/// When OTLP export is enabled, spans are also sent to the configured
+/// collector and the W3C trace context of incoming requests is honoured.
+///
 /// # Examples
 ///
 /// ```
+/// use zero2prod::configuration::OtlpSettings;
 /// use zero2prod::telemetry::get_subscriber;
 ///
+/// let otlp = OtlpSettings {
+///     enabled: false,
+///     endpoint: "http://localhost:4318/v1/traces".to_string(),
+///     timeout_milliseconds: 3000,
+/// };
 /// let subscriber = get_subscriber(
 ///     "test".to_string(),
 ///     "info".to_string(),
-///     std::io::stdout
+///     std::io::stdout,
+///     &otlp,
 /// );
 /// ```
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
     sink: Sink,
+    otlp: &OtlpSettings,
 ) -> impl Subscriber + Send + Sync
 where
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
@@ -31,6 +58,15 @@ where
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
 
+    let otel_layer = otlp.enabled.then(|| {
+        let provider = build_tracer_provider(name.clone(), otlp);
+        let tracer = provider.tracer(name.clone());
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        opentelemetry::global::set_tracer_provider(provider.clone());
+        let _ = TRACER_PROVIDER.set(provider);
+        tracing_opentelemetry::layer().with_tracer(tracer)
+    });
+
     let formatting_layer = BunyanFormattingLayer::new(name, sink);
 
     // Registry is a Subscriber around which multiple Layers implementing various behaviors may be added.
@@ -39,6 +75,31 @@ where
         .with(env_filter)
         .with(JsonStorageLayer)
         .with(formatting_layer)
+        .with(otel_layer)
+}
+
+fn build_tracer_provider(name: String, otlp: &OtlpSettings) -> SdkTracerProvider {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&otlp.endpoint)
+        .with_timeout(otlp.timeout())
+        .build()
+        .expect("Failed to build the OTLP span exporter");
+    SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(name).build())
+        .build()
+}
+
+/// Flush the spans that have not been exported yet. A no-op when OTLP
+/// export is disabled.
+pub fn shutdown_tracer_provider() {
+    if let Some(provider) = TRACER_PROVIDER.get()
+        && let Err(e) = provider.shutdown()
+    {
+        // The subscriber outlives the provider: the log layer still writes.
+        tracing::error!(error.message = %e, "Failed to shut down the tracer provider");
+    }
 }
 
 pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
@@ -56,3 +117,62 @@ where
     let current_span = tracing::Span::current();
     tokio::task::spawn_blocking(move || current_span.in_scope(f))
 }
+
+/// The W3C `traceparent` of the current span, to be stored next to work
+/// that is picked up later on, e.g. by the issue delivery worker.
+///
+/// Returns `None` when spans are not exported.
+pub fn current_trace_context() -> Option<String> {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&Span::current().context(), &mut carrier);
+    carrier.remove(TRACEPARENT)
+}
+
+/// Make `span` a child of the span described by `traceparent`. Must be
+/// called before `span` is first entered.
+pub fn set_parent_trace_context(span: &Span, traceparent: &str) {
+    let carrier = HashMap::from([(TRACEPARENT.to_string(), traceparent.to_string())]);
+    let parent = TraceContextPropagator::new().extract(&carrier);
+    // Fails only if the span was already entered or is disabled.
+    let _ = span.set_parent(parent);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{current_trace_context, set_parent_trace_context};
+    use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::{Registry, layer::SubscriberExt};
+
+    #[test]
+    fn trace_context_round_trips_through_a_traceparent() {
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber = Registry::default()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let enqueue_span = tracing::info_span!("Enqueue");
+            let traceparent = enqueue_span
+                .in_scope(current_trace_context)
+                .expect("No trace context for an exported span");
+            let trace_id = enqueue_span.context().span().span_context().trace_id();
+            assert!(traceparent.contains(&trace_id.to_string()));
+
+            let delivery_span = tracing::info_span!("Deliver");
+            set_parent_trace_context(&delivery_span, &traceparent);
+            let delivery_trace_id = delivery_span.context().span().span_context().trace_id();
+            assert_eq!(delivery_trace_id, trace_id);
+        });
+    }
+
+    #[test]
+    fn there_is_no_trace_context_when_spans_are_not_exported() {
+        let subscriber = Registry::default();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("Enqueue");
+            assert_eq!(span.in_scope(current_trace_context), None);
+        });
+    }
+}
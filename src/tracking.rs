@@ -0,0 +1,132 @@
+//! src/tracking.rs
+use std::ops::Range;
+use uuid::Uuid;
+
+/// Return the trackable links of an HTML body in document order.
+///
+/// Only absolute `http`/`https` hrefs are tracked - anchors, `mailto:` and
+/// relative links are left alone. The position of a link in the returned
+/// list is the index used by the click-tracking endpoint.
+///
+/// # Examples
+///
+/// ```
+/// use zero2prod::tracking::extract_links;
+/// use assert2::assert;
+///
+/// let html = r#"<a href="https://example.com">x</a> <a href="mailto:a@b.com">y</a>"#;
+/// assert!(extract_links(html) == vec!["https://example.com".to_string()]);
+/// ```
+pub fn extract_links(html: &str) -> Vec<String> {
+    trackable_hrefs(html)
+        .into_iter()
+        .map(|range| html[range].replace("&amp;", "&"))
+        .collect()
+}
+
+/// Rewrite every trackable link to go through the click-tracking endpoint
+/// and add an open-tracking pixel to the body.
+pub fn add_tracking(html: &str, base_url: &str, issue_id: Uuid, subscriber_id: Uuid) -> String {
+    let mut tracked = String::with_capacity(html.len());
+    let mut cursor = 0;
+    for (index, range) in trackable_hrefs(html).into_iter().enumerate() {
+        tracked.push_str(&html[cursor..range.start]);
+        tracked.push_str(&format!(
+            "{base_url}/tracking/click/{issue_id}/{subscriber_id}/{index}"
+        ));
+        cursor = range.end;
+    }
+    tracked.push_str(&html[cursor..]);
+
+    let pixel = format!(
+        r#"<img src="{base_url}/tracking/open/{issue_id}/{subscriber_id}" width="1" height="1" alt="">"#
+    );
+    match tracked.to_ascii_lowercase().rfind("</body>") {
+        Some(position) => tracked.insert_str(position, &pixel),
+        None => tracked.push_str(&pixel),
+    }
+    tracked
+}
+
+fn trackable_hrefs(html: &str) -> Vec<Range<usize>> {
+    // ASCII lowercasing preserves byte offsets, so ranges found in
+    // `lowercase` are valid for `html` as well.
+    let lowercase = html.to_ascii_lowercase();
+    let mut ranges = Vec::new();
+    let mut cursor = 0;
+    while let Some(offset) = lowercase[cursor..].find("href=") {
+        let value_start = cursor + offset + "href=".len();
+        cursor = value_start;
+        let Some(quote) = html[value_start..]
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+        else {
+            continue;
+        };
+        let value_start = value_start + 1;
+        let Some(length) = html[value_start..].find(quote) else {
+            break;
+        };
+        let range = value_start..value_start + length;
+        cursor = range.end;
+        let value = lowercase[range.clone()].trim_start();
+        if value.starts_with("http://") || value.starts_with("https://") {
+            ranges.push(range);
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_tracking, extract_links};
+    use uuid::Uuid;
+
+    #[test]
+    fn only_absolute_http_links_are_extracted() {
+        let html = r##"<a href="https://a.com">a</a><a href='#top'>b</a>
+            <A HREF='http://b.com/?x=1&amp;y=2'>c</a><a href="/relative">d</a>"##;
+        assert_eq!(
+            extract_links(html),
+            vec![
+                "https://a.com".to_string(),
+                "http://b.com/?x=1&y=2".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn links_are_rewritten_in_order() {
+        let issue_id = Uuid::new_v4();
+        let subscriber_id = Uuid::new_v4();
+        let html = r#"<a href="https://a.com">a</a><a href="mailto:x@y.com">m</a><a href="https://b.com">b</a>"#;
+
+        let tracked = add_tracking(html, "http://base", issue_id, subscriber_id);
+
+        let prefix = format!("http://base/tracking/click/{issue_id}/{subscriber_id}");
+        assert!(tracked.contains(&format!(r#"href="{prefix}/0""#)));
+        assert!(tracked.contains(&format!(r#"href="{prefix}/1""#)));
+        assert!(tracked.contains(r#"href="mailto:x@y.com""#));
+        assert!(!tracked.contains("https://a.com"));
+    }
+
+    #[test]
+    fn the_pixel_is_placed_inside_the_body() {
+        let issue_id = Uuid::new_v4();
+        let subscriber_id = Uuid::new_v4();
+        let html = "<html><body><p>Hi</p></body></html>";
+
+        let tracked = add_tracking(html, "http://base", issue_id, subscriber_id);
+
+        let pixel = format!("http://base/tracking/open/{issue_id}/{subscriber_id}");
+        let pixel_position = tracked.find(&pixel).unwrap();
+        assert!(pixel_position < tracked.find("</body>").unwrap());
+    }
+
+    #[test]
+    fn the_pixel_is_appended_to_fragments() {
+        let tracked = add_tracking("<p>Hi</p>", "http://base", Uuid::new_v4(), Uuid::new_v4());
+        assert!(tracked.starts_with("<p>Hi</p><img"));
+    }
+}
@@ -1,6 +1,7 @@
 //! src/utils.rs
-use actix_web::HttpResponse;
 use actix_web::http::header::LOCATION;
+use actix_web::{HttpRequest, HttpResponse, web};
+use std::net::IpAddr;
 
 pub fn e500<T>(e: T) -> actix_web::Error
 where
@@ -21,3 +22,90 @@ where
 {
     actix_web::error::ErrorBadRequest(e)
 }
+
+/// Safe in text and in quoted attribute values.
+pub fn escape_html(s: &str) -> String {
+    htmlescape::encode_minimal(s)
+}
+
+/// Reverse proxies whose `X-Forwarded-For` header is believed.
+pub struct TrustedProxies(pub Vec<IpAddr>);
+
+/// The client address. Requests relayed by trusted proxies are attributed
+/// to the address they were forwarded for; otherwise `X-Forwarded-For` is
+/// ignored, as any client can set it.
+pub fn client_ip(request: &HttpRequest) -> String {
+    let trusted_proxies = request
+        .app_data::<web::Data<TrustedProxies>>()
+        .expect("The trusted proxies are not registered as application data");
+    let Some(peer) = request.peer_addr() else {
+        return "unknown".into();
+    };
+    let forwarded_for = request
+        .headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|h| h.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+    resolve_client_ip(peer.ip(), &forwarded_for, &trusted_proxies.0).to_string()
+}
+
+/// Walk the proxy chain back from the peer, up to the first address that is
+/// not a trusted proxy: entries further left may have been made up.
+fn resolve_client_ip(peer: IpAddr, forwarded_for: &str, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+    for hop in forwarded_for.rsplit(',') {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+        match hop.trim().parse() {
+            Ok(ip) => client = ip,
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_client_ip;
+    use std::net::IpAddr;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_addresses_are_ignored_without_a_trusted_proxy() {
+        let client = resolve_client_ip(ip("203.0.113.7"), "10.0.0.1", &[]);
+        assert_eq!(client, ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn a_trusted_proxy_reports_the_client_address() {
+        let proxy = ip("192.0.2.1");
+        let client = resolve_client_ip(proxy, "203.0.113.7", &[proxy]);
+        assert_eq!(client, ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn addresses_prepended_by_the_client_are_ignored() {
+        let proxy = ip("192.0.2.1");
+        let client = resolve_client_ip(proxy, "10.0.0.1, 203.0.113.7", &[proxy]);
+        assert_eq!(client, ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn chains_of_trusted_proxies_are_followed() {
+        let (outer, inner) = (ip("192.0.2.1"), ip("192.0.2.2"));
+        let client = resolve_client_ip(inner, "203.0.113.7, 192.0.2.1", &[outer, inner]);
+        assert_eq!(client, ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn a_trusted_proxy_without_a_valid_header_is_the_client() {
+        let proxy = ip("192.0.2.1");
+        assert_eq!(resolve_client_ip(proxy, "", &[proxy]), proxy);
+        assert_eq!(resolve_client_ip(proxy, "not-an-ip", &[proxy]), proxy);
+    }
+}
@@ -0,0 +1,207 @@
+//! tests/api/api_tokens.rs
+use crate::helpers::{TestUser, assert_is_redirect_to, spawn_app};
+use assert2::assert;
+use uuid::Uuid;
+
+fn newsletter_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Release notes",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    })
+}
+
+#[tokio::test]
+async fn api_tokens_give_access_to_the_admin_endpoints() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("editor").await;
+    assert!(token.starts_with("z2p_"));
+
+    let client = app.bearer_client(&token);
+    let response = client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status() == 200);
+
+    let response = client
+        .post(format!("{}/admin/newsletters", &app.address))
+        .form(&newsletter_request_body())
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+#[tokio::test]
+async fn tokens_are_limited_to_their_scope() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("viewer").await;
+
+    let response = app
+        .bearer_client(&token)
+        .post(format!("{}/admin/newsletters", &app.address))
+        .form(&newsletter_request_body())
+        .send()
+        .await
+        .unwrap();
+
+    assert!(response.status() == 403);
+}
+
+#[tokio::test]
+async fn tokens_cannot_have_more_access_than_their_user() {
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    editor.login(&app).await;
+
+    let response = app
+        .post_create_api_token(&serde_json::json!({
+            "name": "CI",
+            "scope": "owner"
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<p><i>You cannot create a token with the owner scope.</i></p>"));
+}
+
+#[tokio::test]
+async fn invalid_tokens_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .bearer_client("z2p_not-a-token")
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert!(response.status() == 401);
+    assert!(response.headers()["WWW-Authenticate"] == r#"Bearer realm="admin""#);
+}
+
+#[tokio::test]
+async fn revoked_tokens_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("editor").await;
+    let api_token_id = sqlx::query!("SELECT api_token_id FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .api_token_id;
+
+    let response = app.post_revoke_api_token(api_token_id).await;
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<p><i>The token has been revoked.</i></p>"));
+    assert!(html_page.contains("<td>revoked</td>"));
+
+    let response = app
+        .bearer_client(&token)
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status() == 401);
+}
+
+#[tokio::test]
+async fn tokens_of_deactivated_users_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("viewer").await;
+    sqlx::query!(
+        "UPDATE users SET active = false WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app
+        .bearer_client(&token)
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert!(response.status() == 401);
+}
+
+#[tokio::test]
+async fn tokens_are_stored_hashed_and_record_their_last_use() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("viewer").await;
+    app.bearer_client(&token)
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    let row = sqlx::query!("SELECT token_hash, last_used_at FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(row.token_hash != token);
+    assert!(row.last_used_at.is_some());
+    let html_page = app.get_api_tokens_html().await;
+    assert!(!html_page.contains(&token));
+    assert!(!html_page.contains("<td>never</td>"));
+}
+
+#[tokio::test]
+async fn the_page_showing_a_new_token_is_not_cached() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_create_api_token(&serde_json::json!({
+            "name": "CI",
+            "scope": "viewer"
+        }))
+        .await;
+
+    assert!(response.status() == 200);
+    assert!(response.headers()["Cache-Control"] == "no-store");
+}
+
+#[tokio::test]
+async fn tokens_cannot_be_used_for_account_management() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("owner").await;
+    let client = app.bearer_client(&token);
+
+    for (method, path) in [
+        ("GET", "/admin/api-tokens"),
+        ("POST", "/admin/api-tokens"),
+        ("POST", "/admin/password"),
+        ("POST", "/admin/email"),
+        ("POST", "/admin/totp"),
+        ("GET", "/admin/sessions"),
+        ("POST", "/admin/sessions/revoke-others"),
+        ("POST", "/admin/users"),
+        ("POST", "/admin/logout"),
+    ] {
+        let response = client
+            .request(method.parse().unwrap(), format!("{}{}", &app.address, path))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status() == 403, "{method} {path}");
+    }
+    let row = sqlx::query!("SELECT count(*) as \"count!\" FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(row.count == 1);
+}
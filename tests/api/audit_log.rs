@@ -0,0 +1,225 @@
+//! tests/api/audit_log.rs
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, spawn_app, spawn_app_with};
+use assert2::assert;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+struct AuditLogEntry {
+    user_id: Option<Uuid>,
+    action: String,
+    target: Option<String>,
+    ip_address: String,
+}
+
+async fn audit_log_entries(app: &TestApp) -> Vec<AuditLogEntry> {
+    sqlx::query_as!(
+        AuditLogEntry,
+        "SELECT user_id, action, target, ip_address FROM audit_log ORDER BY created_at"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_audit_log() {
+    let app = spawn_app().await;
+
+    let response = app.get_audit_log(&[]).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn only_owners_can_see_the_audit_log() {
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    editor.login(&app).await;
+
+    let response = app.get_audit_log(&[]).await;
+
+    assert!(response.status() == 403);
+}
+
+#[tokio::test]
+async fn logins_and_logouts_are_recorded_with_the_client_ip() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_logout().await;
+
+    let entries = audit_log_entries(&app).await;
+
+    assert!(entries.len() == 2);
+    assert!(entries[0].action == "log_in");
+    assert!(entries[1].action == "log_out");
+    for entry in entries {
+        assert!(entry.user_id == Some(app.test_user.user_id));
+        // Forwarded by the test client, a trusted proxy
+        assert!(entry.ip_address.starts_with("10."));
+    }
+}
+
+#[tokio::test]
+async fn failed_logins_are_not_recorded() {
+    let app = spawn_app().await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password"
+    }))
+    .await;
+
+    assert!(audit_log_entries(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn publishing_an_issue_is_recorded_with_its_id() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    let entry = audit_log_entries(&app).await.pop().unwrap();
+    assert!(entry.action == "publish_newsletter");
+    assert!(entry.user_id == Some(app.test_user.user_id));
+    assert!(entry.target == Some(issue_id.to_string()));
+}
+
+#[tokio::test]
+async fn subscriptions_are_recorded_anonymously() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    assert!(response.status() == 200);
+
+    let entries = audit_log_entries(&app).await;
+    assert!(entries.len() == 1);
+    assert!(entries[0].action == "subscribe");
+    assert!(entries[0].user_id == None);
+}
+
+#[tokio::test]
+async fn forwarded_addresses_from_untrusted_peers_are_not_recorded() {
+    let app = spawn_app_with(|c| c.application.trusted_proxies = vec![]).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    assert!(response.status() == 200);
+
+    let entries = audit_log_entries(&app).await;
+    assert!(entries[0].ip_address == "127.0.0.1");
+}
+
+#[tokio::test]
+async fn the_audit_log_lists_who_did_what() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let new_password = Uuid::new_v4().to_string();
+    app.post_change_password(&serde_json::json!({
+        "current_password": &app.test_user.password,
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    }))
+    .await;
+
+    let html_page = app.get_audit_log_html(&[]).await;
+
+    assert!(html_page.contains(&format!(
+        "<td>{}</td><td>change_password</td>",
+        app.test_user.username
+    )));
+    assert!(html_page.contains(&format!(
+        "<td>{}</td><td>log_in</td>",
+        app.test_user.username
+    )));
+}
+
+#[tokio::test]
+async fn the_audit_log_can_be_filtered_by_action_and_username() {
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    editor.login(&app).await;
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_audit_log_html(&[("action", "log_out")]).await;
+    assert!(html_page.contains(&format!("<td>{}</td><td>log_out</td>", editor.username)));
+    assert!(!html_page.contains("<td>log_in</td>"));
+
+    let html_page = app
+        .get_audit_log_html(&[("username", &app.test_user.username)])
+        .await;
+    assert!(html_page.contains(&format!(
+        "<td>{}</td><td>log_in</td>",
+        app.test_user.username
+    )));
+    assert!(!html_page.contains(&editor.username));
+}
+
+#[tokio::test]
+async fn unknown_actions_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_audit_log(&[("action", "drop_table")]).await;
+
+    assert!(response.status() == 400);
+}
+
+#[tokio::test]
+async fn the_audit_log_is_paginated() {
+    let app = spawn_app().await;
+    for i in 0..60 {
+        sqlx::query!(
+            "INSERT INTO audit_log (audit_log_id, user_id, action, target, ip_address, created_at)
+            VALUES ($1, NULL, 'subscribe', $2, '127.0.0.1', now() - make_interval(mins => $3))",
+            Uuid::new_v4(),
+            format!("subscriber-{i}"),
+            i,
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_audit_log_html(&[]).await;
+    assert!(html_page.contains("<td>subscriber-0</td>"));
+    assert!(!html_page.contains("<td>subscriber-59</td>"));
+    assert!(html_page.contains(">Older</a>"));
+    assert!(!html_page.contains(">Newer</a>"));
+
+    let html_page = app.get_audit_log_html(&[("page", "1")]).await;
+    assert!(html_page.contains("<td>subscriber-59</td>"));
+    assert!(html_page.contains(">Newer</a>"));
+    assert!(!html_page.contains(">Older</a>"));
+}
@@ -0,0 +1,114 @@
+//! tests/api/change_email.rs
+use crate::helpers::{TestUser, assert_is_redirect_to, spawn_app};
+use assert2::assert;
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_change_your_email() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "email": "new@example.com",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_email_can_be_changed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "email": "new@example.com",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/email");
+
+    let html_page = app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>Your email has been changed.</i></p>"));
+    assert!(html_page.contains("<p>Your email is new@example.com.</p>"));
+    let row = sqlx::query!(
+        "SELECT email FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(row.email.as_deref() == Some("new@example.com"));
+}
+
+#[tokio::test]
+async fn the_current_password_is_required_to_change_the_email() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "current_password": "wrong-password",
+            "email": "new@example.com",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/email");
+
+    let html_page = app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>The current password is incorrect.</i></p>"));
+    let row = sqlx::query!(
+        "SELECT email FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(row.email.as_deref() == Some(app.test_user.email.as_str()));
+}
+
+#[tokio::test]
+async fn invalid_or_taken_emails_are_rejected() {
+    let app = spawn_app().await;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    for (email, message) in [
+        (
+            "not-an-email",
+            "not-an-email is not a valid subscriber email.",
+        ),
+        (
+            other_user.email.as_str(),
+            "Another user already has this email.",
+        ),
+    ] {
+        let response = app
+            .post_change_email(&serde_json::json!({
+                "current_password": &app.test_user.password,
+                "email": email,
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/email");
+
+        let html_page = app.get_change_email_html().await;
+        assert!(html_page.contains(&format!("<p><i>{message}</i></p>")));
+    }
+}
+
+#[tokio::test]
+async fn rejected_emails_are_escaped_in_the_error_message() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_change_email(&serde_json::json!({
+        "current_password": &app.test_user.password,
+        "email": "<script>alert(1)</script>",
+    }))
+    .await;
+
+    let html_page = app.get_change_email_html().await;
+    assert!(html_page.contains("&lt;script&gt;alert(1)&lt;/script&gt; is not a valid"));
+    assert!(!html_page.contains("<script>"));
+}
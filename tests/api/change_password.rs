@@ -122,3 +122,80 @@ async fn change_password_works() {
     let response = app.post_login(&login_body).await;
     assert_is_redirect_to(&response, "/admin/dashboard");
 }
+
+#[tokio::test]
+async fn changing_password_ends_the_other_sessions() {
+    let app = spawn_app().await;
+    let other_browser = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    other_browser
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .send()
+        .await
+        .unwrap();
+    app.test_user.login(&app).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    app.post_change_password(&serde_json::json!({
+        "current_password": &app.test_user.password,
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    }))
+    .await;
+
+    // This session is still valid, the other one is not
+    assert!(app.get_admin_dashboard().await.status() == 200);
+    let response = other_browser
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn new_passwords_must_follow_the_password_policy() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for (new_password, error_message) in [
+        (
+            "too-short".to_string(),
+            "The new password must be at least 12 characters long.",
+        ),
+        (
+            "a".repeat(129),
+            "The new password must be at most 128 characters long.",
+        ),
+        (
+            app.test_user.username.clone(),
+            "The new password cannot be your username.",
+        ),
+        (
+            "password1234".to_string(),
+            "This password has appeared in a data breach, please choose another one.",
+        ),
+    ] {
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "current_password": &app.test_user.password,
+                "new_password": &new_password,
+                "new_password_check": &new_password,
+            }))
+            .await;
+
+        assert_is_redirect_to(&response, "/admin/password");
+        let html_page = app.get_change_password_html().await;
+        assert!(
+            html_page.contains(&format!("<p><i>{error_message}</i></p>")),
+            "{new_password} was not rejected"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! tests/api/csrf.rs
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use assert2::assert;
+
+async fn post_logout_with_token(app: &TestApp, csrf_token: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/logout", &app.address))
+        .header("X-CSRF-Token", csrf_token)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn admin_forms_carry_the_csrf_token_of_the_session() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let csrf_token = app.csrf_token().await;
+    assert!(csrf_token.len() == 32);
+
+    let field = format!(r#"<input hidden type="text" name="csrf_token" value="{csrf_token}">"#);
+    assert!(app.get_admin_dashboard_html().await.contains(&field));
+    assert!(app.get_change_password_html().await.contains(&field));
+    assert!(app.get_sessions_html().await.contains(&field));
+    assert!(app.get_api_tokens_html().await.contains(&field));
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains(&format!(
+        r#"action="/admin/newsletters/attachments" method="post" enctype="multipart/form-data">
+                    {field}"#
+    )));
+    // Never in a URL, where it would end up in logs
+    assert!(!html_page.contains("?csrf_token="));
+}
+
+#[tokio::test]
+async fn submissions_without_a_csrf_token_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/logout", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert!(response.status() == 403);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("This form has expired")
+    );
+    // Still logged in
+    assert!(app.get_admin_dashboard().await.status() == 200);
+}
+
+#[tokio::test]
+async fn submissions_with_a_wrong_csrf_token_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = post_logout_with_token(&app, "not-the-token").await;
+
+    assert!(response.status() == 403);
+    assert!(app.get_admin_dashboard().await.status() == 200);
+}
+
+#[tokio::test]
+async fn the_csrf_token_of_another_session_is_rejected() {
+    let app = spawn_app().await;
+    // e.g. the attacker's own session, on another browser
+    let other_browser = app.other_browser(&app.test_user).await;
+    let other_html = other_browser
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let other_token = other_html
+        .split(r#"name="csrf_token" value=""#)
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .unwrap()
+        .to_string();
+    app.test_user.login(&app).await;
+    assert!(other_token != app.csrf_token().await);
+
+    let response = post_logout_with_token(&app, &other_token).await;
+
+    assert!(response.status() == 403);
+}
+
+#[tokio::test]
+async fn a_new_csrf_token_is_issued_at_each_login() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let old_token = app.csrf_token().await;
+    assert_is_redirect_to(&app.post_logout().await, "/login");
+    app.test_user.login(&app).await;
+
+    let response = post_logout_with_token(&app, &old_token).await;
+
+    assert!(response.status() == 403);
+}
+
+#[tokio::test]
+async fn the_csrf_token_is_accepted_as_a_form_field() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let csrf_token = app.csrf_token().await;
+
+    // Like a browser submitting the logout form of the dashboard
+    let response = app
+        .api_client
+        .post(format!("{}/admin/logout", &app.address))
+        .form(&serde_json::json!({ "csrf_token": csrf_token }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+async fn post_attachment_form(app: &TestApp, csrf_token: Option<&str>) -> reqwest::Response {
+    let file = reqwest::multipart::Part::bytes(b"Hello".to_vec())
+        .file_name("hello.txt")
+        .mime_str("text/plain")
+        .unwrap();
+    let mut form = reqwest::multipart::Form::new().part("file", file);
+    if let Some(csrf_token) = csrf_token {
+        form = form.text("csrf_token", csrf_token.to_owned());
+    }
+    app.api_client
+        .post(format!("{}/admin/newsletters/attachments", &app.address))
+        .multipart(form)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn the_csrf_token_is_accepted_as_a_multipart_field() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let csrf_token = app.csrf_token().await;
+
+    let response = post_attachment_form(&app, Some(&csrf_token)).await;
+
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("<p><i>hello.txt has been attached.</i></p>"));
+}
+
+#[tokio::test]
+async fn multipart_submissions_without_a_valid_csrf_token_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for csrf_token in [None, Some("not-the-token")] {
+        let response = post_attachment_form(&app, csrf_token).await;
+        assert!(response.status() == 403);
+    }
+    let row = sqlx::query!(r#"SELECT count(*) as "count!" FROM newsletter_issue_attachments"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(row.count == 0);
+}
+
+#[tokio::test]
+async fn the_form_fields_are_still_passed_to_the_handler() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let csrf_token = app.csrf_token().await;
+
+    let response = app
+        .api_client
+        .post(format!("{}/admin/api-tokens", &app.address))
+        .form(&serde_json::json!({
+            "name": "Deploy script",
+            "scope": "viewer",
+            "csrf_token": csrf_token,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert!(response.status() == 200);
+    assert!(response.text().await.unwrap().contains("z2p_"));
+}
+
+#[tokio::test]
+async fn requests_with_an_api_token_do_not_need_a_csrf_token() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token("editor").await;
+
+    let response = app
+        .bearer_client(&token)
+        .post(format!("{}/admin/newsletters", &app.address))
+        .form(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
@@ -1,5 +1,5 @@
 //! tests/api/health_check.rs
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with};
 use assert2::assert;
 
 #[tokio::test]
@@ -16,3 +16,66 @@ async fn health_check_works() {
     assert!(response.status().is_success());
     assert!(let Some(0) = response.content_length());
 }
+
+#[tokio::test]
+async fn readiness_reports_each_dependency() {
+    let app = spawn_app().await;
+
+    let response = app.get_readiness().await;
+
+    assert!(response.status() == 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert!(report["ready"] == true);
+    for dependency in ["postgres", "redis", "migrations"] {
+        assert!(report["checks"][dependency]["status"] == "up");
+        assert!(report["checks"][dependency]["latency_ms"].is_u64());
+    }
+    // Not checked unless enabled.
+    assert!(report["checks"]["email_provider"].is_null());
+}
+
+#[tokio::test]
+async fn readiness_fails_while_migrations_are_pending() {
+    let app = spawn_app().await;
+    sqlx::query!(
+        "DELETE FROM _sqlx_migrations WHERE version = (SELECT max(version) FROM _sqlx_migrations)"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app.get_readiness().await;
+
+    assert!(response.status() == 503);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert!(report["ready"] == false);
+    assert!(report["checks"]["migrations"]["status"] == "down");
+    assert!(report["checks"]["postgres"]["status"] == "up");
+}
+
+#[tokio::test]
+async fn readiness_checks_the_email_provider_when_enabled() {
+    let app = spawn_app_with(|c| c.health.check_email_provider = true).await;
+
+    let response = app.get_readiness().await;
+
+    assert!(response.status() == 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert!(report["checks"]["email_provider"]["status"] == "up");
+}
+
+#[tokio::test]
+async fn readiness_fails_if_the_email_provider_is_unreachable() {
+    let app = spawn_app_with(|c| {
+        c.health.check_email_provider = true;
+        // Nothing listens on the discard port.
+        c.email_client.base_url = "http://127.0.0.1:9".into();
+    })
+    .await;
+
+    let response = app.get_readiness().await;
+
+    assert!(response.status() == 503);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert!(report["checks"]["email_provider"]["status"] == "down");
+}
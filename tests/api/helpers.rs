@@ -2,27 +2,38 @@
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
 use assert2::assert;
+use rand::Rng;
 use reqwest::Url;
-use secrecy::SecretString;
+use secrecy::{ExposeSecret, SecretString};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use std::sync::LazyLock;
 use uuid::Uuid;
 use wiremock::MockServer;
-use zero2prod::configuration::{DatabaseSettings, get_configuration};
+use zero2prod::configuration::{DatabaseSettings, Settings, TrackingSettings, get_configuration};
 use zero2prod::email_client::EmailClient;
-use zero2prod::issue_delivery_worker::{ExecutionOutcome, try_execute_task};
+use zero2prod::issue_delivery_worker::{AttachmentCache, ExecutionOutcome, try_execute_task};
+use zero2prod::migrations::MIGRATOR;
 use zero2prod::startup::{Application, get_connection_pool};
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
 
 static TRACING: LazyLock<()> = LazyLock::new(|| {
     let default_filter_level = "debug".to_string();
     let subscriber_name = "test".to_string();
+    let otlp = get_configuration()
+        .expect("Failed to read configuration.")
+        .otlp;
 
     if std::env::var("TEST_LOG").is_ok() {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::stdout);
+        let subscriber = get_subscriber(
+            subscriber_name,
+            default_filter_level,
+            std::io::stdout,
+            &otlp,
+        );
         init_subscriber(subscriber);
     } else {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::sink);
+        let subscriber =
+            get_subscriber(subscriber_name, default_filter_level, std::io::sink, &otlp);
         init_subscriber(subscriber);
     }
 });
@@ -35,6 +46,10 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub base_url: String,
+    pub tracking: TrackingSettings,
+    pub webhook_token: String,
+    pub metrics_token: String,
 }
 
 pub struct ConfirmationLinks {
@@ -43,14 +58,34 @@ pub struct ConfirmationLinks {
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// The configuration of a test application, with a database of its own and
+/// a random port.
+pub fn test_configuration() -> Settings {
+    let mut c = get_configuration().expect("Failed to read configuration.");
+    c.database.database_name = Uuid::new_v4().to_string();
+    c.application.port = 0;
+    // A small sample of the breached password list, with a range for every
+    // breached password used in the tests.
+    c.password_policy.breached_passwords_directory = "tests/breached_passwords".into();
+    c.password_policy.require_complete_list = false;
+    c
+}
+
+/// Spawn the application after tweaking its configuration with `customize`.
+pub async fn spawn_app_with(customize: impl FnOnce(&mut Settings)) -> TestApp {
     LazyLock::force(&TRACING);
 
     let email_server = MockServer::start().await;
     let configuration = {
-        let mut c = get_configuration().expect("Failed to read configuration.");
-        c.database.database_name = Uuid::new_v4().to_string();
-        c.application.port = 0;
+        let mut c = test_configuration();
         c.email_client.base_url = email_server.uri();
+        // The test client poses as a reverse proxy, forwarding requests
+        // for a client address of its own.
+        c.application.trusted_proxies = vec!["127.0.0.1".parse().unwrap(), "::1".parse().unwrap()];
+        customize(&mut c);
         c
     };
     configure_database(&configuration.database).await;
@@ -64,9 +99,15 @@ pub async fn spawn_app() -> TestApp {
     // Intentionally discarding the JoinHandle without triggering clippy warnings.
     drop(tokio::spawn(application.run_until_stopped()));
 
+    // Failed logins are throttled per client IP: give each test its own.
+    let client_ip = random_client_ip();
+    let mut default_headers = reqwest::header::HeaderMap::new();
+    default_headers.insert("X-Forwarded-For", client_ip.parse().unwrap());
     let client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
         .cookie_store(true)
+        .user_agent("zero2prod-tests")
+        .default_headers(default_headers)
         .build()
         .unwrap();
 
@@ -77,7 +118,19 @@ pub async fn spawn_app() -> TestApp {
         port: application_port,
         test_user: TestUser::generate(),
         api_client: client,
-        email_client: configuration.email_client.client(),
+        email_client: configuration.email_client.clone().client(),
+        base_url: configuration.application.base_url,
+        tracking: configuration.tracking,
+        webhook_token: configuration
+            .email_client
+            .webhook_token
+            .expose_secret()
+            .to_string(),
+        metrics_token: configuration
+            .metrics
+            .bearer_token
+            .expose_secret()
+            .to_string(),
     };
 
     test_app.test_user.store(&test_app.db_pool).await;
@@ -86,6 +139,18 @@ pub async fn spawn_app() -> TestApp {
 }
 
 pub async fn configure_database(config: &DatabaseSettings) -> PgPool {
+    let connection_pool = create_database(config).await;
+
+    MIGRATOR
+        .run(&connection_pool)
+        .await
+        .expect("Failed to migrate the database");
+
+    connection_pool
+}
+
+/// Create an empty database, without running the migrations.
+pub async fn create_database(config: &DatabaseSettings) -> PgPool {
     let maintenance_settings = DatabaseSettings {
         database_name: "postgres".to_string(),
         username: "postgres".to_string(),
@@ -93,6 +158,7 @@ pub async fn configure_database(config: &DatabaseSettings) -> PgPool {
         port: config.port,
         host: config.host.clone(),
         require_ssl: config.require_ssl,
+        migrate_on_startup: false,
     };
 
     let mut connection = PgConnection::connect_with(&maintenance_settings.connection_options())
@@ -104,25 +170,24 @@ pub async fn configure_database(config: &DatabaseSettings) -> PgPool {
         .await
         .expect("Failed to create database");
 
-    let connection_pool = PgPool::connect_with(config.connection_options())
-        .await
-        .expect("Failed to connect to Postgres");
-
-    sqlx::migrate!("./migrations")
-        .run(&connection_pool)
+    PgPool::connect_with(config.connection_options())
         .await
-        .expect("Failed to migrate the database");
-
-    connection_pool
+        .expect("Failed to connect to Postgres")
 }
 
 impl TestApp {
     pub async fn dispatch_all_pending_emails(&self) {
+        let mut attachments = AttachmentCache::default();
         loop {
-            if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
-                    .await
-                    .unwrap()
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.base_url,
+                &self.tracking,
+                &mut attachments,
+            )
+            .await
+            .unwrap()
             {
                 break;
             }
@@ -199,6 +264,17 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    /// The CSRF token of the current session, as embedded in the admin
+    /// forms - empty when logged out.
+    pub async fn csrf_token(&self) -> String {
+        let html = self.get_admin_dashboard_html().await;
+        html.split(r#"name="csrf_token" value=""#)
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap_or_default()
+            .to_string()
+    }
+
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
             .get(format!("{}/admin/password", &self.address))
@@ -218,6 +294,31 @@ impl TestApp {
         self.api_client
             .post(format!("{}/admin/password", &self.address))
             .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_change_email_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/email", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/email", &self.address))
+            .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -226,6 +327,7 @@ impl TestApp {
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
             .post(format!("{}/admin/logout", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -243,6 +345,374 @@ impl TestApp {
         self.get_publish_newsletter().await.text().await.unwrap()
     }
 
+    pub async fn post_attachment(
+        &self,
+        file_name: &str,
+        content_type: &str,
+        content: Vec<u8>,
+        inline: bool,
+    ) -> reqwest::Response {
+        let file = reqwest::multipart::Part::bytes(content)
+            .file_name(file_name.to_owned())
+            .mime_str(content_type)
+            .unwrap();
+        let mut form = reqwest::multipart::Form::new().part("file", file);
+        if inline {
+            form = form.text("inline", "on");
+        }
+        self.api_client
+            .post(format!("{}/admin/newsletters/attachments", &self.address))
+            .multipart(form)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_issues_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/issues", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_issue_action(&self, issue_id: Uuid, action: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/issues/{}/{}",
+                &self.address, issue_id, action
+            ))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_issue_stats_html(&self, issue_id: Uuid) -> String {
+        self.api_client
+            .get(format!("{}/admin/issues/{}/stats", &self.address, issue_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_users_html(&self) -> String {
+        self.get_users().await.text().await.unwrap()
+    }
+
+    pub async fn post_invite_user<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/users", &self.address))
+            .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_deactivate_user(&self, user_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/users/{}/deactivate",
+                &self.address, user_id
+            ))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_audit_log(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/audit-log", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_audit_log_html(&self, query: &[(&str, &str)]) -> String {
+        self.get_audit_log(query).await.text().await.unwrap()
+    }
+
+    pub async fn post_accept_invitation<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/invitations/accept", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_request_password_reset<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_revoke_session(&self, session_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/sessions/{}/revoke",
+                &self.address, session_id
+            ))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_other_sessions(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/sessions/revoke-others", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// A separate cookie jar, logged in as `user`.
+    pub async fn other_browser(&self, user: &TestUser) -> reqwest::Client {
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .cookie_store(true)
+            .user_agent("another-browser")
+            .build()
+            .unwrap();
+        let response = client
+            .post(format!("{}/login", &self.address))
+            .form(&serde_json::json!({
+                "username": &user.username,
+                "password": &user.password
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_is_redirect_to(&response, "/admin/dashboard");
+        client
+    }
+
+    pub async fn get_api_tokens_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/api-tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_create_api_token<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/api-tokens", &self.address))
+            .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_api_token(&self, api_token_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/api-tokens/{}/revoke",
+                &self.address, api_token_id
+            ))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Create an API token with `scope` for the logged in user, and return it.
+    pub async fn create_api_token(&self, scope: &str) -> String {
+        let html_page = self
+            .post_create_api_token(&serde_json::json!({
+                "name": "CI",
+                "scope": scope
+            }))
+            .await
+            .text()
+            .await
+            .unwrap();
+        let start = html_page.find("<p><code>").unwrap() + "<p><code>".len();
+        let end = start + html_page[start..].find("</code>").unwrap();
+        html_page[start..end].to_string()
+    }
+
+    /// A client without cookies, authenticating with `token`.
+    pub fn bearer_client(&self, token: &str) -> reqwest::Client {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            format!("Bearer {token}").parse().unwrap(),
+        );
+        reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .default_headers(headers)
+            .build()
+            .unwrap()
+    }
+
+    pub async fn get_totp_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/totp", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_totp(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/totp", &self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_login_totp_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/login/totp", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_login_totp(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/login/totp", &self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Enrol the logged-in user in two-factor authentication.
+    ///
+    /// Returns the TOTP secret and the recovery codes.
+    pub async fn enable_totp(&self) -> (String, Vec<String>) {
+        let html_page = self.get_totp_html().await;
+        let secret = html_page
+            .split("secret=")
+            .nth(1)
+            .unwrap()
+            .split(['&', '<'])
+            .next()
+            .unwrap()
+            .to_string();
+
+        let response = self.post_totp(&totp_code(&secret, 0)).await;
+        assert!(response.status() == 200);
+        let html_page = response.text().await.unwrap();
+        let recovery_codes = html_page
+            .split("<li><code>")
+            .skip(1)
+            .map(|s| s.split("</code>").next().unwrap().to_string())
+            .collect();
+        (secret, recovery_codes)
+    }
+
+    pub async fn get_readiness(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/health/ready", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_metrics(&self, token: Option<&str>) -> reqwest::Response {
+        let mut request = self.api_client.get(format!("{}/metrics", &self.address));
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn get_metrics_text(&self) -> String {
+        self.get_metrics(Some(&self.metrics_token))
+            .await
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_postmark_open(
+        &self,
+        body: &serde_json::Value,
+        webhook_token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request = self
+            .api_client
+            .post(format!("{}/webhooks/postmark/open", &self.address))
+            .json(body);
+        if let Some(token) = webhook_token {
+            request = request.basic_auth("postmark", Some(token));
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
     pub async fn post_publish_newsletter<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -250,6 +720,7 @@ impl TestApp {
         self.api_client
             .post(format!("{}/admin/newsletters", &self.address))
             .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -260,18 +731,26 @@ pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub email: String,
+    pub role: &'static str,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::generate_with_role("owner")
+    }
+
+    pub fn generate_with_role(role: &'static str) -> Self {
         Self {
             user_id: Uuid::new_v4(),
             username: Uuid::new_v4().to_string(),
             password: Uuid::new_v4().to_string(),
+            email: format!("{}@example.com", Uuid::new_v4()),
+            role,
         }
     }
 
-    async fn store(&self, pool: &PgPool) {
+    pub async fn store(&self, pool: &PgPool) {
         let salt = SaltString::generate(&mut rand::thread_rng());
         let password_hash = Argon2::new(
             Algorithm::Argon2d,
@@ -282,26 +761,59 @@ impl TestUser {
         .unwrap()
         .to_string();
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash)
-            VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, password_hash, email, role)
+            VALUES ($1, $2, $3, $4, $5)",
             self.user_id,
             self.username,
             password_hash,
+            self.email,
+            self.role,
         )
         .execute(pool)
         .await
         .expect("Failed to create test user");
     }
 
-    pub async fn login(&self, app: &TestApp) {
+    pub async fn login(&self, app: &TestApp) -> reqwest::Response {
         app.post_login(&serde_json::json!({
             "username": &self.username,
             "password": &self.password
         }))
-        .await;
+        .await
     }
 }
 
+fn random_client_ip() -> String {
+    let mut rng = rand::thread_rng();
+    format!(
+        "10.{}.{}.{}",
+        rng.gen_range(0..=255),
+        rng.gen_range(0..=255),
+        rng.gen_range(1..=254)
+    )
+}
+
+/// The TOTP code for `secret`, `steps` time steps away from now.
+pub fn totp_code(secret: &str, steps: i64) -> String {
+    let totp = totp_rs::TOTP::new(
+        totp_rs::Algorithm::SHA1,
+        6,
+        0,
+        30,
+        totp_rs::Secret::Encoded(secret.to_string())
+            .to_bytes()
+            .unwrap(),
+        None,
+        "test".to_string(),
+    )
+    .unwrap();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    totp.generate((now + steps * 30) as u64)
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert!(response.status() == 303);
     assert!(response.headers().get("Location").unwrap() == location);
@@ -0,0 +1,299 @@
+//! tests/api/idempotency.rs
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, spawn_app, spawn_app_with};
+use assert2::assert;
+use sqlx::{Postgres, Transaction};
+use std::time::Duration;
+use uuid::Uuid;
+use zero2prod::configuration::IdempotencySettings;
+use zero2prod::idempotency::{
+    IdempotencyKey, NextAction, delete_expired_idempotency_keys, try_processing,
+};
+
+fn newsletter_request_body(idempotency_key: &str) -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": idempotency_key
+    })
+}
+
+/// Pretend that every saved key was saved `hours` earlier.
+async fn age_idempotency_keys(app: &TestApp, hours: i32) {
+    sqlx::query!(
+        "UPDATE idempotency SET created_at = created_at - make_interval(hours => $1)",
+        hours
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn count_issues(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT count(*) as "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+/// Hold `idempotency_key` as an in-flight request would, until the returned
+/// transaction ends.
+async fn hold_idempotency_key(
+    app: &TestApp,
+    idempotency_key: &str,
+) -> Transaction<'static, Postgres> {
+    let mut transaction = app.db_pool.begin().await.unwrap();
+    sqlx::query!(
+        "INSERT INTO idempotency (user_id, idempotency_key, created_at) VALUES ($1, $2, now())",
+        app.test_user.user_id,
+        idempotency_key
+    )
+    .execute(&mut *transaction)
+    .await
+    .unwrap();
+    transaction
+}
+
+#[tokio::test]
+async fn an_expired_idempotency_key_is_treated_as_new() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let body = newsletter_request_body(&Uuid::new_v4().to_string());
+
+    let response = app.post_publish_newsletter(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    // The default retention is 48 hours.
+    age_idempotency_keys(&app, 49).await;
+    let response = app.post_publish_newsletter(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    assert!(count_issues(&app).await == 2);
+}
+
+#[tokio::test]
+async fn an_idempotency_key_within_retention_is_replayed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let body = newsletter_request_body(&Uuid::new_v4().to_string());
+
+    app.post_publish_newsletter(&body).await;
+    age_idempotency_keys(&app, 47).await;
+    let response = app.post_publish_newsletter(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    assert!(count_issues(&app).await == 1);
+}
+
+#[tokio::test]
+async fn the_cleanup_job_only_deletes_expired_keys() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_publish_newsletter(&newsletter_request_body(&Uuid::new_v4().to_string()))
+        .await;
+    age_idempotency_keys(&app, 2).await;
+    let fresh_key = Uuid::new_v4().to_string();
+    app.post_publish_newsletter(&newsletter_request_body(&fresh_key))
+        .await;
+
+    let n_deleted = delete_expired_idempotency_keys(&app.db_pool, chrono::Duration::hours(1))
+        .await
+        .unwrap();
+
+    assert!(n_deleted == 1);
+    let remaining_keys = sqlx::query!("SELECT idempotency_key FROM idempotency")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(remaining_keys.len() == 1);
+    assert!(remaining_keys[0].idempotency_key == fresh_key);
+}
+
+#[tokio::test]
+async fn a_concurrent_request_waits_for_the_saved_response() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    let mut transaction = hold_idempotency_key(&app, &idempotency_key).await;
+    let body = newsletter_request_body(&idempotency_key);
+
+    let finish_first_request = async {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        sqlx::query!(
+            r#"
+            UPDATE idempotency
+            SET
+                response_status_code = 303,
+                response_headers = ARRAY[ROW('location', '/admin/newsletters'::bytea)]::header_pair[],
+                response_body = ''::bytea
+            WHERE idempotency_key = $1
+            "#,
+            idempotency_key
+        )
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+    };
+    let (response, ()) = tokio::join!(app.post_publish_newsletter(&body), finish_first_request);
+
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert!(count_issues(&app).await == 0);
+}
+
+#[tokio::test]
+async fn a_concurrent_request_that_waits_too_long_gets_a_409() {
+    let app = spawn_app_with(|c| c.idempotency.wait_timeout_milliseconds = 200).await;
+    app.test_user.login(&app).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    let transaction = hold_idempotency_key(&app, &idempotency_key).await;
+
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body(&idempotency_key))
+        .await;
+
+    assert!(response.status() == 409);
+    assert!(response.headers().get("Retry-After").unwrap() == "1");
+    transaction.rollback().await.unwrap();
+    assert!(count_issues(&app).await == 0);
+}
+
+#[tokio::test]
+async fn requests_without_an_idempotency_key_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let mut body = newsletter_request_body("");
+    body.as_object_mut().unwrap().remove("idempotency_key");
+
+    let response = app.post_publish_newsletter(&body).await;
+
+    assert!(response.status() == 400);
+    assert!(count_issues(&app).await == 0);
+}
+
+#[tokio::test]
+async fn the_idempotency_key_can_be_sent_as_a_header() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let client = app.bearer_client(&app.create_api_token("editor").await);
+    let idempotency_key = Uuid::new_v4().to_string();
+    let mut body = newsletter_request_body("");
+    body.as_object_mut().unwrap().remove("idempotency_key");
+
+    for _ in 0..2 {
+        let response = client
+            .post(format!("{}/admin/newsletters", &app.address))
+            .header("Idempotency-Key", &idempotency_key)
+            .form(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_is_redirect_to(&response, "/admin/newsletters");
+    }
+
+    assert!(count_issues(&app).await == 1);
+}
+
+#[tokio::test]
+async fn idempotency_keys_are_scoped_per_user() {
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    let body = newsletter_request_body(&Uuid::new_v4().to_string());
+
+    app.test_user.login(&app).await;
+    app.post_publish_newsletter(&body).await;
+    app.post_logout().await;
+    editor.login(&app).await;
+    let response = app.post_publish_newsletter(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    assert!(count_issues(&app).await == 2);
+}
+
+#[tokio::test]
+async fn reusing_an_idempotency_key_for_a_different_request_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    app.post_publish_newsletter(&newsletter_request_body(&idempotency_key))
+        .await;
+
+    let mut body = newsletter_request_body(&idempotency_key);
+    body["title"] = "Another title".into();
+    let response = app.post_publish_newsletter(&body).await;
+
+    assert!(response.status() == 422);
+    assert!(count_issues(&app).await == 1);
+}
+
+#[tokio::test]
+async fn rejected_submissions_do_not_use_up_their_key() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    let mut body = newsletter_request_body(&idempotency_key);
+    body["title"] = "".into();
+    app.post_publish_newsletter(&body).await;
+
+    // e.g. the form, resubmitted once fixed
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body(&idempotency_key))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert!(count_issues(&app).await == 1);
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains(
+        "<p><i>The newsletter issue has been accepted - emails will go out shortly.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn a_retry_from_a_new_session_is_not_a_different_request() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let idempotency_key = Uuid::new_v4().to_string();
+    let mut body = newsletter_request_body(&idempotency_key);
+    body["csrf_token"] = app.csrf_token().await.into();
+    app.post_publish_newsletter(&body).await;
+
+    // The CSRF token of the form changes with the session
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+    body["csrf_token"] = app.csrf_token().await.into();
+    let response = app.post_publish_newsletter(&body).await;
+
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert!(count_issues(&app).await == 1);
+}
+
+#[tokio::test]
+async fn the_lock_timeout_does_not_apply_to_the_handler() {
+    let app = spawn_app().await;
+    let settings = IdempotencySettings {
+        retention_hours: 24,
+        cleanup_interval_seconds: 3600,
+        wait_timeout_milliseconds: 100,
+    };
+    let idempotency_key = IdempotencyKey::try_from(Uuid::new_v4().to_string()).unwrap();
+
+    let next_action = try_processing(
+        &app.db_pool,
+        &idempotency_key,
+        app.test_user.user_id,
+        b"fingerprint",
+        &settings,
+    )
+    .await
+    .unwrap();
+
+    let NextAction::StartProcessing(mut transaction) = next_action else {
+        panic!("A new key should be processed");
+    };
+    let row = sqlx::query!(r#"SELECT current_setting('lock_timeout') as "lock_timeout!""#)
+        .fetch_one(&mut *transaction)
+        .await
+        .unwrap();
+    assert!(row.lock_timeout == "0");
+}
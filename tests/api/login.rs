@@ -1,5 +1,7 @@
 //! tests/api/login.rs
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with};
+use assert2::assert;
+use uuid::Uuid;
 
 #[tokio::test]
 async fn an_error_flash_message_is_set_on_failure() {
@@ -39,3 +41,201 @@ async fn redirect_to_admin_dashboard_after_login_success() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
 }
+
+#[tokio::test]
+async fn usernames_are_locked_out_after_too_many_failures() {
+    let app = spawn_app().await;
+    let wrong_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password",
+    });
+    for _ in 0..5 {
+        let response = app.post_login(&wrong_password).await;
+        assert_is_redirect_to(&response, "/login");
+    }
+
+    // Act - The right password is rejected while locked out
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(
+        html_page.contains("<p><i>Too many failed login attempts, please try again later.</i></p>")
+    );
+}
+
+#[tokio::test]
+async fn lockouts_do_not_reveal_whether_a_username_exists() {
+    let app = spawn_app().await;
+    let unknown_username = serde_json::json!({
+        "username": Uuid::new_v4().to_string(),
+        "password": "wrong-password",
+    });
+    for _ in 0..5 {
+        app.post_login(&unknown_username).await;
+    }
+
+    let response = app.post_login(&unknown_username).await;
+
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(
+        html_page.contains("<p><i>Too many failed login attempts, please try again later.</i></p>")
+    );
+}
+
+#[tokio::test]
+async fn a_successful_login_resets_the_failure_count() {
+    let app = spawn_app().await;
+    let wrong_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password",
+    });
+    for _ in 0..4 {
+        app.post_login(&wrong_password).await;
+    }
+    app.test_user.login(&app).await;
+    app.post_logout().await;
+    for _ in 0..4 {
+        app.post_login(&wrong_password).await;
+    }
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn ip_addresses_are_locked_out_after_too_many_failures() {
+    let app = spawn_app_with(|c| c.login_throttling.max_failures_per_ip = 3).await;
+    for _ in 0..3 {
+        app.post_login(&serde_json::json!({
+            "username": Uuid::new_v4().to_string(),
+            "password": "wrong-password",
+        }))
+        .await;
+    }
+
+    // Act - A different, valid, username from the same address
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(
+        html_page.contains("<p><i>Too many failed login attempts, please try again later.</i></p>")
+    );
+}
+
+#[tokio::test]
+async fn forged_forwarded_addresses_do_not_bypass_the_ip_lockout() {
+    let app = spawn_app_with(|c| {
+        c.application.trusted_proxies = vec![];
+        c.login_throttling.max_failures_per_ip = 3;
+    })
+    .await;
+    let login = |username: String, password: String, forwarded_for: String| {
+        app.api_client
+            .post(format!("{}/login", &app.address))
+            .header("X-Forwarded-For", forwarded_for)
+            .form(&serde_json::json!({
+                "username": username,
+                "password": password,
+            }))
+            .send()
+    };
+    for i in 0..3 {
+        login(
+            Uuid::new_v4().to_string(),
+            "wrong-password".into(),
+            format!("192.0.2.{i}"),
+        )
+        .await
+        .unwrap();
+    }
+
+    let response = login(
+        app.test_user.username.clone(),
+        app.test_user.password.clone(),
+        "192.0.2.100".into(),
+    )
+    .await
+    .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+async fn stored_password_hash(app: &crate::helpers::TestApp) -> String {
+    sqlx::query!(
+        r#"SELECT password_hash as "password_hash!" FROM users WHERE user_id = $1"#,
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .password_hash
+}
+
+#[tokio::test]
+async fn outdated_password_hashes_are_upgraded_on_login() {
+    // The test user is stored with an argon2d hash
+    let app = spawn_app().await;
+    assert!(stored_password_hash(&app).await.starts_with("$argon2d$"));
+
+    let response = app.test_user.login(&app).await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert!(
+        stored_password_hash(&app)
+            .await
+            .starts_with("$argon2id$v=19$m=15000,t=2,p=1$")
+    );
+    // The upgraded hash still matches the password
+    app.post_logout().await;
+    let response = app.test_user.login(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn password_hashes_are_upgraded_to_the_configured_parameters() {
+    let app = spawn_app_with(|c| {
+        c.password_hashing.memory_size_kib = 19456;
+        c.password_hashing.iterations = 3;
+    })
+    .await;
+
+    app.test_user.login(&app).await;
+
+    assert!(
+        stored_password_hash(&app)
+            .await
+            .starts_with("$argon2id$v=19$m=19456,t=3,p=1$")
+    );
+}
+
+#[tokio::test]
+async fn failed_logins_do_not_upgrade_password_hashes() {
+    let app = spawn_app().await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password",
+    }))
+    .await;
+
+    assert!(stored_password_hash(&app).await.starts_with("$argon2d$"));
+}
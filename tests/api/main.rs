@@ -1,10 +1,22 @@
 //! tests/api/main.rs
 
 mod admin_dashboard;
+mod api_tokens;
+mod audit_log;
+mod change_email;
 mod change_password;
+mod csrf;
 mod health_check;
 mod helpers;
+mod idempotency;
 mod login;
+mod metrics;
+mod migrations;
 mod newsletter;
+mod oidc;
+mod password_reset;
+mod sessions;
 mod subscriptions;
 mod subscriptions_confirm;
+mod totp;
+mod users;
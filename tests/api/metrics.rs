@@ -0,0 +1,124 @@
+//! tests/api/metrics.rs
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use assert2::assert;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn create_confirmed_subscriber(app: &TestApp) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_link = app.get_confirmation_links(&email_request);
+    reqwest::get(confirmation_link.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn metrics_require_the_metrics_token() {
+    let app = spawn_app().await;
+
+    let response = app.get_metrics(None).await;
+    assert!(response.status() == 401);
+
+    let response = app.get_metrics(Some("not-the-token")).await;
+    assert!(response.status() == 401);
+
+    let response = app.get_metrics(Some(&app.metrics_token)).await;
+    assert!(response.status() == 200);
+}
+
+#[tokio::test]
+async fn requests_are_counted_per_route() {
+    let app = spawn_app().await;
+    app.api_client
+        .get(format!("{}/health_check", &app.address))
+        .send()
+        .await
+        .unwrap();
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/issues/{}/stats",
+            &app.address,
+            Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+
+    let metrics = app.get_metrics_text().await;
+
+    assert!(
+        metrics.contains(r#"http_requests_total{method="GET",route="/health_check",status="200"}"#)
+    );
+    // Labelled with the route pattern rather than the path.
+    assert!(metrics.contains(
+        r#"http_requests_total{method="GET",route="/admin/issues/{issue_id}/stats",status="303"}"#
+    ));
+    assert!(
+        metrics.contains(
+            r#"http_request_duration_seconds_bucket{method="GET",route="/health_check","#
+        )
+    );
+}
+
+#[tokio::test]
+async fn database_pool_usage_is_reported() {
+    let app = spawn_app().await;
+
+    let metrics = app.get_metrics_text().await;
+
+    assert!(metrics.contains(r#"db_pool_connections{state="idle"}"#));
+    assert!(metrics.contains(r#"db_pool_connections{state="in_use"}"#));
+}
+
+#[tokio::test]
+async fn deliveries_are_reported() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    assert!(
+        app.get_metrics_text()
+            .await
+            .contains("\nissue_delivery_queue_depth 1\n")
+    );
+
+    app.dispatch_all_pending_emails().await;
+    let metrics = app.get_metrics_text().await;
+    assert!(metrics.contains("\nissue_delivery_queue_depth 0\n"));
+    assert!(metrics.contains(r#"issue_delivery_emails_total{outcome="success"}"#));
+    assert!(
+        metrics.contains(r#"email_provider_request_duration_seconds_count{outcome="success"}"#)
+    );
+}
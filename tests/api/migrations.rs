@@ -0,0 +1,74 @@
+//! tests/api/migrations.rs
+use crate::helpers::{configure_database, create_database, test_configuration};
+use assert2::assert;
+use sqlx::PgPool;
+use zero2prod::migrations::MIGRATOR;
+use zero2prod::startup::Application;
+
+async fn count_applied_migrations(pool: &PgPool) -> i64 {
+    sqlx::query!(r#"SELECT count(*) as "count!" FROM _sqlx_migrations WHERE success"#)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn pending_migrations_are_applied_on_startup() {
+    let configuration = test_configuration();
+    let pool = create_database(&configuration.database).await;
+
+    Application::build(configuration).await.unwrap();
+
+    assert!(count_applied_migrations(&pool).await == MIGRATOR.iter().count() as i64);
+}
+
+#[tokio::test]
+async fn migrations_are_not_applied_on_startup_when_disabled() {
+    let mut configuration = test_configuration();
+    configuration.database.migrate_on_startup = false;
+    let pool = create_database(&configuration.database).await;
+
+    Application::build(configuration).await.unwrap();
+
+    assert!(count_applied_migrations(&pool).await == 0);
+}
+
+#[tokio::test]
+async fn replicas_starting_together_apply_migrations_once() {
+    let configuration = test_configuration();
+    let pool = create_database(&configuration.database).await;
+
+    let (first, second) = tokio::join!(
+        Application::build(configuration.clone()),
+        Application::build(configuration)
+    );
+
+    assert!(first.is_ok());
+    assert!(second.is_ok());
+    assert!(count_applied_migrations(&pool).await == MIGRATOR.iter().count() as i64);
+}
+
+#[tokio::test]
+async fn startup_fails_if_the_schema_is_ahead_of_the_binary() {
+    let configuration = test_configuration();
+    let pool = configure_database(&configuration.database).await;
+    sqlx::query!(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+        VALUES (99991231000000, 'from the future', true, '\\x00', 0)"
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let outcome = Application::build(configuration).await;
+
+    let Err(e) = outcome else {
+        panic!("The application started with a schema ahead of the binary")
+    };
+    assert!(
+        e.to_string()
+            .contains("The database schema is ahead of this binary")
+    );
+    assert!(e.to_string().contains("99991231000000"));
+}
@@ -1,5 +1,7 @@
 //! tests/api/newsletter.rs
-use crate::helpers::{ConfirmationLinks, TestApp, assert_is_redirect_to, spawn_app};
+use crate::helpers::{
+    ConfirmationLinks, TestApp, assert_is_redirect_to, spawn_app, spawn_app_with,
+};
 use assert2::assert;
 use fake::Fake;
 use fake::faker::internet::en::SafeEmail;
@@ -210,3 +212,705 @@ async fn concurrent_form_submission_is_handled_gracefully() {
     app.dispatch_all_pending_emails().await;
     // Mock verifies here when email_server drops at end of function
 }
+
+#[tokio::test]
+async fn opens_and_clicks_are_reported_on_the_issue_stats_page() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": r#"<p>Read <a href="https://example.com/post">this</a></p>"#,
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Act - Part 1 - Find the tracking URLs in the delivered issue
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html_body = body["HtmlBody"].as_str().unwrap();
+    assert!(!html_body.contains("https://example.com/post"));
+    let tracking_url = |kind: &str| {
+        let raw_link = linkify::LinkFinder::new()
+            .links(html_body)
+            .map(|l| l.as_str().to_owned())
+            .find(|l| l.contains(&format!("/tracking/{kind}/")))
+            .unwrap();
+        let mut url = reqwest::Url::parse(&raw_link).unwrap();
+        url.set_port(Some(app.port)).unwrap();
+        url
+    };
+
+    // Act - Part 2 - Open the issue twice and click the link
+    for _ in 0..2 {
+        let response = app
+            .api_client
+            .get(tracking_url("open"))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status() == 200);
+        assert!(response.headers()["Content-Type"] == "image/gif");
+    }
+    let response = app
+        .api_client
+        .get(tracking_url("click"))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "https://example.com/post");
+
+    // Assert
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    let html_page = app.get_issue_stats_html(issue_id).await;
+    assert!(html_page.contains("<p>Unique opens: 1</p>"));
+    assert!(html_page.contains("<tr><td>https://example.com/post</td><td>1</td><td>1</td></tr>"));
+}
+
+#[tokio::test]
+async fn clicks_on_unknown_links_return_a_404() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/tracking/click/{}/{}/0",
+            app.address,
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4()
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    assert!(response.status() == 404);
+}
+
+async fn publish_issue(app: &TestApp) -> uuid::Uuid {
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+}
+
+#[tokio::test]
+async fn paused_issues_are_not_delivered_until_resumed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let issue_id = publish_issue(&app).await;
+
+    // Act - Part 1 - Pause the issue
+    let response = app.post_issue_action(issue_id, "pause").await;
+    assert_is_redirect_to(&response, "/admin/issues");
+    let html_page = app.get_issues_html().await;
+    assert!(html_page.contains("<p><i>Delivery of the issue has been paused.</i></p>"));
+    assert!(html_page.contains("<td>paused</td><td>1</td>"));
+
+    // Act - Part 2 - Nothing goes out while paused
+    {
+        let _mock_guard = Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount_as_scoped(&app.email_server)
+            .await;
+        app.dispatch_all_pending_emails().await;
+    }
+
+    // Act - Part 3 - Resume the issue
+    let response = app.post_issue_action(issue_id, "resume").await;
+    assert_is_redirect_to(&response, "/admin/issues");
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_issues_html().await;
+    assert!(html_page.contains("<td>active</td><td>0</td>"));
+}
+
+#[tokio::test]
+async fn cancelled_issues_are_removed_from_the_delivery_queue() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let issue_id = publish_issue(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_issue_action(issue_id, "cancel").await;
+    assert_is_redirect_to(&response, "/admin/issues");
+    app.dispatch_all_pending_emails().await;
+
+    let html_page = app.get_issues_html().await;
+    assert!(html_page.contains("<p><i>Delivery of the issue has been cancelled.</i></p>"));
+    assert!(html_page.contains("<td>cancelled</td><td>0</td>"));
+
+    // A cancelled issue cannot be resumed
+    app.post_issue_action(issue_id, "resume").await;
+    let html_page = app.get_issues_html().await;
+    assert!(html_page.contains("<p><i>Only paused issues can be resumed.</i></p>"));
+}
+
+#[tokio::test]
+async fn controlling_an_unknown_issue_returns_a_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for action in ["pause", "resume", "cancel"] {
+        let response = app.post_issue_action(uuid::Uuid::new_v4(), action).await;
+
+        assert!(
+            response.status() == 404,
+            "Failed to {action} an unknown issue"
+        );
+    }
+}
+
+#[tokio::test]
+async fn issue_titles_are_escaped_in_the_issue_list() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "<script>alert('title')</script>",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let html_page = app.get_issues_html().await;
+
+    assert!(!html_page.contains("<script>"));
+    assert!(html_page.contains("&lt;script&gt;alert(&#x27;title&#x27;)&lt;/script&gt;"));
+}
+
+#[tokio::test]
+async fn titles_and_links_are_escaped_on_the_issue_stats_page() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "<b>Bold title</b>",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    sqlx::query!(
+        "INSERT INTO newsletter_issue_events
+            (event_id, newsletter_issue_id, subscriber_id, event_type, link_url, occurred_at)
+        VALUES ($1, $2, $3, 'click', 'https://example.com/\"><script>alert(1)</script>', now())",
+        uuid::Uuid::new_v4(),
+        issue_id,
+        uuid::Uuid::new_v4()
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let html_page = app.get_issue_stats_html(issue_id).await;
+
+    assert!(html_page.contains("<h1>&lt;b&gt;Bold title&lt;/b&gt;</h1>"));
+    assert!(!html_page.contains("<script>"));
+    assert!(html_page.contains("https://example.com/&quot;&gt;&lt;script&gt;"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_cancel_an_issue() {
+    let app = spawn_app().await;
+
+    let response = app.post_issue_action(uuid::Uuid::new_v4(), "cancel").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn invalid_issues_are_rejected_and_the_form_is_repopulated() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let test_cases = vec![
+        (
+            serde_json::json!({
+                "title": "",
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p>Newsletter body as HTML</p>",
+            }),
+            "The issue title cannot be empty.",
+        ),
+        (
+            serde_json::json!({
+                "title": "Newsletter title",
+                "text_content": " ",
+                "html_content": "<p>Newsletter body as HTML</p>",
+            }),
+            "The plain text content cannot be empty.",
+        ),
+        (
+            serde_json::json!({
+                "title": "Newsletter title",
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p><b>Newsletter body as HTML</p></b>",
+            }),
+            "The HTML content is not well-formed: expected &lt;/b&gt; but found &lt;/p&gt;.",
+        ),
+    ];
+    for (mut body, error_message) in test_cases {
+        body["idempotency_key"] = uuid::Uuid::new_v4().to_string().into();
+
+        let response = app.post_publish_newsletter(&body).await;
+        assert_is_redirect_to(&response, "/admin/newsletters");
+
+        let html_page = app.get_publish_newsletter_html().await;
+        assert!(html_page.contains(&format!("<p><i>{error_message}</i></p>")));
+        let text_content = body["text_content"].as_str().unwrap();
+        assert!(html_page.contains(&format!(">{text_content}</textarea>")));
+
+        // The draft is only replayed once
+        let html_page = app.get_publish_newsletter_html().await;
+        assert!(!html_page.contains(&format!(">{text_content}</textarea>")));
+    }
+
+    let n_issues = sqlx::query!(r#"SELECT COUNT(*) as "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert!(n_issues == 0);
+}
+
+#[tokio::test]
+async fn issues_up_to_the_maximum_length_can_be_submitted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let csrf_token = app.csrf_token().await;
+
+    for (text_length, message) in [
+        (
+            100_000,
+            "The newsletter issue has been accepted - emails will go out shortly.",
+        ),
+        (
+            100_001,
+            "The plain text content cannot be longer than 100000 characters.",
+        ),
+    ] {
+        // Submitted like a browser would, with the CSRF token in the body
+        let response = app
+            .api_client
+            .post(format!("{}/admin/newsletters", &app.address))
+            .form(&serde_json::json!({
+                "title": "Newsletter title",
+                "text_content": "a".repeat(text_length),
+                "html_content": format!("<p>{}</p>", "a".repeat(99_993)),
+                "idempotency_key": uuid::Uuid::new_v4().to_string(),
+                "csrf_token": &csrf_token,
+            }))
+            .send()
+            .await
+            .unwrap();
+
+        assert_is_redirect_to(&response, "/admin/newsletters");
+        let html_page = app.get_publish_newsletter_html().await;
+        assert!(html_page.contains(&format!("<p><i>{message}</i></p>")));
+    }
+}
+
+#[tokio::test]
+async fn attachments_are_delivered_with_the_issue() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Upload a document and an inline image
+    let response = app
+        .post_attachment("report.pdf", "application/pdf", b"%PDF-1.4".to_vec(), false)
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let response = app
+        .post_attachment("logo.png", "image/png", b"png".to_vec(), true)
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let content_id = sqlx::query!(
+        r#"SELECT content_id as "content_id!" FROM newsletter_issue_attachments WHERE content_id IS NOT NULL"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .content_id;
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("<p><i>logo.png has been attached.</i></p>"));
+    assert!(html_page.contains("report.pdf (application/pdf, 8 bytes)"));
+    assert!(html_page.contains(&format!("<code>cid:{content_id}</code>")));
+
+    // Act - Part 2 - Publish and deliver the issue
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": format!(r#"<p><img src="cid:{content_id}"></p>"#),
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(
+        body["Attachments"]
+            == serde_json::json!([
+                {
+                    "Name": "report.pdf",
+                    "Content": "JVBERi0xLjQ=",
+                    "ContentType": "application/pdf"
+                },
+                {
+                    "Name": "logo.png",
+                    "Content": "cG5n",
+                    "ContentType": "image/png",
+                    "ContentID": format!("cid:{content_id}")
+                }
+            ])
+    );
+
+    // Published attachments are no longer pending
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(!html_page.contains("report.pdf"));
+}
+
+#[tokio::test]
+async fn inline_images_with_the_same_name_get_different_content_ids() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    for content in [b"first".to_vec(), b"second".to_vec()] {
+        let response = app
+            .post_attachment("logo.png", "image/png", content, true)
+            .await;
+        assert_is_redirect_to(&response, "/admin/newsletters");
+    }
+
+    let content_ids: Vec<String> = sqlx::query!(
+        r#"SELECT content_id as "content_id!" FROM newsletter_issue_attachments WHERE content_id IS NOT NULL"#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| r.content_id)
+    .collect();
+    assert!(content_ids.len() == 2);
+    assert!(content_ids[0] != content_ids[1]);
+    let html_page = app.get_publish_newsletter_html().await;
+    for content_id in &content_ids {
+        assert!(html_page.contains(&format!("<code>cid:{content_id}</code>")));
+    }
+}
+
+#[tokio::test]
+async fn attachments_over_the_size_limit_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_attachment(
+            "big.pdf",
+            "application/pdf",
+            vec![0; 5 * 1024 * 1024 + 1],
+            false,
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains(
+        "<p><i>The attachment is too large - files cannot exceed 5242880 bytes.</i></p>"
+    ));
+    assert!(!html_page.contains("big.pdf"));
+}
+
+#[tokio::test]
+async fn concurrent_uploads_cannot_exceed_the_total_size_limit() {
+    let app = spawn_app_with(|c| c.attachments.max_total_size_bytes = 10).await;
+    app.test_user.login(&app).await;
+
+    let (first, second) = tokio::join!(
+        app.post_attachment("first.pdf", "application/pdf", vec![0; 6], false),
+        app.post_attachment("second.pdf", "application/pdf", vec![0; 6], false)
+    );
+    assert_is_redirect_to(&first, "/admin/newsletters");
+    assert_is_redirect_to(&second, "/admin/newsletters");
+
+    let n_attachments =
+        sqlx::query!(r#"SELECT count(*) as "count!" FROM newsletter_issue_attachments"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap()
+            .count;
+    assert!(n_attachments == 1);
+}
+
+#[tokio::test]
+async fn only_images_can_be_inlined() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_attachment("report.pdf", "application/pdf", b"%PDF-1.4".to_vec(), true)
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("<p><i>Only images can be embedded inline.</i></p>"));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_upload_an_attachment() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_attachment("report.pdf", "application/pdf", b"%PDF-1.4".to_vec(), false)
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+fn issue_emails(requests: &[wiremock::Request]) -> Vec<serde_json::Value> {
+    requests
+        .iter()
+        .map(|r| serde_json::from_slice::<serde_json::Value>(&r.body).unwrap())
+        .filter(|body| body.get("Metadata").is_some())
+        .collect()
+}
+
+#[tokio::test]
+async fn the_winning_subject_line_is_sent_to_the_rest_of_the_list() {
+    let app = spawn_app_with(|c| c.ab_testing.test_percentage = 50).await;
+    for _ in 0..4 {
+        create_confirmed_subscriber(&app).await;
+    }
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Publish an issue with a variant subject line
+    let newsletter_request_body = serde_json::json!({
+        "title": "Subject A",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "variant_title": "Subject B",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert - Part 1 - Only the test group received the issue
+    let emails = issue_emails(&app.email_server.received_requests().await.unwrap());
+    assert!(emails.len() == 2);
+    let email_b = emails
+        .iter()
+        .find(|e| e["Subject"] == "Subject B")
+        .expect("Variant B was not sent");
+    assert!(email_b["Metadata"]["variant"] == "b");
+    assert!(email_b["TrackOpens"] == true);
+    assert!(emails.iter().any(|e| e["Subject"] == "Subject A"));
+
+    // Act - Part 2 - The provider reports an open of variant B
+    let response = app
+        .post_postmark_open(
+            &serde_json::json!({
+                "RecordType": "Open",
+                "Recipient": email_b["To"],
+                "Metadata": email_b["Metadata"]
+            }),
+            Some(&app.webhook_token),
+        )
+        .await;
+    assert!(response.status() == 200);
+
+    // Act - Part 3 - The wait window elapses
+    sqlx::query!("UPDATE newsletter_issues SET ab_test_ends_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    zero2prod::ab_testing::finish_ab_tests(&app.db_pool, &app.tracking)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert - Part 3 - The rest of the list received the winner
+    let emails = issue_emails(&app.email_server.received_requests().await.unwrap());
+    assert!(emails.len() == 4);
+    assert!(emails[2..].iter().all(|e| e["Subject"] == "Subject B"));
+
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    let html_page = app.get_issue_stats_html(issue_id).await;
+    assert!(html_page.contains("<tr><td>a</td><td>1</td><td>0</td></tr>"));
+    assert!(html_page.contains("<tr><td>b</td><td>1</td><td>1</td></tr>"));
+}
+
+#[tokio::test]
+async fn a_variant_body_without_a_variant_title_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "variant_text_content": "Another body",
+        "idempotency_key": uuid::Uuid::new_v4().to_string()
+    });
+    let response = app.post_publish_newsletter(&newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("<p><i>A variant body needs a variant subject line.</i></p>"));
+    assert!(html_page.contains(">Another body</textarea>"));
+}
+
+#[tokio::test]
+async fn provider_webhooks_require_the_webhook_token() {
+    let app = spawn_app().await;
+    let body = serde_json::json!({
+        "RecordType": "Open",
+        "Recipient": "ursula_le_guin@gmail.com",
+        "Metadata": { "newsletter_issue_id": uuid::Uuid::new_v4().to_string() }
+    });
+
+    let response = app.post_postmark_open(&body, None).await;
+    assert!(response.status() == 401);
+
+    let response = app.post_postmark_open(&body, Some("wrong-token")).await;
+    assert!(response.status() == 401);
+
+    // Opens of unknown issues are acknowledged
+    let response = app
+        .post_postmark_open(&body, Some(&app.webhook_token))
+        .await;
+    assert!(response.status() == 200);
+}
+
+#[tokio::test]
+async fn opens_are_not_tracked_by_the_provider_when_tracking_is_disabled() {
+    let app = spawn_app_with(|c| c.tracking.enabled = false).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    publish_issue(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let emails = issue_emails(&app.email_server.received_requests().await.unwrap());
+    assert!(emails.len() == 1);
+    assert!(emails[0].get("TrackOpens").is_none());
+}
+
+#[tokio::test]
+async fn reported_opens_are_ignored_when_tracking_is_disabled() {
+    let app = spawn_app_with(|c| c.tracking.enabled = false).await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let issue_id = publish_issue(&app).await;
+    let recipient = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+
+    let response = app
+        .post_postmark_open(
+            &serde_json::json!({
+                "RecordType": "Open",
+                "Recipient": recipient,
+                "Metadata": { "newsletter_issue_id": issue_id.to_string() }
+            }),
+            Some(&app.webhook_token),
+        )
+        .await;
+
+    assert!(response.status() == 200);
+    let row = sqlx::query!(r#"SELECT count(*) as "count!" FROM newsletter_issue_events"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(row.count == 0);
+}
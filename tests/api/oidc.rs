@@ -0,0 +1,299 @@
+//! tests/api/oidc.rs
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app, spawn_app_with};
+use assert2::assert;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const SIGNING_KEY: &str = include_str!("fixtures/oidc_signing_key.pem");
+/// The public part of `SIGNING_KEY`, as published in the provider's JWKS.
+const SIGNING_KEY_MODULUS: &str = "sFGFfnpYaRzPiDLTrVQY1BPQumlL5kS-gSCjOgYnH5wMspDzL5u7IIuqrPsJRTL8OkN8DDshgTpRVqIxOnjJ32RyG33_ukNO0yWEVBC-Yuf7s62u-tn6I1bZwli5nOfoCdu6CciL3TR1Sk5XL_0TtvpG5ADrqhPtA1J1--y2zyAhFqy78x07QtFPTVXR3JQfx0pJQBiPO_icdLh1EL-L6fYGPf2NgFwXO29t1XHiv0iM0wyx_xV2mSvyqhdpYO7VMEjRCR0-8t86rOQD-kzmDyidDCxuW87pCNBsi_6UHVZEbGcmRjbjbGrxELXFPeuX3JTPi40Pb1tQd8DTJN1kpQ";
+const CLIENT_ID: &str = "zero2prod";
+
+/// A local identity provider, serving its discovery document and keys.
+struct MockIdp {
+    server: MockServer,
+}
+
+impl MockIdp {
+    async fn start() -> Self {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/.well-known/openid-configuration"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "issuer": server.uri(),
+                "authorization_endpoint": format!("{}/authorize", server.uri()),
+                "token_endpoint": format!("{}/token", server.uri()),
+                "jwks_uri": format!("{}/jwks", server.uri()),
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/jwks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "keys": [{
+                    "kty": "RSA",
+                    "kid": "test-key",
+                    "use": "sig",
+                    "alg": "RS256",
+                    "n": SIGNING_KEY_MODULUS,
+                    "e": "AQAB",
+                }]
+            })))
+            .mount(&server)
+            .await;
+        Self { server }
+    }
+
+    /// Valid claims for the login started with `authorization_request`.
+    fn claims(
+        &self,
+        authorization_request: &HashMap<String, String>,
+        email: &str,
+    ) -> serde_json::Value {
+        serde_json::json!({
+            "iss": self.server.uri(),
+            "aud": CLIENT_ID,
+            "sub": "248289761001",
+            "exp": chrono::Utc::now().timestamp() + 300,
+            "iat": chrono::Utc::now().timestamp(),
+            "nonce": authorization_request["nonce"],
+            "email": email,
+            "email_verified": true,
+        })
+    }
+
+    /// Answer the token request with an ID token carrying `claims`.
+    async fn issue_id_token(&self, claims: serde_json::Value) {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("test-key".into());
+        let id_token = jsonwebtoken::encode(
+            &header,
+            &claims,
+            &EncodingKey::from_rsa_pem(SIGNING_KEY.as_bytes()).unwrap(),
+        )
+        .unwrap();
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "access-token",
+                "token_type": "Bearer",
+                "id_token": id_token,
+            })))
+            .mount(&self.server)
+            .await;
+    }
+}
+
+async fn spawn_app_with_idp(idp: &MockIdp) -> TestApp {
+    spawn_app_with(|c| {
+        c.oidc.enabled = true;
+        c.oidc.issuer_url = idp.server.uri();
+    })
+    .await
+}
+
+/// Follow the single sign-on link, returning the parameters of the
+/// authorization request the user is sent to the provider with.
+async fn start_login(app: &TestApp) -> HashMap<String, String> {
+    let response = app
+        .api_client
+        .get(format!("{}/login/oidc", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status() == 303);
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    Url::parse(location)
+        .unwrap()
+        .query_pairs()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect()
+}
+
+/// The provider sending the user back.
+async fn callback(app: &TestApp, state: &str) -> reqwest::Response {
+    app.api_client
+        .get(format!("{}/login/oidc/callback", &app.address))
+        .query(&[("code", "authorization-code"), ("state", state)])
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn single_sign_on_is_not_offered_when_disabled() {
+    let app = spawn_app().await;
+
+    assert!(!app.get_login_html().await.contains("/login/oidc"));
+    let response = app
+        .api_client
+        .get(format!("{}/login/oidc", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status() == 404);
+}
+
+#[tokio::test]
+async fn the_login_page_links_to_single_sign_on() {
+    let idp = MockIdp::start().await;
+    let app = spawn_app_with_idp(&idp).await;
+
+    let html_page = app.get_login_html().await;
+
+    assert!(html_page.contains(r#"<a href="/login/oidc">Log in with single sign-on</a>"#));
+}
+
+#[tokio::test]
+async fn users_are_sent_to_the_provider_with_a_pkce_challenge() {
+    let idp = MockIdp::start().await;
+    let app = spawn_app_with_idp(&idp).await;
+
+    let request = start_login(&app).await;
+
+    assert!(request["response_type"] == "code");
+    assert!(request["client_id"] == CLIENT_ID);
+    assert!(request["scope"].split(' ').any(|s| s == "openid"));
+    assert!(request["code_challenge_method"] == "S256");
+    assert!(request["redirect_uri"].ends_with("/login/oidc/callback"));
+    assert!(request["state"].len() >= 32);
+    assert!(request["nonce"].len() >= 32);
+}
+
+#[tokio::test]
+async fn users_are_logged_in_with_their_verified_email() {
+    let idp = MockIdp::start().await;
+    let app = spawn_app_with_idp(&idp).await;
+    let request = start_login(&app).await;
+    idp.issue_id_token(idp.claims(&request, &app.test_user.email))
+        .await;
+
+    let response = callback(&app, &request["state"]).await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert!(app.get_admin_dashboard().await.status() == 200);
+
+    // The code was redeemed with the verifier of the challenge.
+    let token_request = idp
+        .server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|r| r.url.path() == "/token")
+        .unwrap();
+    let form: HashMap<String, String> = serde_urlencoded::from_bytes(&token_request.body).unwrap();
+    assert!(form["grant_type"] == "authorization_code");
+    assert!(form["code"] == "authorization-code");
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(form["code_verifier"].as_bytes()));
+    assert!(challenge == request["code_challenge"]);
+}
+
+#[tokio::test]
+async fn unknown_emails_are_rejected() {
+    let idp = MockIdp::start().await;
+    let app = spawn_app_with_idp(&idp).await;
+    let request = start_login(&app).await;
+    idp.issue_id_token(idp.claims(&request, "someone-else@example.com"))
+        .await;
+
+    let response = callback(&app, &request["state"]).await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert!(
+        app.get_login_html()
+            .await
+            .contains("<p><i>Authentication failed</i></p>")
+    );
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn unverified_emails_are_rejected() {
+    let idp = MockIdp::start().await;
+    let app = spawn_app_with_idp(&idp).await;
+    let request = start_login(&app).await;
+    let mut claims = idp.claims(&request, &app.test_user.email);
+    claims["email_verified"] = false.into();
+    idp.issue_id_token(claims).await;
+
+    let response = callback(&app, &request["state"]).await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn callbacks_for_another_login_are_rejected() {
+    let idp = MockIdp::start().await;
+    let app = spawn_app_with_idp(&idp).await;
+    start_login(&app).await;
+    Mock::given(method("POST"))
+        .and(path("/token"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&idp.server)
+        .await;
+
+    // e.g. a link crafted by an attacker, with their own code
+    let response = callback(&app, "another-state").await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn id_tokens_issued_for_another_login_are_rejected() {
+    let idp = MockIdp::start().await;
+    let app = spawn_app_with_idp(&idp).await;
+    let request = start_login(&app).await;
+    let mut claims = idp.claims(&request, &app.test_user.email);
+    claims["nonce"] = "another-nonce".into();
+    idp.issue_id_token(claims).await;
+
+    let response = callback(&app, &request["state"]).await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn id_tokens_issued_to_another_client_are_rejected() {
+    let idp = MockIdp::start().await;
+    let app = spawn_app_with_idp(&idp).await;
+    let request = start_login(&app).await;
+    let mut claims = idp.claims(&request, &app.test_user.email);
+    claims["aud"] = "another-client".into();
+    idp.issue_id_token(claims).await;
+
+    let response = callback(&app, &request["state"]).await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
+
+#[tokio::test]
+async fn expired_id_tokens_are_rejected() {
+    let idp = MockIdp::start().await;
+    let app = spawn_app_with_idp(&idp).await;
+    let request = start_login(&app).await;
+    let mut claims = idp.claims(&request, &app.test_user.email);
+    claims["exp"] = (chrono::Utc::now().timestamp() - 3600).into();
+    idp.issue_id_token(claims).await;
+
+    let response = callback(&app, &request["state"]).await;
+
+    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&app.get_admin_dashboard().await, "/login");
+}
@@ -0,0 +1,272 @@
+//! tests/api/password_reset.rs
+use crate::helpers::{TestApp, assert_is_redirect_to, spawn_app};
+use assert2::assert;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Ask for a password reset for the test user and return the token that
+/// was emailed to them.
+async fn request_reset_token(app: &TestApp) -> String {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_request_password_reset(&serde_json::json!({
+            "email": &app.test_user.email
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let reset_link = app.get_confirmation_links(email_request).html;
+    reset_link
+        .query_pairs()
+        .find(|(k, _)| k == "reset_token")
+        .unwrap()
+        .1
+        .to_string()
+}
+
+#[tokio::test]
+async fn a_password_can_be_reset_through_the_emailed_link() {
+    let app = spawn_app().await;
+    let reset_token = request_reset_token(&app).await;
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(
+        "<p><i>If an account uses this email, we have sent it a link to reset its password.</i></p>"
+    ));
+
+    // Act - Part 1 - Follow the link
+    let response = reqwest::get(format!(
+        "{}/password-reset/confirm?reset_token={}",
+        app.address, reset_token
+    ))
+    .await
+    .unwrap();
+    assert!(response.status() == 200);
+
+    // Act - Part 2 - Choose a new password
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "reset_token": &reset_token,
+            "new_password": "a-brand-new-password",
+            "new_password_check": "a-brand-new-password"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Your password has been reset, you can now log in.</i></p>"));
+
+    // Act - Part 3 - The old password no longer works, the new one does
+    let response = app.test_user.login(&app).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": "a-brand-new-password"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn reset_links_can_only_be_used_once() {
+    let app = spawn_app().await;
+    let reset_token = request_reset_token(&app).await;
+    app.post_reset_password(&serde_json::json!({
+        "reset_token": &reset_token,
+        "new_password": "a-brand-new-password",
+        "new_password_check": "a-brand-new-password"
+    }))
+    .await;
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "reset_token": &reset_token,
+            "new_password": "another-password",
+            "new_password_check": "another-password"
+        }))
+        .await;
+
+    assert!(response.status() == 401);
+}
+
+#[tokio::test]
+async fn expired_reset_links_are_rejected() {
+    let app = spawn_app().await;
+    let reset_token = request_reset_token(&app).await;
+    sqlx::query!("UPDATE password_resets SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "reset_token": &reset_token,
+            "new_password": "a-brand-new-password",
+            "new_password_check": "a-brand-new-password"
+        }))
+        .await;
+
+    assert!(response.status() == 401);
+}
+
+#[tokio::test]
+async fn unknown_emails_get_the_same_answer_and_no_email() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_request_password_reset(&serde_json::json!({
+            "email": "nobody@example.com"
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(
+        "<p><i>If an account uses this email, we have sent it a link to reset its password.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn new_passwords_must_match() {
+    let app = spawn_app().await;
+    let reset_token = request_reset_token(&app).await;
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "reset_token": &reset_token,
+            "new_password": "a-brand-new-password",
+            "new_password_check": "another-password"
+        }))
+        .await;
+
+    assert_is_redirect_to(
+        &response,
+        &format!("/password-reset/confirm?reset_token={reset_token}"),
+    );
+    let response = app.test_user.login(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn resetting_a_password_ends_existing_sessions() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    assert!(app.get_admin_dashboard().await.status() == 200);
+    let reset_token = request_reset_token(&app).await;
+
+    // The reset happens from another browser
+    let other_browser = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    other_browser
+        .post(format!("{}/password-reset/confirm", &app.address))
+        .form(&serde_json::json!({
+            "reset_token": &reset_token,
+            "new_password": "a-brand-new-password",
+            "new_password_check": "a-brand-new-password"
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Your session has ended, please log in again.</i></p>"));
+}
+
+#[tokio::test]
+async fn breached_passwords_cannot_be_chosen() {
+    let app = spawn_app().await;
+    let reset_token = request_reset_token(&app).await;
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "reset_token": &reset_token,
+            "new_password": "qwerty123456",
+            "new_password_check": "qwerty123456"
+        }))
+        .await;
+
+    let form_url = format!("/password-reset/confirm?reset_token={reset_token}");
+    assert_is_redirect_to(&response, &form_url);
+    let html_page = app
+        .api_client
+        .get(format!("{}{}", app.address, form_url))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(
+        "<p><i>This password has appeared in a data breach, please choose another one.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn reset_tokens_are_not_stored_in_plaintext() {
+    let app = spawn_app().await;
+    let reset_token = request_reset_token(&app).await;
+
+    let row = sqlx::query!("SELECT reset_token_hash FROM password_resets")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(row.reset_token_hash != reset_token);
+}
+
+#[tokio::test]
+async fn a_failed_email_send_gets_the_same_answer() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_request_password_reset(&serde_json::json!({
+            "email": &app.test_user.email
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(
+        "<p><i>If an account uses this email, we have sent it a link to reset its password.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn reset_requests_for_an_account_are_throttled() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(3)
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..5 {
+        let response = app
+            .post_request_password_reset(&serde_json::json!({
+                "email": &app.test_user.email
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/login");
+    }
+}
@@ -0,0 +1,143 @@
+//! tests/api/sessions.rs
+use crate::helpers::{TestApp, TestUser, assert_is_redirect_to, spawn_app, spawn_app_with};
+use assert2::assert;
+use uuid::Uuid;
+
+async fn dashboard_status(app: &TestApp, client: &reqwest::Client) -> reqwest::StatusCode {
+    client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+async fn session_id_by_user_agent(app: &TestApp, user_agent: &str) -> Uuid {
+    sqlx::query!(
+        "SELECT session_id FROM user_sessions WHERE user_agent = $1",
+        user_agent
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .session_id
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_your_sessions() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/sessions", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn sessions_are_listed_with_their_browser() {
+    let app = spawn_app().await;
+    app.other_browser(&app.test_user).await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_sessions_html().await;
+
+    assert!(html_page.contains("<td>zero2prod-tests</td><td>this session</td>"));
+    assert!(html_page.contains("<td>another-browser</td><td><form"));
+}
+
+#[tokio::test]
+async fn revoked_sessions_are_logged_out() {
+    let app = spawn_app().await;
+    let other_browser = app.other_browser(&app.test_user).await;
+    app.test_user.login(&app).await;
+    let session_id = session_id_by_user_agent(&app, "another-browser").await;
+
+    let response = app.post_revoke_session(session_id).await;
+
+    assert_is_redirect_to(&response, "/admin/sessions");
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("<p><i>The session has been revoked.</i></p>"));
+    assert!(!html_page.contains("another-browser"));
+    assert!(dashboard_status(&app, &other_browser).await == 303);
+    assert!(app.get_admin_dashboard().await.status() == 200);
+}
+
+#[tokio::test]
+async fn users_can_log_out_everywhere_else() {
+    let app = spawn_app().await;
+    let other_browser = app.other_browser(&app.test_user).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_revoke_other_sessions().await;
+
+    assert_is_redirect_to(&response, "/admin/sessions");
+    assert!(dashboard_status(&app, &other_browser).await == 303);
+    assert!(app.get_admin_dashboard().await.status() == 200);
+}
+
+#[tokio::test]
+async fn users_cannot_revoke_the_sessions_of_others() {
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    let editor_browser = app.other_browser(&editor).await;
+    app.test_user.login(&app).await;
+    let session_id = session_id_by_user_agent(&app, "another-browser").await;
+
+    app.post_revoke_session(session_id).await;
+
+    assert!(dashboard_status(&app, &editor_browser).await == 200);
+}
+
+#[tokio::test]
+async fn logging_out_revokes_the_session() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    app.post_logout().await;
+
+    let row = sqlx::query!("SELECT revoked_at FROM user_sessions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(row.revoked_at.is_some());
+}
+
+#[tokio::test]
+async fn idle_sessions_expire() {
+    let app = spawn_app_with(|c| c.session.idle_timeout_seconds = 1).await;
+    app.test_user.login(&app).await;
+    assert!(app.get_admin_dashboard().await.status() == 200);
+
+    tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(
+        "<p><i>Your session has expired after a period of inactivity, please log in again.</i></p>"
+    ));
+}
+
+#[tokio::test]
+async fn sessions_expire_after_their_maximum_duration_even_when_active() {
+    let app = spawn_app_with(|c| c.session.absolute_timeout_seconds = 2).await;
+    app.test_user.login(&app).await;
+
+    for _ in 0..2 {
+        tokio::time::sleep(std::time::Duration::from_millis(900)).await;
+        assert!(app.get_admin_dashboard().await.status() == 200);
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(900)).await;
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains(
+        "<p><i>Your session has reached its maximum duration, please log in again.</i></p>"
+    ));
+}
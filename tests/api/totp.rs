@@ -0,0 +1,133 @@
+//! tests/api/totp.rs
+use crate::helpers::{assert_is_redirect_to, spawn_app, totp_code};
+use assert2::assert;
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_enable_two_factor_authentication() {
+    let app = spawn_app().await;
+
+    let response = app.post_totp("123456").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn enrolment_requires_a_valid_code() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let html_page = app.get_totp_html().await;
+    assert!(html_page.contains("data:image/png;base64,"));
+    assert!(html_page.contains("otpauth://totp/"));
+
+    let response = app.post_totp("000000x").await;
+    assert_is_redirect_to(&response, "/admin/totp");
+    let html_page = app.get_totp_html().await;
+    assert!(html_page.contains("<p><i>The code is invalid, please try again.</i></p>"));
+    assert!(!html_page.contains("Two-factor authentication is enabled."));
+}
+
+#[tokio::test]
+async fn the_secret_is_kept_until_the_enrolment_is_confirmed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let first = app.get_totp_html().await;
+    let second = app.get_totp_html().await;
+
+    let uri = |page: &str| page.split("<code>").nth(1).unwrap().to_string();
+    assert!(uri(&first) == uri(&second));
+}
+
+#[tokio::test]
+async fn login_requires_a_totp_code_once_enrolled() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Enrol
+    let (secret, recovery_codes) = app.enable_totp().await;
+    assert!(recovery_codes.len() == 10);
+    let html_page = app.get_totp_html().await;
+    assert!(html_page.contains("Two-factor authentication is enabled."));
+    assert!(html_page.contains("Unused recovery codes: 10"));
+    app.post_logout().await;
+
+    // Act - Part 2 - The password alone is not enough
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login/totp");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - An invalid code
+    let response = app.post_login_totp("000000").await;
+    assert_is_redirect_to(&response, "/login/totp");
+    let html_page = app.get_login_totp_html().await;
+    assert!(html_page.contains("<p><i>Invalid authentication code.</i></p>"));
+
+    // Act - Part 4 - A valid code
+    let code = totp_code(&secret, 1);
+    let response = app.post_login_totp(&code).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert!(app.get_admin_dashboard().await.status() == 200);
+    app.post_logout().await;
+
+    // Act - Part 5 - Codes cannot be replayed
+    app.test_user.login(&app).await;
+    let response = app.post_login_totp(&code).await;
+    assert_is_redirect_to(&response, "/login/totp");
+}
+
+#[tokio::test]
+async fn recovery_codes_can_be_used_once() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (_, recovery_codes) = app.enable_totp().await;
+    app.post_logout().await;
+
+    app.test_user.login(&app).await;
+    let response = app.post_login_totp(&recovery_codes[0].to_uppercase()).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_totp_html().await;
+    assert!(html_page.contains("Unused recovery codes: 9"));
+    app.post_logout().await;
+
+    app.test_user.login(&app).await;
+    let response = app.post_login_totp(&recovery_codes[0]).await;
+    assert_is_redirect_to(&response, "/login/totp");
+}
+
+#[tokio::test]
+async fn too_many_invalid_codes_restart_the_login() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (secret, _) = app.enable_totp().await;
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+
+    for _ in 0..4 {
+        let response = app.post_login_totp("000000").await;
+        assert_is_redirect_to(&response, "/login/totp");
+    }
+    let response = app.post_login_totp("000000").await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Too many invalid codes, please log in again.</i></p>"));
+
+    // The pending login is gone
+    let response = app.post_login_totp(&totp_code(&secret, 1)).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_second_step_requires_the_password_step() {
+    let app = spawn_app().await;
+
+    let response = app.post_login_totp("123456").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,291 @@
+//! tests/api/users.rs
+use crate::helpers::{TestUser, assert_is_redirect_to, spawn_app};
+use assert2::assert;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_users() {
+    let app = spawn_app().await;
+
+    let response = app.get_users().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn only_owners_can_manage_users() {
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    editor.login(&app).await;
+
+    let response = app.get_users().await;
+    assert!(response.status() == 403);
+
+    let response = app
+        .post_invite_user(&serde_json::json!({
+            "username": "someone",
+            "email": "someone@example.com",
+            "role": "owner"
+        }))
+        .await;
+    assert!(response.status() == 403);
+}
+
+#[tokio::test]
+async fn viewers_can_browse_issues_but_not_publish() {
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    viewer.login(&app).await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/issues", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status() == 200);
+
+    let response = app.get_publish_newsletter().await;
+    assert!(response.status() == 403);
+
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert!(response.status() == 403);
+}
+
+#[tokio::test]
+async fn invited_users_choose_a_password_and_get_the_invited_role() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Invite an editor
+    let response = app
+        .post_invite_user(&serde_json::json!({
+            "username": "new-editor",
+            "email": "new-editor@example.com",
+            "role": "editor"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>new-editor has been invited.</i></p>"));
+    assert!(html_page.contains(
+        "<tr><td>new-editor</td><td>new-editor@example.com</td><td>editor</td><td>invited</td>"
+    ));
+    app.post_logout().await;
+
+    // Act - Part 2 - Follow the invitation link
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let invitation_link = app.get_confirmation_links(email_request).html;
+    let invitation_token = invitation_link
+        .query_pairs()
+        .find(|(k, _)| k == "invitation_token")
+        .unwrap()
+        .1
+        .to_string();
+    let response = reqwest::get(invitation_link).await.unwrap();
+    assert!(response.status() == 200);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .contains("Welcome new-editor!")
+    );
+
+    // Act - Part 3 - Choose a password
+    let response = app
+        .post_accept_invitation(&serde_json::json!({
+            "invitation_token": invitation_token,
+            "new_password": "a-new-password",
+            "new_password_check": "a-new-password"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 4 - Log in as the new editor
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "new-editor",
+            "password": "a-new-password"
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert!(app.get_publish_newsletter().await.status() == 200);
+    assert!(app.get_users().await.status() == 403);
+
+    // The invitation can only be used once
+    let response = app
+        .post_accept_invitation(&serde_json::json!({
+            "invitation_token": invitation_token,
+            "new_password": "another-password",
+            "new_password_check": "another-password"
+        }))
+        .await;
+    assert!(response.status() == 401);
+}
+
+#[tokio::test]
+async fn invited_users_cannot_log_in_before_accepting() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_invite_user(&serde_json::json!({
+        "username": "new-viewer",
+        "email": "new-viewer@example.com",
+        "role": "viewer"
+    }))
+    .await;
+    app.post_logout().await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "new-viewer",
+            "password": ""
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn invitation_tokens_are_not_stored_in_plaintext() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_invite_user(&serde_json::json!({
+        "username": "new-viewer",
+        "email": "new-viewer@example.com",
+        "role": "viewer"
+    }))
+    .await;
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let invitation_link = app.get_confirmation_links(email_request).html;
+    let invitation_token = invitation_link
+        .query_pairs()
+        .find(|(k, _)| k == "invitation_token")
+        .unwrap()
+        .1
+        .to_string();
+    let row = sqlx::query!("SELECT invitation_token_hash FROM user_invitations")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(row.invitation_token_hash != invitation_token);
+}
+
+#[tokio::test]
+async fn usernames_must_be_unique() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_invite_user(&serde_json::json!({
+            "username": &app.test_user.username,
+            "email": "someone@example.com",
+            "role": "viewer"
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>A user with this username or email already exists.</i></p>"));
+}
+
+#[tokio::test]
+async fn invitations_with_an_invalid_role_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_invite_user(&serde_json::json!({
+            "username": "someone",
+            "email": "someone@example.com",
+            "role": "superuser"
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>superuser is not a valid role.</i></p>"));
+}
+
+#[tokio::test]
+async fn deactivated_users_cannot_log_in() {
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_deactivate_user(editor.user_id).await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>The user has been deactivated.</i></p>"));
+    assert!(html_page.contains("<td>editor</td><td>deactivated</td>"));
+    app.post_logout().await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &editor.username,
+            "password": &editor.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn deactivation_ends_existing_sessions() {
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    editor.login(&app).await;
+    assert!(app.get_admin_dashboard().await.status() == 200);
+
+    sqlx::query!(
+        "UPDATE users SET active = false WHERE user_id = $1",
+        editor.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn owners_cannot_deactivate_themselves() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_deactivate_user(app.test_user.user_id).await;
+
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("<p><i>You cannot deactivate your own account.</i></p>"));
+    assert!(app.get_admin_dashboard().await.status() == 200);
+}